@@ -0,0 +1,371 @@
+//! x86-64 assembly backend
+//!
+//! Generates GNU as assembly for x86-64 Linux, following the System V ABI,
+//! that `as` and `ld` turn into an executable without a C compiler or libc.
+//! The program starts at `_start`, which calls `main` and passes its result to
+//! the `exit` system call; writes are `write` system calls too.
+//!
+//! Register allocation is simple: the most used temporaries of a function live
+//! in the callee saved registers, the rest in stack slots. Integers are kept
+//! sign extended to 64 bits, so every operation works on whole registers.
+//! [`Inst::Loc`] becomes a `.loc` directive, giving debuggers the source line.
+
+use crate::ir::{BinOp, BlockId, Function, Inst, Module, Temp, Terminator, Type, Value};
+use crate::log::Stage;
+use crate::target::Target;
+use crate::trace;
+
+/// Registers temporaries are allocated to, saved by every function using them
+const SAVED: &[&str] = &["%rbx", "%r12", "%r13", "%r14", "%r15"];
+
+/// Registers the first arguments are passed in
+const ARGS: &[&str] = &["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// Linux system call numbers
+const SYS_WRITE: u32 = 1;
+const SYS_EXIT: u32 = 60;
+
+/// Check whether the assembly backend can generate code for a target
+pub fn supports(target: &Target) -> bool {
+    target.arch == "x86_64" && target.os == "linux"
+}
+
+/// Generate x86-64 assembly out of an IR module
+///
+/// # Parameters
+/// - `&Module` : The module returned from `lower()`
+/// - `&Target` : Platform the assembly will run on, see [`supports`]
+/// - `bool` : Whether to add `.loc` directives pointing back at the source
+///
+/// # Returns
+/// - `String` : The generated assembly
+pub fn genasm(module: &Module, target: &Target, debug_info: bool) -> String {
+    trace!(Stage::Gen, "Generating assembly for {}", target);
+    let mut gen = Gen {
+        module,
+        // Without a file name there is nothing to point at
+        debug: debug_info && !module.file.is_empty(),
+        out: String::new(),
+        name: "",
+        homes: Vec::new(),
+        saved: 0,
+    };
+    gen.module();
+    gen.out
+}
+
+/// Where a temporary lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Home {
+    Reg(&'static str),
+    /// Offset from `%rbp`
+    Stack(i64),
+}
+
+/// State of code generation for a module
+struct Gen<'a> {
+    module: &'a Module,
+    debug: bool,
+    out: String,
+    /// Name of the current function
+    name: &'a str,
+    /// Home of each temporary of the current function
+    homes: Vec<Home>,
+    /// Number of callee saved registers the current function uses
+    saved: usize,
+}
+
+impl<'a> Gen<'a> {
+    fn module(&mut self) {
+        if self.debug {
+            self.inst(&format!(
+                ".file 1 \"{}\"",
+                quote(self.module.file.as_bytes())
+            ));
+        }
+        if !self.module.data.is_empty() {
+            self.inst(".section .rodata");
+        }
+        for (id, data) in self.module.data.iter().enumerate() {
+            self.out.push_str(&format!(".Ld{}:\n", id));
+            // NUL terminated, like the C backend's string literals
+            self.inst(&format!(".asciz \"{}\"", quote(&data.bytes)));
+        }
+
+        self.inst(".text");
+        self.inst(".globl _start");
+        self.inst(".type _start, @function");
+        self.out.push_str("_start:\n");
+        // The outermost frame, debuggers stop unwinding here
+        self.inst("xorl %ebp, %ebp");
+        self.inst("call main");
+        self.inst("movl %eax, %edi");
+        self.inst(&format!("movl ${}, %eax", SYS_EXIT));
+        self.inst("syscall");
+
+        for function in &self.module.functions {
+            trace!(Stage::Gen, "Generating assembly for {}", function.name);
+            self.out.push('\n');
+            self.function(function);
+        }
+
+        // Without it the linker makes the stack executable
+        self.out.push('\n');
+        self.inst(".section .note.GNU-stack,\"\",@progbits");
+    }
+
+    /// Write an instruction or directive
+    fn inst(&mut self, inst: &str) {
+        self.out.push_str(&format!("    {}\n", inst));
+    }
+
+    fn function(&mut self, function: &'a Function) {
+        self.name = &function.name;
+        self.allocate(function);
+
+        // Frame: saved registers right below `%rbp`, then the stack slots,
+        // padded so calls are made with a 16 byte aligned stack
+        let slots = self
+            .homes
+            .iter()
+            .filter(|home| matches!(home, Home::Stack(_)))
+            .count();
+        let frame = 8 * (self.saved + slots);
+        let padding = frame % 16;
+
+        self.inst(&format!(".type {}, @function", function.name));
+        self.out.push_str(&format!("{}:\n", function.name));
+        self.inst("pushq %rbp");
+        self.inst("movq %rsp, %rbp");
+        for reg in &SAVED[..self.saved] {
+            self.inst(&format!("pushq {}", reg));
+        }
+        if slots > 0 || padding > 0 {
+            self.inst(&format!("subq ${}, %rsp", 8 * slots + padding));
+        }
+
+        // Parameters past the sixth are above the return address
+        for (i, param) in function.params.iter().enumerate() {
+            match ARGS.get(i) {
+                Some(reg) => self.inst(&format!("movq {}, %rax", reg)),
+                None => self.inst(&format!("movq {}(%rbp), %rax", 16 + 8 * (i - ARGS.len()))),
+            }
+            self.store(*param, function.temps[param.0]);
+        }
+
+        for (id, block) in function.blocks.iter().enumerate() {
+            self.out
+                .push_str(&format!("{}:\n", self.label(BlockId(id))));
+            for inst in &block.insts {
+                self.gen_inst(function, inst);
+            }
+            self.terminator(&block.term, BlockId(id + 1));
+        }
+        self.inst(&format!(".size {}, .-{}", function.name, function.name));
+    }
+
+    /// Give the most used temporaries a register, the others a stack slot
+    fn allocate(&mut self, function: &Function) {
+        let mut uses = vec![0usize; function.temps.len()];
+        for param in &function.params {
+            uses[param.0] += 1;
+        }
+        for block in &function.blocks {
+            let insts = block.insts.iter();
+            let values = insts
+                .flat_map(|inst| {
+                    inst.operands()
+                        .into_iter()
+                        .chain(inst.dest().map(Value::Temp))
+                })
+                .chain(block.term.operand());
+            for value in values {
+                if let Value::Temp(temp) = value {
+                    uses[temp.0] += 1;
+                }
+            }
+        }
+        let mut ranked: Vec<usize> = (0..function.temps.len()).collect();
+        ranked.sort_by_key(|temp| std::cmp::Reverse(uses[*temp]));
+
+        self.homes = vec![Home::Stack(0); function.temps.len()];
+        self.saved = ranked.len().min(SAVED.len());
+        for (rank, temp) in ranked.into_iter().enumerate() {
+            self.homes[temp] = match SAVED.get(rank) {
+                Some(reg) => Home::Reg(reg),
+                None => Home::Stack(-8 * (self.saved + rank - SAVED.len() + 1) as i64),
+            };
+        }
+    }
+
+    fn label(&self, id: BlockId) -> String {
+        format!(".L{}_bb{}", self.name, id.0)
+    }
+
+    fn gen_inst(&mut self, function: &Function, inst: &Inst) {
+        match inst {
+            Inst::Loc { line, col } => {
+                if self.debug {
+                    self.inst(&format!(".loc 1 {} {}", line, col));
+                }
+            }
+            Inst::Copy { dest, value } => {
+                self.load(value, "%rax");
+                self.store(*dest, function.temps[dest.0]);
+            }
+            Inst::Binary { dest, op, lhs, rhs } => {
+                self.load(lhs, "%rax");
+                self.load(rhs, "%rcx");
+                match op {
+                    BinOp::Add => self.inst("addq %rcx, %rax"),
+                    BinOp::Sub => self.inst("subq %rcx, %rax"),
+                    BinOp::Mul => self.inst("imulq %rcx, %rax"),
+                    BinOp::Div | BinOp::Rem => {
+                        self.inst("cqto");
+                        self.inst("idivq %rcx");
+                        if *op == BinOp::Rem {
+                            self.inst("movq %rdx, %rax");
+                        }
+                    }
+                    _ => {
+                        self.inst("cmpq %rcx, %rax");
+                        self.inst(&format!("{} %al", set(*op)));
+                        self.inst("movzbq %al, %rax");
+                    }
+                }
+                self.store(*dest, function.temps[dest.0]);
+            }
+            Inst::Call {
+                dest,
+                function: callee,
+                args,
+            } => {
+                // Arguments past the sixth go on the stack, right to left,
+                // keeping it 16 byte aligned
+                let stacked = args.len().saturating_sub(ARGS.len());
+                let padding = stacked % 2;
+                if padding > 0 {
+                    self.inst("subq $8, %rsp");
+                }
+                for arg in args.iter().skip(ARGS.len()).rev() {
+                    self.load(arg, "%rax");
+                    self.inst("pushq %rax");
+                }
+                for (arg, reg) in args.iter().zip(ARGS) {
+                    self.load(arg, reg);
+                }
+                self.inst(&format!("call {}", callee));
+                if stacked + padding > 0 {
+                    self.inst(&format!("addq ${}, %rsp", 8 * (stacked + padding)));
+                }
+                if let Some(dest) = dest {
+                    self.store(*dest, function.temps[dest.0]);
+                }
+            }
+            Inst::Write { fd, data, len } => {
+                self.load(fd, "%rdi");
+                self.load(data, "%rsi");
+                self.load(len, "%rdx");
+                self.inst(&format!("movl ${}, %eax", SYS_WRITE));
+                self.inst("syscall");
+            }
+        }
+    }
+
+    fn terminator(&mut self, term: &Terminator, next: BlockId) {
+        match term {
+            Terminator::Return(value) => {
+                if let Some(value) = value {
+                    self.load(value, "%rax");
+                }
+                // Restore the saved registers and the caller's frame
+                if self.saved > 0 {
+                    self.inst(&format!("leaq -{}(%rbp), %rsp", 8 * self.saved));
+                }
+                for reg in SAVED[..self.saved].iter().rev() {
+                    self.inst(&format!("popq {}", reg));
+                }
+                self.inst("popq %rbp");
+                self.inst("ret");
+            }
+            // Falling through to the next block needs no jump
+            Terminator::Jump(to) if *to == next => {}
+            Terminator::Jump(to) => {
+                let label = self.label(*to);
+                self.inst(&format!("jmp {}", label));
+            }
+            Terminator::Branch {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.load(cond, "%rax");
+                self.inst("testq %rax, %rax");
+                let (then, otherwise) = (self.label(*then), *otherwise);
+                self.inst(&format!("jne {}", then));
+                if otherwise != next {
+                    let otherwise = self.label(otherwise);
+                    self.inst(&format!("jmp {}", otherwise));
+                }
+            }
+        }
+    }
+
+    /// Put a value into a register
+    fn load(&mut self, value: &Value, reg: &str) {
+        match value {
+            Value::Int(value, ty) => {
+                let value = if *ty == Type::I32 {
+                    i64::from(*value as i32)
+                } else {
+                    *value
+                };
+                if i32::try_from(value).is_ok() {
+                    self.inst(&format!("movq ${}, {}", value, reg));
+                } else {
+                    self.inst(&format!("movabsq ${}, {}", value, reg));
+                }
+            }
+            Value::Temp(temp) => match self.homes[temp.0] {
+                Home::Reg(home) => self.inst(&format!("movq {}, {}", home, reg)),
+                Home::Stack(offset) => self.inst(&format!("movq {}(%rbp), {}", offset, reg)),
+            },
+            Value::Data(data) => self.inst(&format!("leaq .Ld{}(%rip), {}", data.0, reg)),
+        }
+    }
+
+    /// Move `%rax` into a temporary, wrapping it to the temporary's type
+    fn store(&mut self, dest: Temp, ty: Type) {
+        if ty == Type::I32 {
+            self.inst("movslq %eax, %rax");
+        }
+        match self.homes[dest.0] {
+            Home::Reg(home) => self.inst(&format!("movq %rax, {}", home)),
+            Home::Stack(offset) => self.inst(&format!("movq %rax, {}(%rbp)", offset)),
+        }
+    }
+}
+
+/// `setcc` instruction of a comparison
+fn set(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Eq => "sete",
+        BinOp::Ne => "setne",
+        BinOp::Lt => "setl",
+        BinOp::Le => "setle",
+        BinOp::Gt => "setg",
+        _ => "setge",
+    }
+}
+
+/// Escape bytes for a GNU as string, anything but printable ASCII becomes an
+/// octal escape
+fn quote(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            0x20..=0x7e if byte != b'"' && byte != b'\\' => char::from(byte).to_string(),
+            _ => format!("\\{:03o}", byte),
+        })
+        .collect()
+}
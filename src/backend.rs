@@ -0,0 +1,111 @@
+//! Code generators neitc can build executables with
+//!
+//! Every backend starts from the same optimized IR. The C backend hands its
+//! output to a C compiler, the others produce code for lower level tools,
+//! giving neitc control the C compiler would otherwise keep to itself.
+
+use std::fmt;
+
+use crate::asm;
+use crate::qbe;
+use crate::target::Target;
+use crate::wasm;
+
+/// Where the IR goes, `--backend`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// C, built by a C compiler
+    #[default]
+    C,
+    /// Textual LLVM IR, built by clang or llc
+    Llvm,
+    /// x86-64 assembly for Linux, built by as and ld
+    Asm,
+    /// QBE IL, compiled by qbe and as, linked by a C compiler
+    Qbe,
+    /// WebAssembly text for WASI, built by wat2wasm or wasm-tools
+    Wasm,
+}
+
+/// Every backend, in the order `--help` lists them
+pub const BACKENDS: &[Backend] = &[
+    Backend::C,
+    Backend::Llvm,
+    Backend::Asm,
+    Backend::Qbe,
+    Backend::Wasm,
+];
+
+impl Backend {
+    /// Parse the value of `--backend`
+    ///
+    /// # Parameters
+    /// - `&str` : Name of the backend, e.g. `llvm`
+    ///
+    /// # Returns
+    /// - `Result<Backend, String>` : The backend, or the name that isn't one
+    pub fn parse(name: &str) -> Result<Backend, String> {
+        BACKENDS
+            .iter()
+            .copied()
+            .find(|backend| backend.name() == name)
+            .ok_or_else(|| name.to_string())
+    }
+
+    /// Name used by `--backend` and `--stage`
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::C => "c",
+            Backend::Llvm => "llvm",
+            Backend::Asm => "asm",
+            Backend::Qbe => "qbe",
+            Backend::Wasm => "wasm",
+        }
+    }
+
+    /// Extension of the files holding the generated code
+    pub fn extension(self) -> &'static str {
+        match self {
+            Backend::C => "c",
+            Backend::Llvm => "ll",
+            Backend::Asm => "s",
+            Backend::Qbe => "ssa",
+            Backend::Wasm => "wat",
+        }
+    }
+
+    /// Platforms the backend can generate code for, in words
+    pub fn platforms(self) -> &'static str {
+        match self {
+            Backend::C | Backend::Llvm => "every platform",
+            Backend::Asm => "x86_64 Linux",
+            Backend::Qbe => "x86_64, aarch64 and riscv64 outside Windows",
+            Backend::Wasm => "wasm32-wasi",
+        }
+    }
+
+    /// Check whether the backend can generate code for `target`
+    pub fn supports(self, target: &Target) -> bool {
+        match self {
+            Backend::C | Backend::Llvm => true,
+            Backend::Asm => asm::supports(target),
+            Backend::Qbe => qbe::qbe_target(target).is_some(),
+            Backend::Wasm => wasm::supports(target),
+        }
+    }
+
+    /// Target to generate code for when `--target` isn't given, the host
+    /// unless the backend only has one platform that can't be it
+    pub fn default_target(self) -> Target {
+        match self {
+            Backend::Wasm => Target::parse("wasm32-wasi").unwrap_or_default(),
+            _ => Target::host(),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,513 @@
+//! A small C syntax tree and its printer
+//!
+//! The code generator builds a [`Unit`] instead of pasting strings together.
+//! Printing it takes care of indentation, braces and parentheses: an
+//! expression is only parenthesized where C's precedence rules would
+//! otherwise read it differently, so the output is valid and formatted the
+//! same way however deeply the code nests.
+
+use std::fmt;
+
+/// Spaces per level of indentation
+const INDENT: usize = 4;
+
+/// A C type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CType {
+    Void,
+    Char,
+    Int,
+    LongLong,
+    SizeT,
+    /// `const` applied to the type
+    Const(Box<CType>),
+    /// Pointer to the type
+    Ptr(Box<CType>),
+    /// Array of unknown size of the type, as in `char *argv[]`
+    Array(Box<CType>),
+}
+
+impl CType {
+    /// `const char *`, what string literals are used as
+    pub fn const_char_ptr() -> CType {
+        CType::Ptr(Box::new(CType::Const(Box::new(CType::Char))))
+    }
+
+    /// Declare `name` with this type, e.g. `const char *argv[]`
+    ///
+    /// # Parameters
+    /// - `&str` : The declared name, empty for an abstract declarator like in a cast
+    ///
+    /// # Returns
+    /// - `String` : The declaration
+    pub fn declare(&self, name: &str) -> String {
+        let base = match self {
+            CType::Void => "void",
+            CType::Char => "char",
+            CType::Int => "int",
+            CType::LongLong => "long long",
+            CType::SizeT => "size_t",
+            CType::Ptr(inner) => {
+                // A pointer to an array needs parentheses, `char (*p)[]`
+                let declarator = if matches!(**inner, CType::Array(_)) {
+                    format!("(*{})", name)
+                } else {
+                    format!("*{}", name)
+                };
+                return inner.declare(&declarator);
+            }
+            CType::Array(inner) => return inner.declare(&format!("{}[]", name)),
+            // A constant pointer is `char *const p`
+            CType::Const(inner) if matches!(**inner, CType::Ptr(_)) => {
+                return inner.declare(format!("const {}", name).trim_end());
+            }
+            CType::Const(inner) => return format!("const {}", inner.declare(name)),
+        };
+        if name.is_empty() {
+            base.to_string()
+        } else {
+            format!("{} {}", base, name)
+        }
+    }
+}
+
+/// Binary operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+impl BinOp {
+    fn token(self) -> &'static str {
+        match self {
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 13,
+            BinOp::Add | BinOp::Sub => 12,
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 10,
+            BinOp::Eq | BinOp::Ne => 9,
+            BinOp::And => 5,
+            BinOp::Or => 4,
+        }
+    }
+}
+
+/// Prefix operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+    Deref,
+    AddrOf,
+}
+
+impl UnOp {
+    fn token(self) -> &'static str {
+        match self {
+            UnOp::Neg => "-",
+            UnOp::Not => "!",
+            UnOp::Deref => "*",
+            UnOp::AddrOf => "&",
+        }
+    }
+}
+
+/// Precedence of unary operators and casts
+const UNARY: u8 = 14;
+/// Precedence of calls, and of anything that never needs parentheses
+const POSTFIX: u8 = 15;
+/// Precedence of assignments, which group right to left
+const ASSIGN: u8 = 2;
+
+/// An expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// An integer literal, `long long` when it doesn't fit an `int`
+    Int(i64),
+    /// A string literal holding these bytes
+    Str(Vec<u8>),
+    /// A variable or function name
+    Ident(String),
+    Call(String, Vec<Expr>),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Assign(Box<Expr>, Box<Expr>),
+    Cast(CType, Box<Expr>),
+}
+
+impl Expr {
+    pub fn ident(name: impl Into<String>) -> Expr {
+        Expr::Ident(name.into())
+    }
+
+    pub fn binary(op: BinOp, lhs: Expr, rhs: Expr) -> Expr {
+        Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn assign(lhs: Expr, rhs: Expr) -> Expr {
+        Expr::Assign(Box::new(lhs), Box::new(rhs))
+    }
+
+    /// How tightly the expression binds, higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            // `-1` is the negation of `1`
+            Expr::Int(value) if *value < 0 => UNARY,
+            Expr::Int(_) | Expr::Str(_) | Expr::Ident(_) | Expr::Call(..) => POSTFIX,
+            Expr::Unary(..) | Expr::Cast(..) => UNARY,
+            Expr::Binary(op, ..) => op.precedence(),
+            Expr::Assign(..) => ASSIGN,
+        }
+    }
+
+    /// Write the expression, parenthesized if it binds looser than `min`
+    fn write(&self, min: u8, out: &mut String) {
+        let parens = self.precedence() < min;
+        if parens {
+            out.push('(');
+        }
+        match self {
+            Expr::Int(value) => out.push_str(&int_literal(*value)),
+            Expr::Str(bytes) => out.push_str(&string_literal(bytes)),
+            Expr::Ident(name) => out.push_str(name),
+            Expr::Call(function, args) => {
+                out.push_str(function);
+                out.push('(');
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    // Anything binding tighter than the comma operator
+                    arg.write(ASSIGN, out);
+                }
+                out.push(')');
+            }
+            Expr::Unary(op, operand) => {
+                out.push_str(op.token());
+                // `- -x` and `& &x` must not merge into `--x` and `&&x`
+                let mut operand_out = String::new();
+                operand.write(UNARY, &mut operand_out);
+                if operand_out.starts_with(op.token()) {
+                    out.push(' ');
+                }
+                out.push_str(&operand_out);
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                // Left to right, so only the right operand needs parentheses
+                // at the same precedence
+                lhs.write(op.precedence(), out);
+                out.push_str(&format!(" {} ", op.token()));
+                rhs.write(op.precedence() + 1, out);
+            }
+            Expr::Assign(lhs, rhs) => {
+                lhs.write(UNARY, out);
+                out.push_str(" = ");
+                rhs.write(ASSIGN, out);
+            }
+            Expr::Cast(ty, operand) => {
+                out.push_str(&format!("({})", ty.declare("")));
+                operand.write(UNARY, out);
+            }
+        }
+        if parens {
+            out.push(')');
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        self.write(0, &mut out);
+        f.write_str(&out)
+    }
+}
+
+/// C spelling of an integer, `long long` when it doesn't fit an `int`
+fn int_literal(value: i64) -> String {
+    if value == i64::MIN {
+        // The literal itself would be too large before being negated
+        return format!("({}LL - 1)", value + 1);
+    }
+    if i32::try_from(value).is_ok() {
+        value.to_string()
+    } else {
+        format!("{}LL", value)
+    }
+}
+
+/// Write bytes as a C string literal, escaping what can't appear in one as is
+fn string_literal(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                '\r' => out.push_str("\\r"),
+                // `??` could start a trigraph
+                '?' if out.ends_with('?') => out.push_str("\\?"),
+                c if c.is_ascii_control() => out.push_str(&format!("\\{:03o}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\{:03o}", byte));
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+    /// A local variable, optionally initialized
+    Decl {
+        ty: CType,
+        name: String,
+        init: Option<Expr>,
+    },
+    Expr(Expr),
+    Return(Option<Expr>),
+    /// A label `goto` can jump to
+    Label(String),
+    Goto(String),
+    /// `if`, the `else` part left out when empty
+    If {
+        cond: Expr,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+    While {
+        cond: Expr,
+        body: Vec<Stmt>,
+    },
+    /// A nested `{ ... }` block
+    Block(Vec<Stmt>),
+    /// `#line`, the statements after it come from this line of `file`
+    Line { line: usize, file: String },
+}
+
+/// A function signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub ret: CType,
+    pub name: String,
+    pub params: Vec<(CType, String)>,
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(ty, name)| ty.declare(name))
+            .collect();
+        let params = if params.is_empty() {
+            String::from("void")
+        } else {
+            params.join(", ")
+        };
+        let declarator = format!("{}({})", self.name, params);
+        f.write_str(&self.ret.declare(&declarator))
+    }
+}
+
+/// A top level item of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    /// C code written by hand, like the prelude helpers, printed as is
+    Verbatim(String),
+    /// A function declaration
+    Prototype(Signature),
+    Function(Signature, Vec<Stmt>),
+}
+
+/// A whole C file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Unit {
+    /// Headers, like `<unistd.h>`
+    pub includes: Vec<String>,
+    pub items: Vec<Item>,
+}
+
+impl Unit {
+    /// Include `header`, unless it already is
+    pub fn include(&mut self, header: &str) {
+        if !self.includes.iter().any(|include| include == header) {
+            self.includes.push(header.to_string());
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::default();
+        for include in &self.includes {
+            printer.line(&format!("#include {}", include));
+        }
+        let mut previous: Option<&Item> = None;
+        for item in &self.items {
+            // Prototypes are kept together, everything else is set apart
+            let together = matches!(
+                (previous, item),
+                (Some(Item::Prototype(_)), Item::Prototype(_))
+            );
+            if !together && (previous.is_some() || !self.includes.is_empty()) {
+                printer.out.push('\n');
+            }
+            printer.item(item);
+            previous = Some(item);
+        }
+        f.write_str(&printer.out)
+    }
+}
+
+/// Writes out the tree, keeping track of the indentation
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn line(&mut self, line: &str) {
+        self.out.push_str(&" ".repeat(self.depth * INDENT));
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Verbatim(code) => {
+                self.out.push_str(code);
+                if !code.ends_with('\n') {
+                    self.out.push('\n');
+                }
+            }
+            Item::Prototype(signature) => self.line(&format!("{};", signature)),
+            Item::Function(signature, body) => {
+                self.line(&format!("{} {{", signature));
+                self.block(body);
+                self.line("}");
+            }
+        }
+    }
+
+    /// Write the statements of a `{ ... }` block, without the braces
+    fn block(&mut self, stmts: &[Stmt]) {
+        self.depth += 1;
+        for (i, stmt) in stmts.iter().enumerate() {
+            self.stmt(stmt);
+            // A label has to be followed by a statement, a directive isn't one
+            let rest = &stmts[i + 1..];
+            if matches!(stmt, Stmt::Label(_))
+                && rest.iter().all(|stmt| matches!(stmt, Stmt::Line { .. }))
+            {
+                self.line(";");
+            }
+        }
+        self.depth -= 1;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Decl { ty, name, init } => match init {
+                Some(init) => self.line(&format!("{} = {};", ty.declare(name), init_expr(init))),
+                None => self.line(&format!("{};", ty.declare(name))),
+            },
+            Stmt::Expr(expr) => self.line(&format!("{};", expr)),
+            Stmt::Return(Some(expr)) => self.line(&format!("return {};", expr)),
+            Stmt::Return(None) => self.line("return;"),
+            Stmt::Label(label) => {
+                // Labels stick out one level from the code around them
+                let depth = self.depth;
+                self.depth = depth.saturating_sub(1);
+                self.line(&format!("{}:", label));
+                self.depth = depth;
+            }
+            Stmt::Goto(label) => self.line(&format!("goto {};", label)),
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.line(&format!("if ({}) {{", cond));
+                self.block(then);
+                let mut otherwise = otherwise;
+                // `else if` chains stay flat
+                while let [Stmt::If {
+                    cond,
+                    then,
+                    otherwise: rest,
+                }] = otherwise.as_slice()
+                {
+                    self.line(&format!("}} else if ({}) {{", cond));
+                    self.block(then);
+                    otherwise = rest;
+                }
+                if !otherwise.is_empty() {
+                    self.line("} else {");
+                    self.block(otherwise);
+                }
+                self.line("}");
+            }
+            Stmt::While { cond, body } => {
+                self.line(&format!("while ({}) {{", cond));
+                self.block(body);
+                self.line("}");
+            }
+            Stmt::Block(stmts) => {
+                self.line("{");
+                self.block(stmts);
+                self.line("}");
+            }
+            // Directives always start at the beginning of the line
+            Stmt::Line { line, file } => {
+                self.out.push_str(&format!(
+                    "#line {} {}\n",
+                    line,
+                    string_literal(file.as_bytes())
+                ));
+            }
+        }
+    }
+}
+
+/// An initializer, which can't be a comma expression
+fn init_expr(expr: &Expr) -> String {
+    let mut out = String::new();
+    expr.write(ASSIGN, &mut out);
+    out
+}
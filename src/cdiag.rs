@@ -0,0 +1,161 @@
+//! Errors of the C compiler, translated back into Neit diagnostics
+//!
+//! gcc and clang both report problems as `file:line:col: severity: message`,
+//! followed by `note:` lines and a copy of the offending code. Thanks to the
+//! `#line` directives in the generated C, `file:line` usually names a line of
+//! the Neit source, which the translated diagnostic then points at. Since
+//! neitc only hands the compiler code it generated itself, any such error
+//! means the generated C is invalid, which is a bug in neitc. The same goes
+//! for the tools building the other backends' output.
+
+use crate::diag::{Diagnostic, Severity, SourceFile, Span};
+
+/// One `file:line:col: severity: message` line of compiler output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// File the message points into, or the program reporting it like `cc1`
+    pub file: String,
+    /// 1 based line, `None` for messages about the whole compilation
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+    pub severity: Severity,
+    pub text: String,
+}
+
+/// Parse one line of gcc or clang output
+///
+/// # Parameters
+/// - `&str` : A line of the compiler's stderr
+///
+/// # Returns
+/// - `Option<Message>` : The message, `None` for code excerpts, carets and
+///   anything else that isn't one
+pub fn parse_line(line: &str) -> Option<Message> {
+    let severities = [
+        ("fatal error", Severity::Error),
+        ("error", Severity::Error),
+        ("warning", Severity::Warning),
+        ("note", Severity::Note),
+        // GNU as capitalizes them
+        ("Error", Severity::Error),
+        ("Warning", Severity::Warning),
+    ];
+    // The location may contain ": " itself, so find the severity first
+    let (at, name, severity) = severities
+        .iter()
+        .filter_map(|(name, severity)| {
+            line.find(&format!(": {}: ", name))
+                .map(|at| (at, *name, *severity))
+        })
+        .min_by_key(|(at, ..)| *at)?;
+    let text = line[at + name.len() + 4..].trim().to_string();
+
+    let (file, line, col) = split_location(&line[..at]);
+    Some(Message {
+        file,
+        line,
+        col,
+        severity,
+        text,
+    })
+}
+
+/// Split `file:line:col`, `file:line` or just `file` into its parts
+fn split_location(location: &str) -> (String, Option<usize>, Option<usize>) {
+    let mut file = location;
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 {
+        match file
+            .rsplit_once(':')
+            .map(|(rest, n)| (rest, n.parse::<usize>()))
+        {
+            Some((rest, Ok(n))) => {
+                numbers.insert(0, n);
+                file = rest;
+            }
+            _ => break,
+        }
+    }
+    (
+        file.trim().to_string(),
+        numbers.first().copied(),
+        numbers.get(1).copied(),
+    )
+}
+
+/// Translate the output of a failed C compilation into diagnostics
+///
+/// # Parameters
+/// - `&str` : Everything the compiler wrote to stderr
+/// - `&SourceFile` : The Neit source the C was generated from
+/// - `&str` : Name of the compiler, for the notes
+///
+/// # Returns
+/// - `Vec<Diagnostic>` : One diagnostic per error or warning, with the
+///   compiler's notes attached, always holding at least one error
+pub fn translate(stderr: &str, file: &SourceFile, compiler: &str) -> Vec<Diagnostic> {
+    let mut diags: Vec<Diagnostic> = Vec::new();
+    // Notes belong to the message right before them, if it was kept
+    let mut attach = false;
+    for message in stderr.lines().filter_map(parse_line) {
+        if message.severity == Severity::Note {
+            if let Some(diag) = diags.last_mut().filter(|_| attach) {
+                diag.notes.push(message.text);
+            }
+            continue;
+        }
+        // Messages without a line are summaries like `ld returned 1 exit status`
+        attach = message.line.is_some();
+        if attach {
+            diags.push(diagnostic(message, file, compiler));
+        }
+    }
+
+    // Linker errors and the like have no location to point at, show them as is
+    if !diags.iter().any(Diagnostic::is_error) {
+        diags.push(
+            Diagnostic::error(format!("Compilation with {} failed", compiler)).with_note(format!(
+                "backend error, {} said:\n{}",
+                compiler,
+                stderr.trim_end()
+            )),
+        );
+    }
+    diags
+}
+
+/// Diagnostic for a message with a location
+fn diagnostic(message: Message, file: &SourceFile, compiler: &str) -> Diagnostic {
+    let diag = Diagnostic::new(message.severity, message.text).with_note(format!(
+        "backend error, reported by {} on the generated code",
+        compiler
+    ));
+    let line = message.line.unwrap_or(1);
+    let mapped = message.file == file.name
+        && !file.name.is_empty()
+        && line <= file.text.lines().count().max(1);
+    let diag = if mapped {
+        // Columns are those of the generated C, only the line carries over
+        let span = file.line_span(line);
+        let text = &file.text[span.start..span.end];
+        let indent = text.len() - text.trim_start().len();
+        let end = span.start + text.trim_end().len();
+        diag.with_label(
+            Span::new(span.start + indent, end.max(span.start + indent)),
+            "the C generated for this line",
+        )
+    } else {
+        let col = message
+            .col
+            .map(|col| format!(":{}", col))
+            .unwrap_or_default();
+        diag.with_note(format!("at {}:{}{}", message.file, line, col))
+    };
+    if message.severity == Severity::Error {
+        diag.with_help(
+            "this is a neitc bug, please report it along with the code kept by '--keep-c'",
+        )
+    } else {
+        diag
+    }
+}
@@ -0,0 +1,34 @@
+use crate::parse1::{items, Item, ParseError, AST};
+
+/// Check a parsed AST for errors the parser can't see on its own
+///
+/// # Parameters
+/// - `&[AST]` : The AST returned from `parse()`
+///
+/// # Returns
+/// - `Result<(), Vec<ParseError>>` : Every error found in the program
+pub fn check(ast: &[AST]) -> Result<(), Vec<ParseError>> {
+    let mut errors = Vec::new();
+    checkci(ast, &mut errors);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(())
+}
+
+/// `__wrt__` and friends are lowered to `<unistd.h>` calls, so they need `cimport cstd`
+fn checkci(ast: &[AST], errors: &mut Vec<ParseError>) {
+    let ast = items(ast);
+    let first_use = ast.iter().find_map(|node| match node {
+        AST::CWrt(wrt) => Some(wrt.span),
+        _ => None,
+    });
+    // A broken import could have been meant as `cimport cstd`, don't pile on
+    let cif = ast.iter().any(|node| {
+        matches!(node, AST::Cimport(lib, _) if lib == "cstd")
+            || matches!(node, AST::Poisoned(Item::Import, _))
+    });
+    if let (Some(span), false) = (first_use, cif) {
+        errors.push(ParseError::NoCimport { span });
+    }
+}
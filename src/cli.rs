@@ -0,0 +1,277 @@
+use neitc::{
+    backend::Backend,
+    lint::{LintLevel, LintLevels},
+    log::{self, Level},
+    opt::OptLevel,
+    report::ErrorFormat,
+};
+
+pub const HELP: &str = "Usage: neitc <command> <input_file> [options]\n\n\
+Commands:\n\
+    build                         Compile to an executable (the default when no command is given)\n\
+    run [-- <args>...]            Build to a temporary directory and run it, forwarding <args>\n\
+    check                         Parse and check the program without generating code\n\
+    emit --stage=<stage>          Print a compiler stage to stdout: tokens, ast, ir, ir-unopt, c, llvm, asm, qbe or wasm\n\
+    fix [--dry-run]               Apply the suggested fixes to the file, or print them as a diff\n\
+    --explain <code>              Explain an error code, e.g. N0003\n\n\
+Options:\n\
+    --backend=<backend>           Generate code through c (default), llvm (built by clang or llc), asm (x86_64 Linux, built by as and ld), qbe (built by qbe and as) or wasm (wasm32-wasi, built by wat2wasm or wasm-tools)\n\
+    --bcompiler, -bc <compiler>   Specify the C compiler (default: first of $CC, clang, gcc, cc, tcc)\n\
+    --output, -o <file>           Name of the executable to build (default: the input file name)\n\
+    --target <triple>             Build for another platform, e.g. x86_64-pc-windows-gnu (default: the host, wasm32-wasi with --backend=wasm)\n\
+    --emit-c <file>               Also write the generated C (or LLVM IR, assembly, QBE IL, WebAssembly text) code to <file>\n\
+    --keep-c                      Keep the generated code in the temporary directory\n\
+    --no-line-directives          Don't point the C compiler and debuggers back at the source with #line or debug info\n\
+    -O0, -O1, -O2, -O3            Optimization level passed to the C compiler\n\
+    -g                            Build with debug info\n\
+    -l<lib>                       Link against <lib>\n\
+    -L<dir>                       Add <dir> to the library search path\n\
+    -I<dir>                       Add <dir> to the include search path\n\
+    --cflags \"<flags>\"            Pass <flags> to the C compiler as is\n\
+    --verbose, -v                 Explain what the compiler is doing, -vv to trace every stage\n\
+    --quiet, -q                   Only print errors\n\
+    --trace=<stages>              Trace the given stages (lexer,parser,gen)\n\
+    --error-format=<format>       How to print errors: human, json or sarif\n\
+    --opt-level=<level>           Optimize the IR: 0 not at all, 1 each function, 2 the whole program (default)\n\
+    -W, -A, -D <lint>             Warn about, allow or deny a lint, '-D warnings' makes every warning an error\n\
+    --help, -h                    Show this help message\n\n\
+Lints:\n\
+    wrt_size_mismatch             The size given to __wrt__ doesn't match the length of the text\n\
+    unused_cimport                A library is imported but never used\n\
+    unknown_lints                 An attribute names a lint that doesn't exist\n\
+    Not available yet, Neit has no variables or control flow: unused_variables, unreachable_code, shadowing\n";
+
+/// What the driver has been asked to do
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Build,
+    Run,
+    Check,
+    Emit(EmitStage),
+    /// Apply suggested fixes, only print them as a diff when `dry_run` is set
+    Fix { dry_run: bool },
+    /// `--explain <code>`
+    Explain(String),
+    Help,
+}
+
+/// Stages `neitc emit` can print
+#[derive(Debug, PartialEq, Eq)]
+pub enum EmitStage {
+    Tokens,
+    Ast,
+    /// The IR after the optimizations `--opt-level` asks for
+    Ir,
+    /// The IR as lowered, before any optimization
+    IrUnopt,
+    /// The code a backend generates, whatever `--backend` says
+    Code(Backend),
+}
+
+/// Parsed command line
+#[derive(Debug)]
+pub struct Args {
+    pub command: Command,
+    /// Source file to compile
+    pub input: String,
+    /// Executable to create
+    pub output: Option<String>,
+    /// Where to write the generated C code, a temporary file when not given
+    pub emit_c: Option<String>,
+    /// Keep the temporary C file around after building
+    pub keep_c: bool,
+    /// Extra flags for the C compiler, in command line order
+    pub cflags: Vec<String>,
+    /// Libraries from `-l`, without the `-l`, linked after the code like
+    /// the program's own `clink`s
+    pub libs: Vec<String>,
+    /// C compiler used to build the generated code, detected when not given
+    pub compiler: Option<String>,
+    /// Target triple to build for, the host when not given
+    pub target: Option<String>,
+    /// Arguments after `--`, passed to the program by `neitc run`
+    pub run_args: Vec<String>,
+    /// How diagnostics are printed
+    pub error_format: ErrorFormat,
+    /// Lint levels from `-W`, `-A` and `-D`
+    pub lints: LintLevels,
+    /// Optimizations to run on the IR
+    pub opt_level: OptLevel,
+    /// Map the generated code back to the source with `#line` directives or
+    /// debug info
+    pub line_directives: bool,
+    /// Code generator building the executable
+    pub backend: Backend,
+}
+
+/// Parse the command line, also applying the logging flags
+///
+/// # Parameters
+/// - `&[String]` : Arguments without the program name
+///
+/// # Returns
+/// - `Result<Args, String>` : The parsed arguments or a message explaining what is wrong
+pub fn parse_args(args: &[String]) -> Result<Args, String> {
+    // `neitc <file>` without a command still means build
+    let (command, rest) = match args.first().map(|arg| arg.as_str()) {
+        Some("build") => (Command::Build, &args[1..]),
+        Some("run") => (Command::Run, &args[1..]),
+        Some("check") => (Command::Check, &args[1..]),
+        Some("emit") => (Command::Emit(EmitStage::Code(Backend::C)), &args[1..]),
+        Some("fix") => (Command::Fix { dry_run: false }, &args[1..]),
+        _ => (Command::Build, args),
+    };
+    let mut iter = rest.iter();
+
+    let mut parsed = Args {
+        command,
+        input: String::new(),
+        output: None,
+        emit_c: None,
+        keep_c: false,
+        cflags: Vec::new(),
+        libs: Vec::new(),
+        compiler: None,
+        target: None,
+        run_args: Vec::new(),
+        error_format: ErrorFormat::Human,
+        lints: LintLevels::default(),
+        opt_level: OptLevel::default(),
+        line_directives: true,
+        backend: Backend::default(),
+    };
+    let mut stage = None;
+    let mut dry_run = false;
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--help" | "-h" => parsed.command = Command::Help,
+            "--explain" => parsed.command = Command::Explain(value(&mut iter, arg)?),
+            "--bcompiler" | "-bc" => {
+                parsed.compiler = Some(value(&mut iter, arg)?);
+            }
+            "--output" | "-o" => {
+                parsed.output = Some(value(&mut iter, arg)?);
+            }
+            "--target" => {
+                parsed.target = Some(value(&mut iter, arg)?);
+            }
+            "--emit-c" => {
+                parsed.emit_c = Some(value(&mut iter, arg)?);
+            }
+            "--keep-c" => parsed.keep_c = true,
+            "--no-line-directives" => parsed.line_directives = false,
+            "--dry-run" => dry_run = true,
+            "-O0" | "-O1" | "-O2" | "-O3" | "-g" => parsed.cflags.push(arg.clone()),
+            "-l" => parsed.libs.push(value(&mut iter, arg)?),
+            "-L" | "-I" => {
+                parsed
+                    .cflags
+                    .push(format!("{}{}", arg, value(&mut iter, arg)?));
+            }
+            "--cflags" => {
+                let flags = value(&mut iter, arg)?;
+                parsed
+                    .cflags
+                    .extend(flags.split_whitespace().map(String::from));
+            }
+            "-W" | "-A" | "-D" => {
+                let name = value(&mut iter, arg)?;
+                set_lint(&mut parsed.lints, arg, &name)?;
+            }
+            "-vv" => log::set_level(Level::Debug),
+            "--verbose" | "-v" => log::set_level(Level::Verbose),
+            "--quiet" | "-q" => log::set_level(Level::Quiet),
+            "--" => {
+                parsed.run_args = iter.by_ref().cloned().collect();
+            }
+            _ => {
+                if let Some(spec) = arg.strip_prefix("--trace=") {
+                    let stages = log::parse_trace(spec).map_err(|name| {
+                        format!(
+                            "Unknown stage '{}' in '--trace', expected lexer, parser or gen",
+                            name
+                        )
+                    })?;
+                    stages.into_iter().for_each(log::enable_trace);
+                } else if let Some(name) = arg.strip_prefix("--error-format=") {
+                    parsed.error_format = ErrorFormat::parse(name).map_err(|name| {
+                        format!(
+                            "Unknown error format '{}', expected human, json or sarif",
+                            name
+                        )
+                    })?;
+                } else if let Some(level) = arg.strip_prefix("--opt-level=") {
+                    parsed.opt_level = OptLevel::parse(level).map_err(|level| {
+                        format!("Unknown optimization level '{}', expected 0, 1 or 2", level)
+                    })?;
+                } else if let Some(name) = arg.strip_prefix("--stage=") {
+                    stage = Some(match name {
+                        "tokens" => EmitStage::Tokens,
+                        "ast" => EmitStage::Ast,
+                        "ir" => EmitStage::Ir,
+                        "ir-unopt" => EmitStage::IrUnopt,
+                        _ => EmitStage::Code(Backend::parse(name).map_err(|name| {
+                            format!(
+                                "Unknown stage '{}', expected tokens, ast, ir, ir-unopt, c, llvm, asm, qbe or wasm",
+                                name
+                            )
+                        })?),
+                    });
+                } else if let Some(name) = arg.strip_prefix("--backend=") {
+                    parsed.backend = Backend::parse(name).map_err(|name| {
+                        format!("Unknown backend '{}', expected c, llvm, asm, qbe or wasm", name)
+                    })?;
+                } else if let Some(flag) = ["-W", "-A", "-D"]
+                    .into_iter()
+                    .find(|flag| arg.starts_with(flag))
+                {
+                    set_lint(&mut parsed.lints, flag, &arg[flag.len()..])?;
+                } else if let Some(lib) = arg.strip_prefix("-l") {
+                    parsed.libs.push(lib.to_string());
+                } else if ["-L", "-I"].iter().any(|flag| arg.starts_with(flag)) {
+                    parsed.cflags.push(arg.clone());
+                } else if arg.starts_with('-') {
+                    return Err(format!("Unknown option '{}'", arg));
+                } else if parsed.input.is_empty() {
+                    parsed.input = arg.clone();
+                } else {
+                    return Err(format!("Unexpected argument '{}'", arg));
+                }
+            }
+        }
+    }
+
+    if let Command::Emit(emit) = &mut parsed.command {
+        *emit = stage.ok_or("'emit' needs a stage, e.g. '--stage=c'")?;
+    } else if stage.is_some() {
+        return Err(String::from("'--stage' can only be used with 'emit'"));
+    }
+    if let Command::Fix { dry_run: fix_dry_run } = &mut parsed.command {
+        *fix_dry_run = dry_run;
+    } else if dry_run {
+        return Err(String::from("'--dry-run' can only be used with 'fix'"));
+    }
+    let needs_input = !matches!(parsed.command, Command::Help | Command::Explain(_));
+    if needs_input && parsed.input.is_empty() {
+        return Err(String::from("No input file provided"));
+    }
+    Ok(parsed)
+}
+
+/// Apply `-W`, `-A` or `-D <lint>`
+fn set_lint(lints: &mut LintLevels, flag: &str, name: &str) -> Result<(), String> {
+    let level = match flag {
+        "-A" => LintLevel::Allow,
+        "-W" => LintLevel::Warn,
+        _ => LintLevel::Deny,
+    };
+    lints.set(name, level)
+}
+
+/// Take the value following a flag like `-o`
+fn value<'a>(iter: &mut impl Iterator<Item = &'a String>, flag: &str) -> Result<String, String> {
+    iter.next()
+        .cloned()
+        .ok_or_else(|| format!("No value specified after '{}'", flag))
+}
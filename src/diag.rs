@@ -0,0 +1,425 @@
+//! Diagnostics shared by every compiler stage
+//!
+//! A [`Diagnostic`] has a severity, an optional error code, a message and any
+//! number of labelled [`Span`]s into a [`SourceFile`], plus free standing
+//! notes and help. Rendering points at the labelled code with carets:
+//!
+//! ```text
+//! error: Invalid library 'cstdd'
+//!  --> hello.nc:1:9
+//!   |
+//! 1 | cimport cstdd
+//!   |         ^^^^^ not a known library
+//!   |
+//!   = help: the only library is 'cstd', C libraries are linked with 'link:<lib>'
+//! ```
+
+use std::fmt;
+
+use colored::*;
+
+/// How bad a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Note,
+    Help,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// Name used when rendering and in machine readable output
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Note => "note",
+            Severity::Help => "help",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    fn paint(self, text: &str) -> ColoredString {
+        match self {
+            Severity::Note => text.cyan().bold(),
+            Severity::Help => text.green().bold(),
+            Severity::Warning => text.yellow().bold(),
+            Severity::Error => text.red().bold(),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A byte range in a source file, `end` is exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// Smallest span covering both `self` and `other`
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+}
+
+/// A span with a message attached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    /// Primary labels point at the cause with `^`, secondary ones add context with `-`
+    pub primary: bool,
+}
+
+/// How sure a suggestion is to be what the programmer meant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applicability {
+    /// Certainly right, can be applied without asking, see `neitc fix`
+    MachineApplicable,
+    /// Probably right, but needs a look before applying
+    MaybeIncorrect,
+}
+
+impl Applicability {
+    /// Name used in machine readable output
+    pub fn name(self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "machine-applicable",
+            Applicability::MaybeIncorrect => "maybe-incorrect",
+        }
+    }
+}
+
+/// A change to the source that would fix the problem a diagnostic is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// Code to replace, empty to insert at `span.start`
+    pub span: Span,
+    pub replacement: String,
+    pub message: String,
+    pub applicability: Applicability,
+}
+
+/// An error, warning or note about the program being compiled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable code like `N0003`, see `neitc --explain`
+    pub code: Option<String>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: &str) -> Diagnostic {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    /// Point at the cause of the diagnostic
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Diagnostic {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    /// Point at code related to the cause
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Diagnostic {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Suggest replacing the code at `span` with `replacement`
+    pub fn with_suggestion(
+        mut self,
+        span: Span,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+        applicability: Applicability,
+    ) -> Diagnostic {
+        self.suggestions.push(Suggestion {
+            span,
+            replacement: replacement.into(),
+            message: message.into(),
+            applicability,
+        });
+        self
+    }
+
+    /// Span of the first primary label
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels
+            .iter()
+            .find(|label| label.primary)
+            .map(|label| label.span)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Render the diagnostic for a terminal
+    ///
+    /// # Parameters
+    /// - `Option<&SourceFile>` : File the labels point into, labels are skipped without it
+    ///
+    /// # Returns
+    /// - `String` : The rendered diagnostic, ending in a newline
+    pub fn render(&self, file: Option<&SourceFile>) -> String {
+        let mut out = String::new();
+        let title = match &self.code {
+            Some(code) => format!("{}[{}]", self.severity, code),
+            None => self.severity.to_string(),
+        };
+        out.push_str(&format!(
+            "{}{} {}\n",
+            self.severity.paint(&title),
+            ":".bold(),
+            self.message.bold()
+        ));
+
+        let snippet = file.filter(|_| !self.labels.is_empty());
+        let last_line = snippet
+            .map(|file| {
+                self.labels
+                    .iter()
+                    .map(|label| {
+                        file.line_col(label.span.end.max(label.span.start + 1) - 1)
+                            .0
+                    })
+                    .max()
+                    .unwrap_or(1)
+            })
+            .unwrap_or(1);
+        let width = last_line.to_string().len();
+        let gutter = format!("{} |", " ".repeat(width)).blue().bold().to_string();
+
+        if let Some(file) = snippet {
+            let span = self.primary_span().unwrap_or(self.labels[0].span);
+            let (line, col) = file.line_col(span.start);
+            out.push_str(&format!(
+                "{}{} {}:{}:{}\n",
+                " ".repeat(width),
+                "-->".blue().bold(),
+                file.name,
+                line,
+                col
+            ));
+            out.push_str(&gutter);
+            out.push('\n');
+            self.render_snippet(file, width, &mut out);
+        } else if let Some(file) = file.filter(|file| !file.name.is_empty()) {
+            out.push_str(&format!(
+                "{}{} {}\n",
+                " ".repeat(width),
+                "-->".blue().bold(),
+                file.name
+            ));
+        }
+
+        // Suggestions are shown as help, the replacement spelled out
+        let suggestions = self.suggestions.iter().map(|suggestion| {
+            match suggestion.replacement.trim_end() {
+                "" => suggestion.message.clone(),
+                replacement => format!("{}: '{}'", suggestion.message, replacement),
+            }
+        });
+        let help: Vec<String> = self.help.iter().cloned().chain(suggestions).collect();
+
+        if !self.notes.is_empty() || !help.is_empty() {
+            if snippet.is_some() {
+                out.push_str(&gutter);
+                out.push('\n');
+            }
+            for note in &self.notes {
+                out.push_str(&format!(
+                    "{} {} {}: {}\n",
+                    " ".repeat(width),
+                    "=".blue().bold(),
+                    "note".bold(),
+                    note
+                ));
+            }
+            for help in &help {
+                out.push_str(&format!(
+                    "{} {} {}: {}\n",
+                    " ".repeat(width),
+                    "=".blue().bold(),
+                    "help".bold(),
+                    help
+                ));
+            }
+        }
+        out
+    }
+
+    /// Print every line a label touches, with one underline row per label and line
+    fn render_snippet(&self, file: &SourceFile, width: usize, out: &mut String) {
+        // (line, start col, end col, label, is last line of the label)
+        let mut marks = Vec::new();
+        for label in &self.labels {
+            let start = file.line_col(label.span.start);
+            let end = file.line_col(label.span.end.max(label.span.start + 1) - 1);
+            for line in start.0..=end.0 {
+                let text = file.line(line);
+                let from = if line == start.0 {
+                    start.1
+                } else {
+                    text.chars().take_while(|c| c.is_whitespace()).count() + 1
+                };
+                let to = if line == end.0 {
+                    end.1 + 1
+                } else {
+                    text.chars().count() + 1
+                };
+                marks.push((line, from, to.max(from + 1), label, line == end.0));
+            }
+        }
+
+        let mut lines: Vec<usize> = marks.iter().map(|mark| mark.0).collect();
+        lines.sort_unstable();
+        lines.dedup();
+
+        let mut prev = None;
+        for line in lines {
+            if prev.is_some_and(|prev| line > prev + 1) {
+                out.push_str(&format!("{}\n", "...".blue().bold()));
+            }
+            prev = Some(line);
+            out.push_str(&format!(
+                "{} {}\n",
+                format!("{:>width$} |", line, width = width).blue().bold(),
+                file.line(line)
+            ));
+            for (_, from, to, label, last) in marks.iter().filter(|mark| mark.0 == line) {
+                let marker = if label.primary { "^" } else { "-" };
+                let underline = marker.repeat(to - from);
+                let message = if *last { label.message.as_str() } else { "" };
+                let painted = if label.primary {
+                    self.severity.paint(&format!("{} {}", underline, message))
+                } else {
+                    format!("{} {}", underline, message).blue().bold()
+                };
+                out.push_str(&format!(
+                    "{} {}{}\n",
+                    format!("{} |", " ".repeat(width)).blue().bold(),
+                    " ".repeat(from - 1),
+                    painted.to_string().trim_end()
+                ));
+            }
+        }
+    }
+}
+
+/// A source file diagnostics point into
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+    /// Byte offset each line starts at
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, text: &str) -> SourceFile {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(pos, _)| pos + 1))
+            .collect();
+        SourceFile {
+            name: name.to_string(),
+            text: text.to_string(),
+            line_starts,
+        }
+    }
+
+    /// 1 based line and column (in characters) of a byte offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let start = self.line_starts[line - 1];
+        let col = self.text[start..floor_char_boundary(&self.text, offset)]
+            .chars()
+            .count()
+            + 1;
+        (line, col)
+    }
+
+    /// Text of a 1 based line, without the line break
+    pub fn line(&self, line: usize) -> &str {
+        let span = self.line_span(line);
+        self.text[span.start..span.end].trim_end_matches('\r')
+    }
+
+    /// Span of a 1 based line, without the line break
+    pub fn line_span(&self, line: usize) -> Span {
+        let line = line.clamp(1, self.line_starts.len());
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map(|next| next - 1)
+            .unwrap_or(self.text.len());
+        Span::new(start, end)
+    }
+}
+
+fn floor_char_boundary(text: &str, mut offset: usize) -> usize {
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
@@ -0,0 +1,154 @@
+//! Unified diffs between two versions of a source file, printed by
+//! `neitc fix --dry-run`
+
+/// Lines of context around every change
+const CONTEXT: usize = 3;
+
+/// One step turning the old lines into the new ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    /// The line is in both, at these indices
+    Keep(usize, usize),
+    /// An old line is removed
+    Delete(usize),
+    /// A new line is added
+    Insert(usize),
+}
+
+/// Write the difference between `old` and `new` as a unified diff
+///
+/// # Parameters
+/// - `&str` : Name of the file, used for both sides
+/// - `&str` : The file as it is
+/// - `&str` : The file as it would be
+///
+/// # Returns
+/// - `String` : The diff, empty when both are the same
+pub fn unified_diff(name: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = edits(&old, &new);
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|i| !matches!(edits[*i], Edit::Keep(..)))
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", name, name);
+    let mut first = 0;
+    while first < changes.len() {
+        // Changes close enough to share their context go in the same hunk
+        let mut last = first;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT + 1 {
+            last += 1;
+        }
+        let start = changes[first].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT + 1).min(edits.len());
+        hunk(&edits[start..end], &old, &new, &mut out);
+        first = last + 1;
+    }
+    out
+}
+
+/// Shortest list of edits turning `old` into `new`, through their longest
+/// common subsequence
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    // lcs[i][j] is the length of the common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut edits = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Keep(i, j));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(Edit::Delete(i));
+            i += 1;
+        } else {
+            edits.push(Edit::Insert(j));
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// Write one hunk, its header followed by its lines
+fn hunk(edits: &[Edit], old: &[&str], new: &[&str], out: &mut String) {
+    // Where the hunk starts on either side, counting lines before it
+    let (old_start, new_start) = match edits[0] {
+        Edit::Keep(i, j) => (i, j),
+        Edit::Delete(i) => (i, new_index(edits)),
+        Edit::Insert(j) => (old_index(edits), j),
+    };
+    let old_len = edits
+        .iter()
+        .filter(|edit| !matches!(edit, Edit::Insert(_)))
+        .count();
+    let new_len = edits
+        .iter()
+        .filter(|edit| !matches!(edit, Edit::Delete(_)))
+        .count();
+    out.push_str(&format!(
+        "@@ -{} +{} @@\n",
+        range(old_start, old_len),
+        range(new_start, new_len)
+    ));
+
+    for edit in edits {
+        let (prefix, line) = match *edit {
+            Edit::Keep(i, _) => (' ', old[i]),
+            Edit::Delete(i) => ('-', old[i]),
+            Edit::Insert(j) => ('+', new[j]),
+        };
+        out.push(prefix);
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+/// Index of the first old line at or after the start of a hunk that begins
+/// with insertions
+fn old_index(edits: &[Edit]) -> usize {
+    edits
+        .iter()
+        .find_map(|edit| match edit {
+            Edit::Keep(i, _) | Edit::Delete(i) => Some(*i),
+            Edit::Insert(_) => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Index of the first new line at or after the start of a hunk that begins
+/// with deletions
+fn new_index(edits: &[Edit]) -> usize {
+    edits
+        .iter()
+        .find_map(|edit| match edit {
+            Edit::Keep(_, j) | Edit::Insert(j) => Some(*j),
+            Edit::Delete(_) => None,
+        })
+        .unwrap_or(0)
+}
+
+/// A side of a hunk header, 1-based, or the line before it when it is empty
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
@@ -0,0 +1,130 @@
+//! Long form descriptions of error codes, printed by `neitc --explain <code>`
+
+/// Everything `--explain` knows about an error code
+#[derive(Debug, PartialEq, Eq)]
+pub struct Explanation {
+    /// The code, e.g. `N0003`
+    pub code: &'static str,
+    /// One line summary
+    pub title: &'static str,
+    /// What the error means and how to fix it
+    pub description: &'static str,
+    /// A program reporting this error
+    pub incorrect: &'static str,
+    /// The same program, fixed
+    pub correct: &'static str,
+}
+
+pub const N0001: Explanation = Explanation {
+    code: "N0001",
+    title: "Invalid character in an import",
+    description: "A `cimport` line holds a comma separated list of library names. \
+Characters like brackets or quotes can't be part of a library name.",
+    incorrect: "cimport cstd(\n",
+    correct: "cimport cstd\n",
+};
+
+pub const N0002: Explanation = Explanation {
+    code: "N0002",
+    title: "Invalid library",
+    description: "`cimport` only knows the `cstd` library, which provides `__wrt__`. \
+C libraries to link against are imported as `link:<name>`, where the name only \
+contains letters, digits, `-`, `.` and `+`.",
+    incorrect: "cimport cstdd\n",
+    correct: "cimport cstd, link:m\n",
+};
+
+pub const N0003: Explanation = Explanation {
+    code: "N0003",
+    title: "Unexpected token",
+    description: "Every line of a program is an import, an attribute or a call to a \
+builtin function. Anything else, or anything left over after a call, is unexpected.",
+    incorrect: "cimport cstd\nhello\n__wrt__(stdout, \"hi\\n\", 3) world\n",
+    correct: "cimport cstd\n__wrt__(stdout, \"hi\\n\", 3)\n",
+};
+
+pub const N0004: Explanation = Explanation {
+    code: "N0004",
+    title: "Unterminated string",
+    description: "Strings start and end with the same quote, `\"` or `'`, on a single \
+line. A string that is still open at the end of a line is never closed.",
+    incorrect: "cimport cstd\n__wrt__(stdout, \"hi, 2)\n",
+    correct: "cimport cstd\n__wrt__(stdout, \"hi\", 2)\n",
+};
+
+pub const N0005: Explanation = Explanation {
+    code: "N0005",
+    title: "Invalid function",
+    description: "Only builtin functions can be called, and the only builtin is \
+`__wrt__(stream, text, size)`.",
+    incorrect: "cimport cstd\n__print__(stdout, \"hi\", 2)\n",
+    correct: "cimport cstd\n__wrt__(stdout, \"hi\", 2)\n",
+};
+
+pub const N0006: Explanation = Explanation {
+    code: "N0006",
+    title: "Invalid argument",
+    description: "A call or import is missing a part, or one of its arguments has the \
+wrong type. `__wrt__` takes exactly three arguments: the stream `stdout` or `stderr`, \
+a string and the number of bytes to write as an integer of 0 or more.",
+    incorrect: "cimport cstd\n__wrt__(stdin, \"hi\", two)\n",
+    correct: "cimport cstd\n__wrt__(stdout, \"hi\", 2)\n",
+};
+
+pub const N0007: Explanation = Explanation {
+    code: "N0007",
+    title: "Invalid attribute",
+    description: "Attributes change the item after them. `#[target_os = \"<os>\"]` and \
+`#[target_arch = \"<arch>\"]` make it conditional and need a value, `#[allow(<lint>)]`, \
+`#[warn(<lint>)]` and `#[deny(<lint>)]` set lint levels. Only lint levels can be set for \
+the whole file, with `#![allow(<lint>)]`.",
+    incorrect: "cimport cstd\n#[target_family = \"unix\"]\n__wrt__(stdout, \"hi\", 2)\n",
+    correct: "cimport cstd\n#[target_os = \"linux\"]\n__wrt__(stdout, \"hi\", 2)\n",
+};
+
+pub const N0008: Explanation = Explanation {
+    code: "N0008",
+    title: "Missing import of cstd",
+    description: "`__wrt__` is provided by the C standard library, which has to be \
+imported with `cimport cstd` before it can be used.",
+    incorrect: "__wrt__(stdout, \"hi\", 2)\n",
+    correct: "cimport cstd\n__wrt__(stdout, \"hi\", 2)\n",
+};
+
+pub const N0009: Explanation = Explanation {
+    code: "N0009",
+    title: "Attribute without an item",
+    description: "An attribute applies to the item right after it, so it can't come last \
+in the file. Add the item it was meant for, or remove the attribute.",
+    incorrect: "cimport cstd\n__wrt__(stdout, \"hi\", 2)\n#[target_os = \"linux\"]\n",
+    correct: "cimport cstd\n#[target_os = \"linux\"]\n__wrt__(stdout, \"hi\", 2)\n",
+};
+
+/// Every explanation, in code order
+pub const EXPLANATIONS: &[&Explanation] = &[
+    &N0001, &N0002, &N0003, &N0004, &N0005, &N0006, &N0007, &N0008, &N0009,
+];
+
+/// Look up the explanation of an error code
+///
+/// # Parameters
+/// - `&str` : The code, case insensitive
+///
+/// # Returns
+/// - `Option<&Explanation>` : The explanation, `None` for unknown codes
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    EXPLANATIONS
+        .iter()
+        .copied()
+        .find(|explanation| explanation.code.eq_ignore_ascii_case(code.trim()))
+}
+
+impl Explanation {
+    /// The explanation as printed by `--explain`
+    pub fn render(&self) -> String {
+        format!(
+            "{}: {}\n\n{}\n\nErroneous code example:\n\n```\n{}```\n\nCorrected:\n\n```\n{}```\n",
+            self.code, self.title, self.description, self.incorrect, self.correct
+        )
+    }
+}
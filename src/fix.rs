@@ -0,0 +1,94 @@
+//! Applying machine applicable suggestions to the source, `neitc fix`
+//!
+//! A fix can uncover the next problem: a call is only checked for its import
+//! once its name is right, and lints only run once there are no errors. So
+//! fixes are applied in passes, until a pass has nothing left to fix.
+
+use crate::diag::{Applicability, Diagnostic, Suggestion};
+use crate::{analyze, Options};
+
+/// Passes after which fixing gives up, so fixes undoing each other can't
+/// loop forever
+const MAX_PASSES: usize = 8;
+
+/// The source after fixing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixed {
+    /// The fixed source code
+    pub source: String,
+    /// Number of suggestions applied over every pass
+    pub applied: usize,
+}
+
+/// Apply every machine applicable suggestion of every diagnostic, errors and
+/// lints alike, to a piece of source code
+///
+/// # Parameters
+/// - `&str` : Neit source code
+/// - `&Options` : How it is compiled, this decides which lints fire
+///
+/// # Returns
+/// - `Fixed` : The fixed source and how many fixes it took
+pub fn fix(source: &str, options: &Options) -> Fixed {
+    let mut fixed = Fixed {
+        source: source.to_string(),
+        applied: 0,
+    };
+    for _ in 0..MAX_PASSES {
+        let diags = match analyze(&fixed.source, options) {
+            Ok(analysis) => analysis.warnings.diagnostics,
+            Err(diags) => diags.diagnostics,
+        };
+        let (source, applied) = apply(&fixed.source, &diags);
+        if applied == 0 {
+            break;
+        }
+        fixed.source = source;
+        fixed.applied += applied;
+    }
+    fixed
+}
+
+/// Apply the machine applicable suggestions of `diags` to `source`
+///
+/// Suggestions overlapping one applied before them are left for the next pass.
+///
+/// # Parameters
+/// - `&str` : The source the diagnostics point into
+/// - `&[Diagnostic]` : Diagnostics carrying the suggestions
+///
+/// # Returns
+/// - `(String, usize)` : The changed source and the number of suggestions applied
+pub fn apply(source: &str, diags: &[Diagnostic]) -> (String, usize) {
+    let mut suggestions: Vec<&Suggestion> = diags
+        .iter()
+        .flat_map(|diag| &diag.suggestions)
+        .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable)
+        .filter(|suggestion| {
+            source
+                .get(suggestion.span.start..suggestion.span.end)
+                .is_some()
+        })
+        .collect();
+    suggestions.sort_by_key(|suggestion| (suggestion.span.start, suggestion.span.end));
+
+    let mut out = String::with_capacity(source.len());
+    // End of the source already copied or replaced
+    let mut done = 0;
+    let mut last_start = None;
+    let mut applied = 0;
+    for suggestion in suggestions {
+        // Two edits starting at the same place, like two insertions, could go
+        // in either order
+        if suggestion.span.start < done || last_start == Some(suggestion.span.start) {
+            continue;
+        }
+        out.push_str(&source[done..suggestion.span.start]);
+        out.push_str(&suggestion.replacement);
+        done = suggestion.span.end;
+        last_start = Some(suggestion.span.start);
+        applied += 1;
+    }
+    out.push_str(&source[done..]);
+    (out, applied)
+}
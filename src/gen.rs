@@ -1,60 +1,255 @@
-use crate::parse1::AST;
+use crate::c_ast::{self, CType, Expr, Item, Signature, Stmt, UnOp, Unit};
+use crate::ir::{BinOp, Block, BlockId, Function, Inst, Module, Terminator, Type, Value};
+use crate::log::Stage;
+use crate::prelude::helper;
+use crate::target::Target;
+use crate::trace;
 
-#[allow(unused)]
-/// Parse the AST and generate C Code out of it
-/// 
+/// Generate C code out of an IR module
+///
 /// # Parameters
-/// - `&Vec<AST>` : Vector of retrieved AST returned from `parse()` function
-/// 
+/// - `&Module` : The module returned from `lower()`
+/// - `&Target` : Platform the C code will be compiled for
+/// - `bool` : Whether to point the C compiler back at the source with `#line`
+///   directives
+///
 /// # Returns
 /// - `String` : The generated C code
-pub fn genc(ast: &Vec<AST>) -> String {
-    let mut c_code = String::new();
-    let mut imports = String::new();
-    let mut mainf = String::new();
-    let mut sidef = String::new();
-    
-    // Main function header
-    mainf.push_str("int main(int argc, char const *argv[]) {\n");
-
-    // Iterate over AST nodes to generate code
-    for item in ast {
-        match item {
-            AST::Cimport(lib) => match lib.as_str() {
-                "cstd" => {
-                    // Include standard libraries and definitions for stdout and stderr
-                    imports.push_str("#include <unistd.h>\nint STDOUT = 0;\nint STDERR = 1;\n");
-                    sidef.push_str("int count(const char *str) {\n    int c = 0;\n    while (*str) {\n        c += 1;\n    }\n    return c;\n}\n");
-                }
-                _ => {}
-            },
-            AST::CWrt(stream, text, size) => {
-                // Format write function call with proper indentation
-                mainf.push_str(&format!(
-                    "    write({}, \"{}\", {});\n",
-                    stream, trim_quotes(text), size
-                ));
+pub fn genc(module: &Module, target: &Target, line_directives: bool) -> String {
+    c_unit(module, target, line_directives).to_string()
+}
+
+/// What code generation needs to know besides the function at hand
+struct Context<'a> {
+    module: &'a Module,
+    target: &'a Target,
+    line_directives: bool,
+}
+
+/// Build the C file for a module, see [`genc`]
+pub fn c_unit(module: &Module, target: &Target, line_directives: bool) -> Unit {
+    let cx = Context {
+        module,
+        target,
+        // Without a file name there is nothing to point at
+        line_directives: line_directives && !module.file.is_empty(),
+    };
+    let mut unit = Unit::default();
+    let insts = || {
+        module
+            .functions
+            .iter()
+            .flat_map(|function| &function.blocks)
+            .flat_map(|block| &block.insts)
+    };
+
+    if insts().any(|inst| matches!(inst, Inst::Write { .. })) {
+        // Include the header declaring the target's write function
+        unit.include(target.write_call().include);
+    }
+
+    // Only pull in the prelude helpers the functions actually call
+    let mut helpers = Vec::new();
+    for inst in insts() {
+        if let Inst::Call { function, .. } = inst {
+            if module.function(function).is_some() {
+                continue;
             }
-            _ => {}
+            if let Some(helper) = helper(function).filter(|helper| !helpers.contains(helper)) {
+                helpers.push(helper);
+            }
+        }
+    }
+    for helper in helpers {
+        helper
+            .includes
+            .iter()
+            .for_each(|include| unit.include(include));
+        unit.items.push(Item::Verbatim(helper.def.to_string()));
+    }
+
+    // Functions other than main may be called before they are defined
+    for function in &module.functions {
+        if function.name != "main" {
+            unit.items.push(Item::Prototype(signature(function)));
+        }
+    }
+    for function in &module.functions {
+        trace!(Stage::Gen, "Generating C for {}", function.name);
+        unit.items.push(Item::Function(
+            signature(function),
+            gen_function(&cx, function),
+        ));
+    }
+    unit
+}
+
+/// C declaration of a function
+fn signature(function: &Function) -> Signature {
+    if function.name == "main" {
+        return Signature {
+            ret: CType::Int,
+            name: String::from("main"),
+            params: vec![
+                (CType::Int, String::from("argc")),
+                (
+                    CType::Array(Box::new(CType::const_char_ptr())),
+                    String::from("argv"),
+                ),
+            ],
+        };
+    }
+    Signature {
+        ret: function.ret.map_or(CType::Void, c_type),
+        name: function.name.clone(),
+        params: function
+            .params
+            .iter()
+            .map(|param| (c_type(function.temps[param.0]), temp(param.0)))
+            .collect(),
+    }
+}
+
+/// Generate the body of a function
+fn gen_function(cx: &Context, function: &Function) -> Vec<Stmt> {
+    let mut body = Vec::new();
+
+    // Temporaries become locals, declared up front
+    for (id, ty) in function.temps.iter().enumerate() {
+        if !function.params.iter().any(|param| param.0 == id) {
+            body.push(Stmt::Decl {
+                ty: c_type(*ty),
+                name: temp(id),
+                init: None,
+            });
+        }
+    }
+
+    // Only blocks something jumps to need a label
+    let targets: Vec<BlockId> = function
+        .blocks
+        .iter()
+        .flat_map(|block| block.term.successors())
+        .collect();
+    for (id, block) in function.blocks.iter().enumerate() {
+        if targets.contains(&BlockId(id)) {
+            body.push(Stmt::Label(label(BlockId(id))));
         }
+        gen_block(cx, block, BlockId(id + 1), &mut body);
+    }
+    body
+}
+
+/// Generate the statements of a block, `next` being the block right after it
+fn gen_block(cx: &Context, block: &Block, next: BlockId, body: &mut Vec<Stmt>) {
+    let value = |value: &Value| c_value(cx.module, value);
+    for inst in &block.insts {
+        let expr = match inst {
+            Inst::Loc { line, .. } => {
+                if cx.line_directives {
+                    body.push(Stmt::Line {
+                        line: *line,
+                        file: cx.module.file.clone(),
+                    });
+                }
+                continue;
+            }
+            Inst::Copy { dest, value: v } => Expr::assign(Expr::ident(temp(dest.0)), value(v)),
+            Inst::Binary { dest, op, lhs, rhs } => Expr::assign(
+                Expr::ident(temp(dest.0)),
+                Expr::binary(c_op(*op), value(lhs), value(rhs)),
+            ),
+            Inst::Call {
+                dest,
+                function,
+                args,
+            } => {
+                let call = Expr::Call(function.clone(), args.iter().map(value).collect());
+                match dest {
+                    Some(dest) => Expr::assign(Expr::ident(temp(dest.0)), call),
+                    None => call,
+                }
+            }
+            Inst::Write { fd, data, len } => Expr::Call(
+                cx.target.write_call().function.to_string(),
+                vec![value(fd), value(data), value(len)],
+            ),
+        };
+        body.push(Stmt::Expr(expr));
     }
 
-    // Closing the main function
-    mainf.push_str("\n}");
+    match &block.term {
+        Terminator::Return(v) => body.push(Stmt::Return(v.as_ref().map(value))),
+        // Falling through to the next block needs no jump
+        Terminator::Jump(to) if *to == next => {}
+        Terminator::Jump(to) => body.push(Stmt::Goto(label(*to))),
+        // Only jump away from the block that follows
+        Terminator::Branch {
+            cond,
+            then,
+            otherwise,
+        } if *then == next => body.push(Stmt::If {
+            cond: Expr::Unary(UnOp::Not, Box::new(value(cond))),
+            then: vec![Stmt::Goto(label(*otherwise))],
+            otherwise: Vec::new(),
+        }),
+        Terminator::Branch {
+            cond,
+            then,
+            otherwise,
+        } => body.push(Stmt::If {
+            cond: value(cond),
+            then: vec![Stmt::Goto(label(*then))],
+            otherwise: if *otherwise == next {
+                Vec::new()
+            } else {
+                vec![Stmt::Goto(label(*otherwise))]
+            },
+        }),
+    }
+}
 
-    // Combine the parts to form the full C code
-    c_code.push_str(&imports);    // Import section
-    c_code.push_str("\n");        // Newline for separation
-    c_code.push_str(&sidef);      // Side functions like count()
-    c_code.push_str("\n");        // Newline for separation
-    c_code.push_str(&mainf);      // Main function
+/// Name of the local holding a temporary
+fn temp(id: usize) -> String {
+    format!("t{}", id)
+}
 
-    c_code.push_str("\n");
+/// Label of a block
+fn label(id: BlockId) -> String {
+    format!("bb{}", id.0)
+}
 
-    c_code
+/// C type of an IR type
+fn c_type(ty: Type) -> CType {
+    match ty {
+        Type::I32 => CType::Int,
+        Type::I64 => CType::LongLong,
+        Type::Ptr => CType::const_char_ptr(),
+    }
 }
 
-/// Helper function to trim quotes (both single and double) from a string
-fn trim_quotes(input: &str) -> String {
-    input.trim_matches(|c| c == '"' || c == '\'').to_string()
+/// C operator of an IR operator
+fn c_op(op: BinOp) -> c_ast::BinOp {
+    match op {
+        BinOp::Add => c_ast::BinOp::Add,
+        BinOp::Sub => c_ast::BinOp::Sub,
+        BinOp::Mul => c_ast::BinOp::Mul,
+        BinOp::Div => c_ast::BinOp::Div,
+        BinOp::Rem => c_ast::BinOp::Rem,
+        BinOp::Eq => c_ast::BinOp::Eq,
+        BinOp::Ne => c_ast::BinOp::Ne,
+        BinOp::Lt => c_ast::BinOp::Lt,
+        BinOp::Le => c_ast::BinOp::Le,
+        BinOp::Gt => c_ast::BinOp::Gt,
+        BinOp::Ge => c_ast::BinOp::Ge,
+    }
+}
+
+/// C expression of a value, data as a string literal
+fn c_value(module: &Module, value: &Value) -> Expr {
+    match value {
+        Value::Int(value, _) => Expr::Int(*value),
+        Value::Temp(temp) => Expr::ident(self::temp(temp.0)),
+        Value::Data(data) => Expr::Str(module.data[data.0].bytes.clone()),
+    }
 }
@@ -0,0 +1,478 @@
+//! The intermediate representation every backend generates code from
+//!
+//! The checked AST is lowered into a [`Module`], see [`crate::lower`]. A module
+//! holds constant data and functions, a function is a list of basic blocks,
+//! and a block is a list of instructions ending in a single [`Terminator`].
+//! Intermediate results live in numbered temporaries, each with a [`Type`]
+//! fixed when it is created. Temporaries can be assigned more than once, so
+//! the IR is not in SSA form.
+
+use std::fmt;
+
+/// Type of a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    /// 32 bit signed integer, also used for truth values
+    I32,
+    /// 64 bit signed integer, used for sizes
+    I64,
+    /// Address of a byte
+    Ptr,
+}
+
+impl Type {
+    /// Name of the type in the textual IR
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::I32 => "i32",
+            Type::I64 => "i64",
+            Type::Ptr => "ptr",
+        }
+    }
+}
+
+/// A temporary, local to a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Temp(pub usize);
+
+/// A basic block, by its index in [`Function::blocks`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(pub usize);
+
+/// Constant data, by its index in [`Module::data`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DataId(pub usize);
+
+/// An operand of an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Value {
+    /// An integer constant
+    Int(i64, Type),
+    /// The current value of a temporary
+    Temp(Temp),
+    /// Address of constant data, a [`Type::Ptr`]
+    Data(DataId),
+}
+
+/// Operators of [`Inst::Binary`], comparisons give 1 or 0 as an `i32`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    /// Signed division, rounding towards zero
+    Div,
+    /// Remainder of [`BinOp::Div`]
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BinOp {
+    /// Name of the operator in the textual IR
+    pub fn name(self) -> &'static str {
+        match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::Div => "div",
+            BinOp::Rem => "rem",
+            BinOp::Eq => "eq",
+            BinOp::Ne => "ne",
+            BinOp::Lt => "lt",
+            BinOp::Le => "le",
+            BinOp::Gt => "gt",
+            BinOp::Ge => "ge",
+        }
+    }
+
+    /// Check whether the operator compares its operands
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge
+        )
+    }
+}
+
+/// An instruction inside a basic block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inst {
+    /// `dest = value`
+    Copy { dest: Temp, value: Value },
+    /// `dest = lhs op rhs`, arithmetic done in the type of `dest` and
+    /// comparisons in the wider of the operand types
+    Binary {
+        dest: Temp,
+        op: BinOp,
+        lhs: Value,
+        rhs: Value,
+    },
+    /// Call a function of the module, keeping its result in `dest` if any
+    Call {
+        dest: Option<Temp>,
+        function: String,
+        args: Vec<Value>,
+    },
+    /// Write `len` bytes starting at `data` to the file descriptor `fd`, what
+    /// `__wrt__` lowers to
+    Write { fd: Value, data: Value, len: Value },
+    /// The instructions after it come from this 1 based line and column of
+    /// [`Module::file`], only used for debug info
+    Loc { line: usize, col: usize },
+}
+
+impl Inst {
+    /// The temporary the instruction assigns, if any
+    pub fn dest(&self) -> Option<Temp> {
+        match self {
+            Inst::Copy { dest, .. } | Inst::Binary { dest, .. } => Some(*dest),
+            Inst::Call { dest, .. } => *dest,
+            Inst::Write { .. } | Inst::Loc { .. } => None,
+        }
+    }
+
+    /// The temporary the instruction assigns, to be changed in place
+    pub fn dest_mut(&mut self) -> Option<&mut Temp> {
+        match self {
+            Inst::Copy { dest, .. } | Inst::Binary { dest, .. } => Some(dest),
+            Inst::Call { dest, .. } => dest.as_mut(),
+            Inst::Write { .. } | Inst::Loc { .. } => None,
+        }
+    }
+
+    /// Every value the instruction reads
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            Inst::Copy { value, .. } => vec![*value],
+            Inst::Binary { lhs, rhs, .. } => vec![*lhs, *rhs],
+            Inst::Call { args, .. } => args.clone(),
+            Inst::Write { fd, data, len } => vec![*fd, *data, *len],
+            Inst::Loc { .. } => Vec::new(),
+        }
+    }
+
+    /// Every value the instruction reads, to be changed in place
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Inst::Copy { value, .. } => vec![value],
+            Inst::Binary { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::Call { args, .. } => args.iter_mut().collect(),
+            Inst::Write { fd, data, len } => vec![fd, data, len],
+            Inst::Loc { .. } => Vec::new(),
+        }
+    }
+
+    /// Check whether the instruction does anything besides assigning its
+    /// temporary, so it has to stay even when the result is unused
+    pub fn has_side_effects(&self) -> bool {
+        matches!(self, Inst::Call { .. } | Inst::Write { .. })
+    }
+}
+
+/// The last instruction of a basic block, deciding where control goes next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    /// Leave the function, with a value unless it returns nothing
+    Return(Option<Value>),
+    /// Continue at another block
+    Jump(BlockId),
+    /// Continue at `then` if `cond` isn't zero, at `otherwise` if it is
+    Branch {
+        cond: Value,
+        then: BlockId,
+        otherwise: BlockId,
+    },
+}
+
+impl Terminator {
+    /// Blocks control can continue at
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Return(_) => Vec::new(),
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch {
+                then, otherwise, ..
+            } => vec![*then, *otherwise],
+        }
+    }
+
+    /// Blocks control can continue at, to be changed in place
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Return(_) => Vec::new(),
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch {
+                then, otherwise, ..
+            } => vec![then, otherwise],
+        }
+    }
+
+    /// The value the terminator reads, if any
+    pub fn operand(&self) -> Option<Value> {
+        match self {
+            Terminator::Return(value) => *value,
+            Terminator::Jump(_) => None,
+            Terminator::Branch { cond, .. } => Some(*cond),
+        }
+    }
+
+    /// The value the terminator reads, to be changed in place
+    pub fn operand_mut(&mut self) -> Option<&mut Value> {
+        match self {
+            Terminator::Return(value) => value.as_mut(),
+            Terminator::Jump(_) => None,
+            Terminator::Branch { cond, .. } => Some(cond),
+        }
+    }
+}
+
+/// Straight line code, entered at the top and left through its terminator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    pub term: Terminator,
+}
+
+/// A function, its first block is where it starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    /// Temporaries holding the arguments
+    pub params: Vec<Temp>,
+    /// Type of the returned value, `None` when nothing is returned
+    pub ret: Option<Type>,
+    /// Type of every temporary, indexed by its number
+    pub temps: Vec<Type>,
+    pub blocks: Vec<Block>,
+}
+
+impl Function {
+    /// Create a function with a single empty block, returning nothing
+    ///
+    /// # Parameters
+    /// - `&str` : Name of the function
+    /// - `&[Type]` : Type of each parameter
+    /// - `Option<Type>` : Type of the returned value
+    ///
+    /// # Returns
+    /// - `Function` : The function, parameters in temporaries `0..params.len()`
+    pub fn new(name: &str, params: &[Type], ret: Option<Type>) -> Function {
+        Function {
+            name: name.to_string(),
+            params: (0..params.len()).map(Temp).collect(),
+            ret,
+            temps: params.to_vec(),
+            blocks: vec![Block {
+                insts: Vec::new(),
+                term: Terminator::Return(None),
+            }],
+        }
+    }
+
+    /// Create a new temporary of type `ty`
+    pub fn temp(&mut self, ty: Type) -> Temp {
+        self.temps.push(ty);
+        Temp(self.temps.len() - 1)
+    }
+
+    /// Append a new block that returns nothing, to be filled in
+    pub fn block(&mut self) -> BlockId {
+        self.blocks.push(Block {
+            insts: Vec::new(),
+            term: Terminator::Return(None),
+        });
+        BlockId(self.blocks.len() - 1)
+    }
+
+    /// Type of a value used in this function
+    pub fn type_of(&self, value: &Value) -> Type {
+        match value {
+            Value::Int(_, ty) => *ty,
+            Value::Temp(temp) => self.temps[temp.0],
+            Value::Data(_) => Type::Ptr,
+        }
+    }
+}
+
+/// Constant bytes, like the text of a string literal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Data {
+    /// The bytes, escapes already resolved and without a terminating NUL
+    pub bytes: Vec<u8>,
+}
+
+/// A whole program
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Module {
+    /// Name of the source file, empty when there is none to point at
+    pub file: String,
+    pub data: Vec<Data>,
+    /// Every function, `main` being where the program starts
+    pub functions: Vec<Function>,
+}
+
+impl Module {
+    /// Add constant data, reusing an identical one if there is one
+    pub fn data(&mut self, bytes: Vec<u8>) -> DataId {
+        if let Some(pos) = self.data.iter().position(|data| data.bytes == bytes) {
+            return DataId(pos);
+        }
+        self.data.push(Data { bytes });
+        DataId(self.data.len() - 1)
+    }
+
+    /// Look up a function by its name
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+}
+
+/// Turn the text of a string literal into its bytes, the way the C compiler
+/// would: quotes removed and escapes like `\n`, `\x41` or `\101` resolved
+pub fn string_bytes(text: &str) -> Vec<u8> {
+    let inner = text
+        .get(1..text.len().saturating_sub(1))
+        .unwrap_or_default();
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let byte = match chars.next() {
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('r') => b'\r',
+            Some('a') => 0x07,
+            Some('b') => 0x08,
+            Some('f') => 0x0c,
+            Some('v') => 0x0b,
+            Some('e') => 0x1b,
+            Some('x') => {
+                let mut value: u32 = 0;
+                while let Some(digit) = chars.next_if(|c| c.is_ascii_hexdigit()) {
+                    value = (value << 4 | digit.to_digit(16).unwrap_or(0)) & 0xff;
+                }
+                value as u8
+            }
+            Some(first @ '0'..='7') => {
+                // Up to three octal digits, one already taken
+                let mut value = first.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    if let Some(digit) = chars.next_if(|c| ('0'..='7').contains(c)) {
+                        value = value * 8 + digit.to_digit(8).unwrap_or(0);
+                    }
+                }
+                value as u8
+            }
+            // `\\`, `\"`, `\'` and unknown escapes stand for the character itself
+            Some(c) => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+            None => b'\\',
+        };
+        bytes.push(byte);
+    }
+    bytes
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value, ty) => write!(f, "{} {}", ty.name(), value),
+            Value::Temp(temp) => write!(f, "%t{}", temp.0),
+            Value::Data(data) => write!(f, "@d{}", data.0),
+        }
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl Function {
+    fn fmt_inst(&self, inst: &Inst, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(dest) = inst.dest() {
+            write!(f, "%t{}: {} = ", dest.0, self.temps[dest.0].name())?;
+        }
+        match inst {
+            Inst::Copy { value, .. } => write!(f, "copy {}", value),
+            Inst::Binary { op, lhs, rhs, .. } => write!(f, "{} {}, {}", op.name(), lhs, rhs),
+            Inst::Call { function, args, .. } => {
+                let args: Vec<String> = args.iter().map(Value::to_string).collect();
+                write!(f, "call {}({})", function, args.join(", "))
+            }
+            Inst::Write { fd, data, len } => write!(f, "write {}, {}, {}", fd, data, len),
+            Inst::Loc { line, col } => write!(f, "loc {}:{}", line, col),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|param| format!("%t{}: {}", param.0, self.temps[param.0].name()))
+            .collect();
+        write!(f, "fn {}({})", self.name, params.join(", "))?;
+        if let Some(ret) = self.ret {
+            write!(f, " -> {}", ret.name())?;
+        }
+        writeln!(f, " {{")?;
+        for (id, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(id))?;
+            for inst in &block.insts {
+                write!(f, "    ")?;
+                self.fmt_inst(inst, f)?;
+                writeln!(f)?;
+            }
+            match &block.term {
+                Terminator::Return(Some(value)) => writeln!(f, "    ret {}", value)?,
+                Terminator::Return(None) => writeln!(f, "    ret")?,
+                Terminator::Jump(target) => writeln!(f, "    jmp {}", target)?,
+                Terminator::Branch {
+                    cond,
+                    then,
+                    otherwise,
+                } => writeln!(f, "    br {}, {}, {}", cond, then, otherwise)?,
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.file.is_empty() {
+            writeln!(f, "file {:?}", self.file)?;
+        }
+        for (id, data) in self.data.iter().enumerate() {
+            writeln!(
+                f,
+                "data @d{} = {:?}",
+                id,
+                String::from_utf8_lossy(&data.bytes)
+            )?;
+        }
+        // A blank line before every function, unless it comes first
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 || !self.data.is_empty() || !self.file.is_empty() {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+//! Just enough JSON to write machine readable output
+//!
+//! Objects keep their keys in insertion order, so the same value always
+//! serializes to the same text.
+
+/// A JSON value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Build an object out of `(key, value)` pairs
+    pub fn object<'a>(fields: impl IntoIterator<Item = (&'a str, Json)>) -> Json {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn string(text: impl Into<String>) -> Json {
+        Json::String(text.into())
+    }
+
+    /// Serialize on a single line
+    pub fn compact(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, None);
+        out
+    }
+
+    /// Serialize with every array element and object field on its own line,
+    /// indented by two spaces
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Some(0));
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: Option<usize>) {
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+            Json::Number(value) => out.push_str(&value.to_string()),
+            Json::String(text) => escape(text, out),
+            Json::Array(items) => write_list(out, indent, '[', ']', items, |item, out, indent| {
+                item.write(out, indent)
+            }),
+            Json::Object(fields) => write_list(
+                out,
+                indent,
+                '{',
+                '}',
+                fields,
+                |(key, value), out, indent| {
+                    escape(key, out);
+                    out.push_str(if indent.is_some() { ": " } else { ":" });
+                    value.write(out, indent);
+                },
+            ),
+        }
+    }
+}
+
+/// Write the items of an array or object between `open` and `close`
+fn write_list<T>(
+    out: &mut String,
+    indent: Option<usize>,
+    open: char,
+    close: char,
+    items: &[T],
+    write_item: impl Fn(&T, &mut String, Option<usize>),
+) {
+    out.push(open);
+    if items.is_empty() {
+        out.push(close);
+        return;
+    }
+    let inner = indent.map(|indent| indent + 1);
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if let Some(inner) = inner {
+            out.push('\n');
+            out.push_str(&"  ".repeat(inner));
+        }
+        write_item(item, out, inner);
+    }
+    if let Some(indent) = indent {
+        out.push('\n');
+        out.push_str(&"  ".repeat(indent));
+    }
+    out.push(close);
+}
+
+/// Write `text` as a quoted JSON string
+fn escape(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
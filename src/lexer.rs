@@ -1,3 +1,8 @@
+use crate::diag::Span;
+use crate::parse1::ParseError;
+use crate::log::{tracing, Stage};
+use crate::trace;
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum Tokens {
     ///C Import
@@ -41,80 +46,119 @@ pub enum Tokens {
     
 }
 
-pub fn lexer(code: &String) -> Vec<Tokens> {
-    let mut code = code.trim().to_string();
-    code.push(' ');
-    let mut toks: Vec<Tokens> = Vec::new();
-    //for line in code.lines(){
+/// A token and the bytes of the source it was lexed from
+#[derive(Debug, Eq, PartialEq)]
+pub struct Token {
+    pub tok: Tokens,
+    pub span: Span,
+}
+
+/// Split source code into tokens
+///
+/// # Parameters
+/// - `&str` : The source code
+///
+/// # Returns
+/// - `(Vec<Token>, Vec<ParseError>)` : The tokens, and the strings left unterminated
+pub fn lexer(code: &str) -> (Vec<Token>, Vec<ParseError>) {
+    let mut toks: Vec<Token> = Vec::new();
+    let mut errors = Vec::new();
     let mut wrd = String::new();
+    // Offset the current word starts at
+    let mut wrd_start = 0;
     let (mut charpos, mut linec) = (0, 0);
-    for char in code.chars() {
+    // Quote character and offset of the string being lexed
+    let mut string: Option<(char, usize)> = None;
+    for (offset, char) in code.char_indices() {
         charpos += 1;
+        let span = Span::new(offset, offset + char.len_utf8());
 
         //println!("[DEBUG] char -> {char} | charpos -> {} | linec -> {}",charpos,linec);
-        match char {
-            '"' => {
-                toks.push(Tokens::DQ);
-            }
-            '\'' => {
-                toks.push(Tokens::SQ);
+        let tok = match char {
+            '"' | '\'' => {
+                match string {
+                    None => string = Some((char, offset)),
+                    Some((quote, _)) if quote == char => string = None,
+                    Some(_) => {}
+                }
+                if char == '"' {
+                    Tokens::DQ
+                } else {
+                    Tokens::SQ
+                }
             }
             '\n' => {
-                if !wrd.is_empty(){
-                toks.push(checkwrd(&wrd));
-                wrd.clear();
+                if let Some((_, start)) = string.take() {
+                    // A \r\n line break isn't part of the string either
+                    let end = if code[..offset].ends_with('\r') { offset - 1 } else { offset };
+                    errors.push(ParseError::UnterminatedString {
+                        span: Span::new(start, end),
+                    });
                 }
-                toks.push(Tokens::Eol);
+                flush(&mut toks, &mut wrd, wrd_start, offset);
                 charpos = 0;
                 linec += 1;
+                Tokens::Eol
             }
-            ' ' => {
-                if !wrd.is_empty(){
-                    toks.push(checkwrd(&wrd));
-                    wrd.clear();
+            ' ' | '\t' | '\r' => {
+                // Words inside strings are just text, never keywords
+                if string.is_none() {
+                    flush(&mut toks, &mut wrd, wrd_start, offset);
                 }
-                toks.push(Tokens::Space);
                 wrd.clear();
+                Tokens::Space
             }
-            '(' => {
-                toks.push(Tokens::LSB);
-            }
-            ')' => {
-                toks.push(Tokens::RSB);
-            }
-            '{' => {
-                toks.push(Tokens::LCurlyB);
-            }
-            '}' => {
-                toks.push(Tokens::RCurlyBrace);
-            }
-            '[' => {
-                toks.push(Tokens::LBBracket);
-            }
-            ']' => {
-                toks.push(Tokens::RBBracket);
-            }
+            '(' => Tokens::LSB,
+            ')' => Tokens::RSB,
+            '{' => Tokens::LCurlyB,
+            '}' => Tokens::RCurlyBrace,
+            '[' => Tokens::LBBracket,
+            ']' => Tokens::RBBracket,
             ';' => {
-                toks.push(Tokens::SColon);
                 wrd.clear();
+                Tokens::SColon
             }
-            ',' => {
-                toks.push(Tokens::Comma);
-            }
-            '_' => {
-                toks.push(Tokens::Underscore);
-            }
+            ',' => Tokens::Comma,
+            '_' => Tokens::Underscore,
             _ => {
+                if wrd.is_empty() {
+                    wrd_start = offset;
+                }
                 wrd.push(char);
-                toks.push(Tokens::Char(charpos, linec, char));
+                Tokens::Char(charpos, linec, char)
             }
+        };
+        toks.push(Token { tok, span });
+    }
+    if let Some((_, start)) = string {
+        errors.push(ParseError::UnterminatedString {
+            span: Span::new(start, code.trim_end().len()),
+        });
+    }
+    flush(&mut toks, &mut wrd, wrd_start, code.len());
+    let end = Span::new(code.len(), code.len());
+    toks.push(Token { tok: Tokens::Eol, span: end });
+    toks.push(Token { tok: Tokens::Eof, span: end });
+    if tracing(Stage::Lexer) {
+        for tok in &toks {
+            trace!(Stage::Lexer, "{:?} at {}..{}", tok.tok, tok.span.start, tok.span.end);
         }
     }
-    toks.push(Tokens::Eol);
-    toks.push(Tokens::Eof);
-    return toks;
+    (toks, errors)
 }
-fn checkwrd(wrd: &String) -> Tokens {
+
+/// Push the keyword token for a finished word, if it is one
+fn flush(toks: &mut Vec<Token>, wrd: &mut String, start: usize, end: usize) {
+    if let Tokens::CImport = checkwrd(wrd) {
+        toks.push(Token {
+            tok: Tokens::CImport,
+            span: Span::new(start, end),
+        });
+    }
+    wrd.clear();
+}
+
+fn checkwrd(wrd: &str) -> Tokens {
     match wrd.trim() {
         "cimport" => Tokens::CImport,
         _ => {
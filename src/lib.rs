@@ -0,0 +1,308 @@
+//! Neitc, a compiler from Neit to C, LLVM IR, x86-64 assembly, QBE IL and
+//! WebAssembly
+//!
+//! The whole pipeline is usable in-process: [`compile_str`] runs every stage
+//! at once, while [`lex`], [`parse`], [`configure`], [`check`], [`lower`],
+//! [`optimize`], [`emit_c`], [`emit_llvm`], [`emit_asm`], [`emit_qbe`] and
+//! [`emit_wasm`] expose each stage on its own and [`analyze`] runs every
+//! stage up to code generation. None of them print or exit, failures come
+//! back as [`Diagnostics`].
+
+use std::fmt;
+
+use backend::Backend;
+use lexer::Token;
+use diag::{Diagnostic, SourceFile};
+use ir::Module;
+use lint::LintLevels;
+use opt::OptLevel;
+use parse1::AST;
+use report::ErrorFormat;
+use target::Target;
+
+pub mod asm;
+pub mod backend;
+pub mod c_ast;
+pub mod cdiag;
+pub mod check;
+pub mod diag;
+pub mod diff;
+pub mod doast;
+pub mod explain;
+pub mod fix;
+pub mod gen;
+pub mod ir;
+pub mod json;
+pub mod lexer;
+pub mod lint;
+pub mod llvm;
+pub mod log;
+pub mod lower;
+pub mod opt;
+pub mod parse1;
+pub mod prelude;
+pub mod qbe;
+pub mod report;
+pub mod suggest;
+pub mod target;
+pub mod toolchain;
+pub mod wasm;
+
+/// Options for [`compile_str`]
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Name of the file the source came from, only used for reporting
+    pub file_name: String,
+    /// Platform to generate code for, the host by default
+    pub target: Target,
+    /// Lint levels from the command line
+    pub lints: LintLevels,
+    /// Optimizations to run on the IR
+    pub opt_level: OptLevel,
+    /// Point the generated code back at `file_name`, with `#line` directives
+    /// in C and debug line info in the other backends
+    pub line_directives: bool,
+    /// Code generator to run on the IR
+    pub backend: Backend,
+}
+
+impl Default for Options {
+    /// The same defaults as the command line, line directives included
+    fn default() -> Self {
+        Options {
+            file_name: String::new(),
+            target: Target::default(),
+            lints: LintLevels::default(),
+            opt_level: OptLevel::default(),
+            line_directives: true,
+            backend: Backend::default(),
+        }
+    }
+}
+
+/// Everything produced by a successful [`compile_str`]
+#[derive(Debug)]
+pub struct Output {
+    /// Tokens returned by the lexer
+    pub tokens: Vec<Token>,
+    /// The parsed, configured and checked AST
+    pub ast: Vec<AST>,
+    /// The AST lowered into the IR, before any optimization
+    pub lowered: Module,
+    /// The lowered IR once optimized
+    pub ir: Module,
+    /// The code generated by the chosen backend
+    pub code: String,
+    /// Libraries requested with `cimport link:<lib>`, to be passed as `-l<lib>`
+    pub link_libs: Vec<String>,
+    /// Lints that fired without stopping compilation
+    pub warnings: Diagnostics,
+}
+
+/// Everything [`analyze`] found out about a program without errors
+#[derive(Debug)]
+pub struct Analysis {
+    /// Tokens returned by the lexer
+    pub tokens: Vec<Token>,
+    /// The parsed, configured and checked AST
+    pub ast: Vec<AST>,
+    /// Lints that fired without stopping compilation
+    pub warnings: Diagnostics,
+}
+
+/// Errors reported by a compiler stage, along with the file they point into
+#[derive(Debug)]
+pub struct Diagnostics {
+    /// The file the diagnostics belong to
+    pub file: SourceFile,
+    /// Every diagnostic, in the order they were found
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    fn new(source: &str, diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            file: SourceFile::new("", source),
+            diagnostics,
+        }
+    }
+
+    /// Attribute the diagnostics to `file_name`, whose content is `source`
+    pub fn in_file(mut self, file_name: &str, source: &str) -> Self {
+        self.file = SourceFile::new(file_name, source);
+        self
+    }
+
+    /// Write the diagnostics as selected with `--error-format`
+    pub fn format(&self, format: ErrorFormat) -> String {
+        report::format(&self.diagnostics, Some(&self.file), format)
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diag in &self.diagnostics {
+            write!(f, "{}", diag.render(Some(&self.file)))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Split source code into tokens
+pub fn lex(source: &str) -> Result<Vec<Token>, Diagnostics> {
+    match lexer::lexer(source) {
+        (tokens, errors) if errors.is_empty() => Ok(tokens),
+        (_, errors) => Err(report_errors(source, &errors)),
+    }
+}
+
+/// Parse tokens from [`lex`] into an AST
+pub fn parse(tokens: &[Token], source: &str) -> Result<Vec<AST>, Diagnostics> {
+    match parse1::parse(tokens, source) {
+        (ast, errors) if errors.is_empty() => Ok(ast),
+        (_, errors) => Err(report_errors(source, &errors)),
+    }
+}
+
+/// Keep only the `#[target_...]` items that apply to `target`
+pub fn configure(ast: Vec<AST>, target: &Target) -> Vec<AST> {
+    target::resolve_cfg(ast, target)
+}
+
+/// Check a configured AST for semantic errors
+pub fn check(ast: &[AST]) -> Result<(), Diagnostics> {
+    check::check(ast).map_err(|errors| report_errors("", &errors))
+}
+
+/// Lower a checked AST into the IR every backend generates code from, `file`
+/// being the source it was parsed from
+pub fn lower(ast: &[AST], file: &SourceFile) -> Module {
+    lower::lower(ast, file)
+}
+
+/// Run the optimizations `level` asks for on the IR
+pub fn optimize(mut module: Module, level: OptLevel) -> Module {
+    opt::optimize(&mut module, level);
+    module
+}
+
+/// Generate C code for `target` from the IR, with `#line` directives pointing
+/// back into the source when `line_directives` is set
+pub fn emit_c(module: &Module, target: &Target, line_directives: bool) -> String {
+    gen::genc(module, target, line_directives)
+}
+
+/// Generate LLVM IR for `target` from the IR, with debug line info pointing
+/// back into the source when `debug_info` is set
+pub fn emit_llvm(module: &Module, target: &Target, debug_info: bool) -> String {
+    llvm::genllvm(module, target, debug_info)
+}
+
+/// Generate x86-64 assembly for `target` from the IR, with `.loc` directives
+/// pointing back into the source when `debug_info` is set
+pub fn emit_asm(module: &Module, target: &Target, debug_info: bool) -> String {
+    asm::genasm(module, target, debug_info)
+}
+
+/// Generate QBE IL for `target` from the IR, with `dbgloc` instructions
+/// pointing back into the source when `debug_info` is set
+pub fn emit_qbe(module: &Module, target: &Target, debug_info: bool) -> String {
+    qbe::genqbe(module, target, debug_info)
+}
+
+/// Generate a WebAssembly text module for WASI from the IR, with comments
+/// pointing back into the source when `debug_info` is set
+pub fn emit_wasm(module: &Module, debug_info: bool) -> String {
+    wasm::genwasm(module, debug_info)
+}
+
+/// Run the whole pipeline on a piece of source code
+///
+/// # Parameters
+/// - `&str` : Neit source code
+/// - `Options` : How to compile it
+///
+/// # Returns
+/// - `Result<Output, Diagnostics>` : The generated C code along with the
+///   intermediate stages, or the errors that stopped compilation
+pub fn compile_str(source: &str, options: Options) -> Result<Output, Diagnostics> {
+    let Analysis {
+        tokens,
+        ast,
+        warnings,
+    } = analyze(source, &options)?;
+    let file = SourceFile::new(&options.file_name, source);
+    let lowered = lower(&ast, &file);
+    let ir = optimize(lowered.clone(), options.opt_level);
+    let code = match options.backend {
+        Backend::C => emit_c(&ir, &options.target, options.line_directives),
+        Backend::Llvm => emit_llvm(&ir, &options.target, options.line_directives),
+        Backend::Asm => emit_asm(&ir, &options.target, options.line_directives),
+        Backend::Qbe => emit_qbe(&ir, &options.target, options.line_directives),
+        Backend::Wasm => emit_wasm(&ir, options.line_directives),
+    };
+    let link_libs = parse1::items(&ast)
+        .into_iter()
+        .filter_map(|node| match node {
+            AST::CLink(lib, _) => Some(lib.clone()),
+            _ => None,
+        })
+        .collect();
+    Ok(Output {
+        tokens,
+        ast,
+        lowered,
+        ir,
+        code,
+        link_libs,
+        warnings,
+    })
+}
+
+/// Lex, parse, configure, check and lint a piece of source code
+///
+/// Chaining [`lex`], [`parse`] and [`check`] stops at the first stage with
+/// errors. Here every stage runs regardless, on whatever the stage before it
+/// recovered, so independent mistakes are all reported at once. Lints only
+/// run on programs without errors.
+///
+/// # Parameters
+/// - `&str` : Neit source code
+/// - `&Options` : How to compile it
+///
+/// # Returns
+/// - `Result<Analysis, Diagnostics>` : The tokens, the configured AST and
+///   any warnings, or every error found, in source order
+pub fn analyze(source: &str, options: &Options) -> Result<Analysis, Diagnostics> {
+    let (tokens, mut errors) = lexer::lexer(source);
+    let (ast, parse_errors) = parse1::parse(&tokens, source);
+    errors.extend(parse_errors);
+    let ast = configure(ast, &options.target);
+    if let Err(check_errors) = check::check(&ast) {
+        errors.extend(check_errors);
+    }
+    if !errors.is_empty() {
+        errors.sort_by_key(|err| err.span().start);
+        return Err(report_errors(source, &errors).in_file(&options.file_name, source));
+    }
+
+    let mut lints = lint::lint(&ast, &options.lints);
+    lints.sort_by_key(|diag| diag.primary_span().map(|span| span.start));
+    let lints = Diagnostics::new(source, lints).in_file(&options.file_name, source);
+    // A denied lint fails compilation, the warnings that came with it are shown too
+    if lints.diagnostics.iter().any(Diagnostic::is_error) {
+        return Err(lints);
+    }
+    Ok(Analysis {
+        tokens,
+        ast,
+        warnings: lints,
+    })
+}
+
+fn report_errors(source: &str, errors: &[parse1::ParseError]) -> Diagnostics {
+    let diags = errors.iter().map(|err| err.to_diagnostic()).collect();
+    Diagnostics::new(source, diags)
+}
@@ -0,0 +1,279 @@
+//! Lints, warnings about programs that compile but probably don't do what
+//! was meant
+//!
+//! Every [`Lint`] has a default [`LintLevel`] that can be changed for the
+//! whole program with `-A`, `-W` and `-D <lint>` on the command line, or in
+//! the source with `#![allow(<lint>)]` for the whole file and `#[allow(<lint>)]`
+//! for the next item, `warn` and `deny` work the same way. `warnings` names
+//! every lint that would otherwise warn, so `-D warnings` makes them errors.
+//!
+//! Unused variables, unreachable code and shadowing are [`PLANNED`]: Neit has
+//! no variables or control flow yet for them to look at.
+
+use std::fmt;
+
+use crate::diag::{Applicability, Diagnostic, Severity};
+use crate::ir::string_bytes;
+use crate::parse1::{items, LintAttr, Wrt, AST};
+use crate::suggest::best_match;
+
+/// What happens when a lint fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintLevel {
+    /// Nothing
+    Allow,
+    /// A warning, compilation goes on
+    Warn,
+    /// An error
+    Deny,
+}
+
+impl LintLevel {
+    /// Name of the level in attributes, e.g. `allow`
+    pub fn name(self) -> &'static str {
+        match self {
+            LintLevel::Allow => "allow",
+            LintLevel::Warn => "warn",
+            LintLevel::Deny => "deny",
+        }
+    }
+
+    /// Parse the name of an attribute, e.g. `deny`
+    pub fn parse(name: &str) -> Option<LintLevel> {
+        match name {
+            "allow" => Some(LintLevel::Allow),
+            "warn" => Some(LintLevel::Warn),
+            "deny" => Some(LintLevel::Deny),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A check that warns about suspicious code
+#[derive(Debug, PartialEq, Eq)]
+pub struct Lint {
+    /// Name used on the command line and in attributes
+    pub name: &'static str,
+    pub default: LintLevel,
+    pub description: &'static str,
+}
+
+/// `__wrt__` writing a different number of bytes than its text holds
+pub const WRT_SIZE_MISMATCH: Lint = Lint {
+    name: "wrt_size_mismatch",
+    default: LintLevel::Warn,
+    description: "the size given to __wrt__ doesn't match the length of the text",
+};
+
+/// `cimport cstd` without anything using it
+pub const UNUSED_CIMPORT: Lint = Lint {
+    name: "unused_cimport",
+    default: LintLevel::Warn,
+    description: "a library is imported but never used",
+};
+
+/// Lint attributes naming a lint that doesn't exist
+pub const UNKNOWN_LINTS: Lint = Lint {
+    name: "unknown_lints",
+    default: LintLevel::Warn,
+    description: "an attribute names a lint that doesn't exist",
+};
+
+/// Every lint, in the order they are listed
+pub const LINTS: &[&Lint] = &[&WRT_SIZE_MISMATCH, &UNUSED_CIMPORT, &UNKNOWN_LINTS];
+
+/// Lints that wait for the language to have what they check, with what that is
+pub const PLANNED: &[(&str, &str)] = &[
+    ("unused_variables", "variables"),
+    ("unreachable_code", "control flow"),
+    ("shadowing", "variables"),
+];
+
+/// Name of the group holding every lint that warns
+const WARNINGS: &str = "warnings";
+
+/// The level of every lint, defaults overridden by the command line and attributes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintLevels {
+    /// Levels set for single lints, later ones win
+    levels: Vec<(&'static str, LintLevel)>,
+    /// Level of every lint that would otherwise warn, set through `warnings`
+    warnings: Option<LintLevel>,
+}
+
+impl LintLevels {
+    /// Set the level of a lint, or of every warning with `warnings`
+    ///
+    /// # Parameters
+    /// - `&str` : Name of the lint
+    /// - `LintLevel` : The level it gets
+    ///
+    /// # Returns
+    /// - `Result<(), String>` : Why the lint can't be set, when there is no such lint
+    pub fn set(&mut self, name: &str, level: LintLevel) -> Result<(), String> {
+        if name == WARNINGS {
+            self.warnings = Some(level);
+            return Ok(());
+        }
+        match LINTS.iter().find(|lint| lint.name == name) {
+            Some(lint) => {
+                self.levels.push((lint.name, level));
+                Ok(())
+            }
+            None => {
+                if let Some((_, needs)) = PLANNED.iter().find(|(planned, _)| *planned == name) {
+                    return Err(format!(
+                        "Lint '{}' isn't available yet, Neit has no {}",
+                        name, needs
+                    ));
+                }
+                let names: Vec<&str> = LINTS.iter().map(|lint| lint.name).collect();
+                Err(match best_match(name, &names) {
+                    Some(close) => format!("Unknown lint '{}', did you mean '{}'?", name, close),
+                    None => format!("Unknown lint '{}'", name),
+                })
+            }
+        }
+    }
+
+    /// Level `lint` ends up at
+    pub fn level(&self, lint: &Lint) -> LintLevel {
+        let level = self
+            .levels
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == lint.name)
+            .map_or(lint.default, |(_, level)| *level);
+        match (level, self.warnings) {
+            (LintLevel::Warn, Some(warnings)) => warnings,
+            _ => level,
+        }
+    }
+
+    /// Check whether the level of `lint` is still its default
+    fn is_default(&self, lint: &Lint) -> bool {
+        self.warnings.is_none() && self.levels.iter().all(|(name, _)| *name != lint.name)
+    }
+}
+
+/// Run every lint over a checked AST
+///
+/// # Parameters
+/// - `&[AST]` : The configured and checked AST
+/// - `&LintLevels` : Levels from the command line
+///
+/// # Returns
+/// - `Vec<Diagnostic>` : Warnings, and errors for denied lints
+pub fn lint(ast: &[AST], levels: &LintLevels) -> Vec<Diagnostic> {
+    let mut cx = LintContext {
+        diags: Vec::new(),
+        uses_cstd: items(ast).iter().any(|node| matches!(node, AST::CWrt(_))),
+    };
+    // File wide attributes apply before and after them
+    let mut levels = levels.clone();
+    for node in items(ast) {
+        if let AST::InnerLint(attr) = node {
+            cx.apply(&mut levels, attr);
+        }
+    }
+    cx.walk(ast, &levels);
+    cx.diags
+}
+
+struct LintContext {
+    diags: Vec<Diagnostic>,
+    /// Whether anything needs `cimport cstd`
+    uses_cstd: bool,
+}
+
+impl LintContext {
+    fn walk(&mut self, ast: &[AST], levels: &LintLevels) {
+        for node in ast {
+            match node {
+                AST::Lint(attr, items) => {
+                    let mut levels = levels.clone();
+                    self.apply(&mut levels, attr);
+                    self.walk(items, &levels);
+                }
+                AST::Cfg(_, items) => self.walk(items, levels),
+                AST::CWrt(wrt) => self.wrt_size_mismatch(wrt, levels),
+                AST::Cimport(lib, span) if lib == "cstd" && !self.uses_cstd => {
+                    let diag = Diagnostic::warning("Unused import of cstd")
+                        .with_label(*span, "imported here but never used")
+                        .with_help("remove the import");
+                    self.emit(&UNUSED_CIMPORT, levels, diag);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Change `levels` as asked for by an attribute
+    fn apply(&mut self, levels: &mut LintLevels, attr: &LintAttr) {
+        for (name, span) in &attr.lints {
+            if levels.set(name, attr.level).is_err() {
+                let names: Vec<&str> = LINTS.iter().map(|lint| lint.name).collect();
+                let mut diag = Diagnostic::warning(format!("Unknown lint '{}'", name))
+                    .with_label(*span, "no such lint");
+                if let Some((_, needs)) = PLANNED.iter().find(|(planned, _)| planned == name) {
+                    diag = diag.with_note(format!("it isn't available yet, Neit has no {}", needs));
+                }
+                if let Some(close) = best_match(name, &names) {
+                    diag = diag.with_suggestion(
+                        *span,
+                        close,
+                        "a lint with a similar name exists",
+                        Applicability::MachineApplicable,
+                    );
+                }
+                self.emit(&UNKNOWN_LINTS, levels, diag);
+            }
+        }
+    }
+
+    fn wrt_size_mismatch(&mut self, wrt: &Wrt, levels: &LintLevels) {
+        let len = literal_len(&wrt.text);
+        if i64::from(wrt.size) == len as i64 {
+            return;
+        }
+        let diag = Diagnostic::warning(format!(
+            "'__wrt__' writes {} bytes but the text is {} bytes long",
+            wrt.size, len
+        ))
+        .with_label(wrt.args[2], format!("{} bytes", wrt.size))
+        .with_secondary(wrt.args[1], format!("{} bytes", len))
+        .with_suggestion(
+            wrt.args[2],
+            len.to_string(),
+            "write the whole text",
+            Applicability::MachineApplicable,
+        );
+        self.emit(&WRT_SIZE_MISMATCH, levels, diag);
+    }
+
+    /// Report `diag` at the level `lint` is at
+    fn emit(&mut self, lint: &Lint, levels: &LintLevels, mut diag: Diagnostic) {
+        let level = levels.level(lint);
+        diag.severity = match level {
+            LintLevel::Allow => return,
+            LintLevel::Warn => Severity::Warning,
+            LintLevel::Deny => Severity::Error,
+        };
+        if levels.is_default(lint) {
+            diag = diag.with_note(format!("'#[{}({})]' on by default", level, lint.name));
+        }
+        self.diags.push(diag.with_code(lint.name));
+    }
+}
+
+/// Number of bytes a string literal holds, quotes excluded and an escape like
+/// `\n` or `\x41` counting as one, the way the C compiler sees it
+pub fn literal_len(text: &str) -> usize {
+    string_bytes(text).len()
+}
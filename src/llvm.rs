@@ -0,0 +1,519 @@
+//! LLVM IR backend
+//!
+//! Generates textual LLVM IR (`.ll`) for clang or llc to compile. Every
+//! temporary gets a stack slot, loaded before each use and stored after each
+//! assignment, so the IR not being in SSA form doesn't matter; LLVM's
+//! `mem2reg` turns the slots back into registers when optimizing. Pointers are
+//! written as `i8*`, which LLVM versions with opaque pointers read as `ptr`.
+//!
+//! Functions other than `main` are internal and use the `fastcc` calling
+//! convention. [`Inst::Loc`] becomes debug line info, the counterpart of the C
+//! backend's `#line` directives.
+
+use crate::ir::{BinOp, BlockId, Function, Inst, Module, Temp, Terminator, Type, Value};
+use crate::log::Stage;
+use crate::target::Target;
+use crate::trace;
+
+/// Generate LLVM IR out of an IR module
+///
+/// # Parameters
+/// - `&Module` : The module returned from `lower()`
+/// - `&Target` : Platform the IR will be compiled for
+/// - `bool` : Whether to attach debug line info pointing back at the source
+///
+/// # Returns
+/// - `String` : The generated LLVM IR
+pub fn genllvm(module: &Module, target: &Target, debug_info: bool) -> String {
+    let mut gen = Gen {
+        module,
+        target,
+        // Without a file name there is nothing to point at
+        debug: debug_info && !module.file.is_empty(),
+        out: String::new(),
+        meta: Vec::new(),
+        next: 0,
+        scope: 0,
+        loc: None,
+    };
+    gen.module();
+    gen.out
+}
+
+/// State of code generation for a module
+struct Gen<'a> {
+    module: &'a Module,
+    target: &'a Target,
+    debug: bool,
+    out: String,
+    /// Metadata nodes, `!N` being `meta[N]`
+    meta: Vec<String>,
+    /// Number of the next unnamed value of the current function
+    next: usize,
+    /// Subprogram of the current function
+    scope: usize,
+    /// Location of the instructions being generated
+    loc: Option<usize>,
+}
+
+impl Gen<'_> {
+    fn module(&mut self) {
+        let file = quote(self.module.file.as_bytes());
+        if !self.module.file.is_empty() {
+            self.out
+                .push_str(&format!("; ModuleID = '{}'\n", self.module.file));
+            self.out
+                .push_str(&format!("source_filename = \"{}\"\n", file));
+        }
+        self.out
+            .push_str(&format!("target triple = \"{}\"\n", self.target.triple));
+        if self.debug {
+            self.meta(String::from("distinct !DICompileUnit(language: DW_LANG_C99, file: !1, producer: \"neitc\", isOptimized: false, runtimeVersion: 0, emissionKind: LineTablesOnly)"));
+            self.meta(format!("!DIFile(filename: \"{}\", directory: \"\")", file));
+            self.meta(String::from("!{i32 7, !\"Dwarf Version\", i32 4}"));
+            self.meta(String::from("!{i32 2, !\"Debug Info Version\", i32 3}"));
+            self.meta(String::from("!{}"));
+            self.meta(String::from("!DISubroutineType(types: !4)"));
+        }
+
+        if !self.module.data.is_empty() {
+            self.out.push('\n');
+        }
+        for (id, data) in self.module.data.iter().enumerate() {
+            // NUL terminated, like the C backend's string literals
+            let mut bytes = data.bytes.clone();
+            bytes.push(0);
+            self.out.push_str(&format!(
+                "@d{} = private unnamed_addr constant [{} x i8] c\"{}\", align 1\n",
+                id,
+                bytes.len(),
+                quote(&bytes)
+            ));
+        }
+
+        let declarations = self.declarations();
+        if !declarations.is_empty() {
+            self.out.push('\n');
+        }
+        for declaration in declarations {
+            self.out.push_str(&declaration);
+            self.out.push('\n');
+        }
+
+        for function in &self.module.functions {
+            trace!(Stage::Gen, "Generating LLVM IR for {}", function.name);
+            self.out.push('\n');
+            self.function(function);
+        }
+
+        if self.debug {
+            self.out.push_str("\n!llvm.dbg.cu = !{!0}\n");
+            self.out.push_str("!llvm.module.flags = !{!2, !3}\n\n");
+            for (id, node) in self.meta.iter().enumerate() {
+                self.out.push_str(&format!("!{} = {}\n", id, node));
+            }
+        }
+    }
+
+    /// Add a metadata node, returning its number
+    fn meta(&mut self, node: String) -> usize {
+        if let Some(id) = self.meta.iter().position(|other| *other == node) {
+            return id;
+        }
+        self.meta.push(node);
+        self.meta.len() - 1
+    }
+
+    /// Declarations of the functions called but not defined in the module
+    fn declarations(&self) -> Vec<String> {
+        let mut declarations = Vec::new();
+        let insts = self
+            .module
+            .functions
+            .iter()
+            .flat_map(|function| function.blocks.iter().map(move |block| (function, block)))
+            .flat_map(|(function, block)| block.insts.iter().map(move |inst| (function, inst)));
+        for (function, inst) in insts {
+            let declaration = match inst {
+                Inst::Write { .. } => {
+                    let (size, name) = self.write_call();
+                    let size = llvm_type(size);
+                    format!("declare {} @{}(i32, i8*, {})", size, name, size)
+                }
+                Inst::Call {
+                    dest,
+                    function: callee,
+                    args,
+                } if self.module.function(callee).is_none() => format!(
+                    "declare {} @{}({})",
+                    dest.map_or("void", |dest| llvm_type(function.temps[dest.0])),
+                    callee,
+                    args.iter()
+                        .map(|arg| llvm_type(function.type_of(arg)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                _ => continue,
+            };
+            if !declarations.contains(&declaration) {
+                declarations.push(declaration);
+            }
+        }
+        declarations
+    }
+
+    /// Integer type of the length and result of the write function, and its name
+    fn write_call(&self) -> (Type, &'static str) {
+        let function = self.target.write_call().function;
+        // `_write` takes and returns an `int`, `write` a `size_t` and `ssize_t`
+        if self.target.is_windows() || self.target.pointer_width() == 32 {
+            (Type::I32, function)
+        } else {
+            (Type::I64, function)
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.next = 0;
+        self.loc = None;
+        let ret = function.ret.map_or("void", llvm_type);
+        let params = function
+            .params
+            .iter()
+            .map(|param| format!("{} %p{}", llvm_type(function.temps[param.0]), param.0))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let linkage = if function.name == "main" {
+            ""
+        } else {
+            "internal fastcc "
+        };
+        let dbg = if self.debug {
+            let line = function
+                .blocks
+                .iter()
+                .flat_map(|block| &block.insts)
+                .find_map(|inst| match inst {
+                    Inst::Loc { line, .. } => Some(*line),
+                    _ => None,
+                })
+                .unwrap_or(1);
+            self.scope = self.meta(format!(
+                "distinct !DISubprogram(name: \"{}\", scope: !1, file: !1, line: {}, type: !5, scopeLine: {}, spFlags: DISPFlagDefinition, unit: !0)",
+                function.name, line, line
+            ));
+            // Code before the first `loc` has no line of its own
+            self.loc = Some(self.location(0, 0));
+            format!(" !dbg !{}", self.scope)
+        } else {
+            String::new()
+        };
+        self.out.push_str(&format!(
+            "define {}{} @{}({}){} {{\n",
+            linkage, ret, function.name, params, dbg
+        ));
+
+        // Every temporary lives in a stack slot, parameters are copied into theirs
+        self.out.push_str("entry:\n");
+        for (id, ty) in function.temps.iter().enumerate() {
+            self.inst(format!("%t{} = alloca {}", id, llvm_type(*ty)));
+        }
+        for param in &function.params {
+            let ty = llvm_type(function.temps[param.0]);
+            self.inst(format!("store {} %p{}, {}* %t{}", ty, param.0, ty, param.0));
+        }
+        self.inst(format!("br label %{}", label(BlockId(0))));
+
+        for (id, block) in function.blocks.iter().enumerate() {
+            self.out.push_str(&format!("\n{}:\n", label(BlockId(id))));
+            for inst in &block.insts {
+                self.gen_inst(function, inst);
+            }
+            self.terminator(function, &block.term);
+        }
+        self.out.push_str("}\n");
+    }
+
+    /// Debug location in the current function
+    fn location(&mut self, line: usize, col: usize) -> usize {
+        self.meta(format!(
+            "!DILocation(line: {}, column: {}, scope: !{})",
+            line, col, self.scope
+        ))
+    }
+
+    /// Write an instruction, with the current debug location
+    fn inst(&mut self, inst: String) {
+        match self.loc {
+            Some(loc) => self.out.push_str(&format!("  {}, !dbg !{}\n", inst, loc)),
+            None => self.out.push_str(&format!("  {}\n", inst)),
+        }
+    }
+
+    /// Name for a new unnamed value
+    fn fresh(&mut self) -> String {
+        self.next += 1;
+        format!("%v{}", self.next - 1)
+    }
+
+    fn gen_inst(&mut self, function: &Function, inst: &Inst) {
+        match inst {
+            Inst::Loc { line, col } => {
+                if self.debug {
+                    self.loc = Some(self.location(*line, *col));
+                }
+            }
+            Inst::Copy { dest, value } => {
+                let ty = function.temps[dest.0];
+                let value = self.value(function, value, ty);
+                self.store(*dest, ty, value);
+            }
+            Inst::Binary { dest, op, lhs, rhs } => {
+                let ty = function.temps[dest.0];
+                let result = if op.is_comparison() {
+                    // Made in the wider of the operand types, like in C
+                    let operands = if [lhs, rhs].iter().all(|v| function.type_of(v) == Type::I32) {
+                        Type::I32
+                    } else {
+                        Type::I64
+                    };
+                    let lhs = self.value(function, lhs, operands);
+                    let rhs = self.value(function, rhs, operands);
+                    let cmp = self.fresh();
+                    self.inst(format!(
+                        "{} = {} {} {}, {}",
+                        cmp,
+                        instruction(*op),
+                        llvm_type(operands),
+                        lhs,
+                        rhs
+                    ));
+                    let result = self.fresh();
+                    self.inst(format!("{} = zext i1 {} to {}", result, cmp, llvm_type(ty)));
+                    result
+                } else {
+                    let lhs = self.value(function, lhs, ty);
+                    let rhs = self.value(function, rhs, ty);
+                    let result = self.fresh();
+                    self.inst(format!(
+                        "{} = {} {} {}, {}",
+                        result,
+                        instruction(*op),
+                        llvm_type(ty),
+                        lhs,
+                        rhs
+                    ));
+                    result
+                };
+                self.store(*dest, ty, result);
+            }
+            Inst::Call {
+                dest,
+                function: callee,
+                args,
+            } => {
+                // Arguments take the callee's parameter types when it is known
+                let defined = self.module.function(callee);
+                let types: Vec<Type> = match defined {
+                    Some(callee) => callee
+                        .params
+                        .iter()
+                        .map(|param| callee.temps[param.0])
+                        .collect(),
+                    None => args.iter().map(|arg| function.type_of(arg)).collect(),
+                };
+                let args = args
+                    .iter()
+                    .zip(&types)
+                    .map(|(arg, ty)| {
+                        format!("{} {}", llvm_type(*ty), self.value(function, arg, *ty))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let cc = if defined.is_some() && callee != "main" {
+                    "fastcc "
+                } else {
+                    ""
+                };
+                let ret = match defined {
+                    Some(callee) => callee.ret,
+                    None => dest.map(|dest| function.temps[dest.0]),
+                };
+                let call = format!(
+                    "call {}{} @{}({})",
+                    cc,
+                    ret.map_or("void", llvm_type),
+                    callee,
+                    args
+                );
+                match (dest, ret) {
+                    (Some(dest), Some(ret)) => {
+                        let result = self.fresh();
+                        self.inst(format!("{} = {}", result, call));
+                        let ty = function.temps[dest.0];
+                        let result = self.convert(result, ret, ty);
+                        self.store(*dest, ty, result);
+                    }
+                    _ => self.inst(call),
+                }
+            }
+            Inst::Write { fd, data, len } => {
+                let (size, name) = self.write_call();
+                let fd = self.value(function, fd, Type::I32);
+                let data = self.value(function, data, Type::Ptr);
+                let len = self.value(function, len, size);
+                let size = llvm_type(size);
+                self.inst(format!(
+                    "call {} @{}(i32 {}, i8* {}, {} {})",
+                    size, name, fd, data, size, len
+                ));
+            }
+        }
+    }
+
+    fn terminator(&mut self, function: &Function, term: &Terminator) {
+        match term {
+            Terminator::Return(value) => match (value, function.ret) {
+                (Some(value), Some(ty)) => {
+                    let value = self.value(function, value, ty);
+                    self.inst(format!("ret {} {}", llvm_type(ty), value));
+                }
+                (None, Some(ty)) => self.inst(format!("ret {} undef", llvm_type(ty))),
+                (_, None) => self.inst(String::from("ret void")),
+            },
+            Terminator::Jump(to) => self.inst(format!("br label %{}", label(*to))),
+            Terminator::Branch {
+                cond,
+                then,
+                otherwise,
+            } => {
+                // Any value other than zero or null is true, like in C
+                let ty = function.type_of(cond);
+                let value = self.value(function, cond, ty);
+                let cond = self.fresh();
+                let zero = if ty == Type::Ptr { "null" } else { "0" };
+                self.inst(format!(
+                    "{} = icmp ne {} {}, {}",
+                    cond,
+                    llvm_type(ty),
+                    value,
+                    zero
+                ));
+                self.inst(format!(
+                    "br i1 {}, label %{}, label %{}",
+                    cond,
+                    label(*then),
+                    label(*otherwise)
+                ));
+            }
+        }
+    }
+
+    fn store(&mut self, dest: Temp, ty: Type, value: String) {
+        self.inst(format!(
+            "store {} {}, {}* %t{}",
+            llvm_type(ty),
+            value,
+            llvm_type(ty),
+            dest.0
+        ));
+    }
+
+    /// Operand for a value, converted to `ty`
+    fn value(&mut self, function: &Function, value: &Value, ty: Type) -> String {
+        match value {
+            // Constants are truncated the way a conversion would
+            Value::Int(value, _) => match ty {
+                Type::I32 => (*value as i32).to_string(),
+                Type::I64 => value.to_string(),
+                Type::Ptr if *value == 0 => String::from("null"),
+                Type::Ptr => format!("inttoptr (i64 {} to i8*)", value),
+            },
+            Value::Temp(temp) => {
+                let from = function.temps[temp.0];
+                let loaded = self.fresh();
+                self.inst(format!(
+                    "{} = load {}, {}* %t{}",
+                    loaded,
+                    llvm_type(from),
+                    llvm_type(from),
+                    temp.0
+                ));
+                self.convert(loaded, from, ty)
+            }
+            Value::Data(data) => {
+                let len = self.module.data[data.0].bytes.len() + 1;
+                let pointer = format!(
+                    "getelementptr inbounds ([{} x i8], [{} x i8]* @d{}, i64 0, i64 0)",
+                    len, len, data.0
+                );
+                self.convert(pointer, Type::Ptr, ty)
+            }
+        }
+    }
+
+    /// Convert a value between types, the way C converts between `int`,
+    /// `long long` and pointers
+    fn convert(&mut self, value: String, from: Type, to: Type) -> String {
+        let op = match (from, to) {
+            _ if from == to => return value,
+            (Type::I32, Type::I64) => "sext",
+            (Type::Ptr, _) => "ptrtoint",
+            (_, Type::Ptr) => "inttoptr",
+            _ => "trunc",
+        };
+        let result = self.fresh();
+        self.inst(format!(
+            "{} = {} {} {} to {}",
+            result,
+            op,
+            llvm_type(from),
+            value,
+            llvm_type(to)
+        ));
+        result
+    }
+}
+
+/// LLVM type of an IR type
+fn llvm_type(ty: Type) -> &'static str {
+    match ty {
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::Ptr => "i8*",
+    }
+}
+
+/// Label of a block
+fn label(id: BlockId) -> String {
+    format!("bb{}", id.0)
+}
+
+/// Instruction computing an operator, comparisons give an `i1`
+fn instruction(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::Div => "sdiv",
+        BinOp::Rem => "srem",
+        BinOp::Eq => "icmp eq",
+        BinOp::Ne => "icmp ne",
+        BinOp::Lt => "icmp slt",
+        BinOp::Le => "icmp sle",
+        BinOp::Gt => "icmp sgt",
+        BinOp::Ge => "icmp sge",
+    }
+}
+
+/// Escape bytes for a quoted LLVM string, anything but printable ASCII
+/// becomes `\XX`
+fn quote(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            0x20..=0x7e if byte != b'"' && byte != b'\\' => char::from(byte).to_string(),
+            _ => format!("\\{:02X}", byte),
+        })
+        .collect()
+}
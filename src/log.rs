@@ -0,0 +1,112 @@
+//! Leveled logging for the compiler
+//!
+//! Successful compiles are silent by default. `-v` and `-vv` raise the
+//! [`Level`], while `--trace=lexer,parser,gen` turns on the per-token
+//! tracing of individual [`Stage`]s. Everything goes to stderr.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How chatty the compiler is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Only errors, `--quiet`
+    Quiet = 0,
+    /// Errors and warnings, the default
+    Normal = 1,
+    /// What the driver is doing, `-v`
+    Verbose = 2,
+    /// Everything including stage traces, `-vv`
+    Debug = 3,
+}
+
+/// Compiler stages that can be traced on their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Lexer = 1,
+    Parser = 2,
+    Gen = 4,
+}
+
+impl Stage {
+    /// Name used for the stage on the command line and in trace output
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Lexer => "lexer",
+            Stage::Parser => "parser",
+            Stage::Gen => "gen",
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+static TRACE: AtomicU8 = AtomicU8::new(0);
+
+/// Set the global log level
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Check whether messages at `level` are shown
+pub fn enabled(level: Level) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Turn on tracing for a single stage
+pub fn enable_trace(stage: Stage) {
+    TRACE.fetch_or(stage as u8, Ordering::Relaxed);
+}
+
+/// Check whether `stage` is being traced, `-vv` traces every stage
+pub fn tracing(stage: Stage) -> bool {
+    enabled(Level::Debug) || TRACE.load(Ordering::Relaxed) & stage as u8 != 0
+}
+
+/// Parse the value of `--trace`, a comma separated list of stage names
+///
+/// # Parameters
+/// - `&str` : The list, e.g. `lexer,parser`
+///
+/// # Returns
+/// - `Result<Vec<Stage>, String>` : The stages, or the first unknown name
+pub fn parse_trace(spec: &str) -> Result<Vec<Stage>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| match name {
+            "lexer" => Ok(Stage::Lexer),
+            "parser" => Ok(Stage::Parser),
+            "gen" => Ok(Stage::Gen),
+            _ => Err(name.to_string()),
+        })
+        .collect()
+}
+
+/// Log a message shown with `-v`
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Verbose) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Log a message shown with `-vv`
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Debug) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Log a message from a compiler stage, shown when that stage is traced
+#[macro_export]
+macro_rules! trace {
+    ($stage:expr, $($arg:tt)*) => {
+        if $crate::log::tracing($stage) {
+            eprintln!("[{}] {}", $stage.name(), format_args!($($arg)*));
+        }
+    };
+}
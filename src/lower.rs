@@ -0,0 +1,77 @@
+//! Lowering the checked AST into the IR
+//!
+//! Top level statements become the body of `main`, which returns 0 once they
+//! have all run. Each statement is preceded by an [`Inst::Loc`] telling where
+//! in the source it came from.
+//!
+//! Lowering doesn't try to be clever: a `__wrt__` checks at run time that its
+//! size stays within the text, and it's up to the optimizer to decide the
+//! check when the size is a constant.
+
+use crate::diag::SourceFile;
+use crate::ir::{string_bytes, BinOp, BlockId, Function, Inst, Module, Terminator, Type, Value};
+use crate::log::Stage;
+use crate::parse1::{items, AST};
+use crate::target::Target;
+use crate::trace;
+
+/// Lower a checked AST into an IR module
+///
+/// # Parameters
+/// - `&[AST]` : The configured and checked AST
+/// - `&SourceFile` : The source the AST was parsed from
+///
+/// # Returns
+/// - `Module` : The whole program, with a `main` function
+pub fn lower(ast: &[AST], file: &SourceFile) -> Module {
+    let mut module = Module {
+        file: file.name.clone(),
+        ..Module::default()
+    };
+    let mut main = Function::new("main", &[], Some(Type::I32));
+    let mut current = BlockId(0);
+
+    for item in items(ast) {
+        trace!(Stage::Gen, "Lowering {:?}", item);
+        if let AST::CWrt(wrt) = item {
+            let (line, col) = file.line_col(wrt.span.start);
+            let bytes = string_bytes(&wrt.text);
+            // The text is stored with a NUL after it, never write past that.
+            // Sizes are never negative, the parser rejects them
+            let stored = Value::Int(bytes.len() as i64 + 1, Type::I64);
+            let size = Value::Int(i64::from(wrt.size), Type::I64);
+            let data = module.data(bytes);
+
+            let (fits, len) = (main.temp(Type::I32), main.temp(Type::I64));
+            let (within, clamped, write) = (main.block(), main.block(), main.block());
+            main.blocks[current.0].insts.extend([
+                Inst::Loc { line, col },
+                Inst::Binary {
+                    dest: fits,
+                    op: BinOp::Le,
+                    lhs: size,
+                    rhs: stored,
+                },
+            ]);
+            main.blocks[current.0].term = Terminator::Branch {
+                cond: Value::Temp(fits),
+                then: within,
+                otherwise: clamped,
+            };
+            for (block, value) in [(within, size), (clamped, stored)] {
+                main.blocks[block.0].insts = vec![Inst::Copy { dest: len, value }];
+                main.blocks[block.0].term = Terminator::Jump(write);
+            }
+            main.blocks[write.0].insts = vec![Inst::Write {
+                fd: Value::Int(i64::from(Target::fd(wrt.stream)), Type::I32),
+                data: Value::Data(data),
+                len: Value::Temp(len),
+            }];
+            current = write;
+        }
+    }
+
+    main.blocks[current.0].term = Terminator::Return(Some(Value::Int(0, Type::I32)));
+    module.functions.push(main);
+    module
+}
@@ -1,120 +1,490 @@
 use std::{
-    env::args,
-    fs::{read_to_string, File, write},
-    process::{exit, Command},
+    env::{args, temp_dir},
+    fs::{create_dir_all, read_to_string, remove_dir_all, remove_file, write},
+    path::{Path, PathBuf},
+    process::{exit, Command, ExitStatus},
+    sync::OnceLock,
 };
 
-use gen::genc;
-use lexer::lexer;
-use parse1::parse;
+use cli::{parse_args, Args, EmitStage, HELP};
+use neitc::{
+    analyze,
+    backend::Backend,
+    cdiag, compile_str,
+    diag::{Diagnostic, SourceFile},
+    diff::unified_diff,
+    explain::explain,
+    fix,
+    log::{self, Level},
+    report::{self, ErrorFormat},
+    target::Target,
+    qbe::qbe_target,
+    toolchain::{wasi_runner, AsmToolchain, LlvmToolchain, QbeToolchain, Toolchain, WasmToolchain},
+    verbose, Diagnostics, Options, Output,
+};
+
+mod cli;
 
-pub mod doast;
-pub mod gen;
-pub mod lexer;
-pub mod parse1;
+/// Format chosen with `--error-format`
+static ERROR_FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+
+fn error_format() -> ErrorFormat {
+    ERROR_FORMAT.get().copied().unwrap_or_default()
+}
 
 fn print_help() {
-    println!(
-        "Usage: neitc <input_file> [options]\n\n\
-        Options:\n\
-        --bcompiler, -bc <compiler>   Specify the C compiler (default: clang)\n\
-        --output, -o <file>           Specify the output file for the generated C code\n\
-        --help, -h                    Show this help message\n"
-    );
+    println!("{}", HELP);
 }
 
 fn main() {
-    let args = args().collect::<Vec<String>>();
-    if args.len() < 2 {
-        eprintln!("Error: Correct args not provided");
+    let args = args().skip(1).collect::<Vec<String>>();
+    if args.is_empty() {
+        eprint!(
+            "{}",
+            Diagnostic::error("Correct args not provided").render(None)
+        );
         print_help();
         exit(1);
     }
-    let _cmd = &args[0];
-    let file = &args[1];
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprint!("{}", Diagnostic::error(e).render(None));
+            print_help();
+            exit(1);
+        }
+    };
+    ERROR_FORMAT.set(args.error_format).ok();
 
-    // Check for --help or -h flag
-    if args.contains(&"--help".to_string()) || args.contains(&"-h".to_string()) {
-        print_help();
-        exit(0);
+    // `--stage=llvm` prints LLVM IR whatever `--backend` says
+    let backend = match args.command {
+        cli::Command::Emit(EmitStage::Code(backend)) => backend,
+        _ => args.backend,
+    };
+    let target = match &args.target {
+        Some(triple) => Target::parse(triple).unwrap_or_else(|e| fail(Diagnostic::error(e))),
+        None => backend.default_target(),
+    };
+
+    let result = match args.command {
+        cli::Command::Help => {
+            print_help();
+            Ok(())
+        }
+        cli::Command::Explain(ref code) => match explain(code) {
+            Some(explanation) => {
+                print!("{}", explanation.render());
+                Ok(())
+            }
+            None => Err(Diagnostic::error(format!("No error code '{}'", code)).into()),
+        },
+        cli::Command::Build => {
+            let binary = match &args.output {
+                Some(binary) => PathBuf::from(binary),
+                None => default_binary(&args.input, &target),
+            };
+            let dir = TempDir::new(format!("neitc-{}", std::process::id()), args.keep_c);
+            let code_file = match &args.emit_c {
+                Some(code_file) => PathBuf::from(code_file),
+                None => dir.path.join(
+                    Path::new(&binary)
+                        .with_extension(args.backend.extension())
+                        .file_name()
+                        .unwrap_or_default(),
+                ),
+            };
+            let built = build(&args, &target, &code_file, &binary);
+            if built.is_ok() {
+                verbose!(
+                    "Compilation successful! Executable created as '{}'.",
+                    binary.display()
+                );
+            }
+            built
+        }
+        // WebAssembly runs in a runtime on any machine
+        cli::Command::Run if !target.is_host() && !target.is_wasi() => Err(Diagnostic::error(
+            format!("Can't run a program built for {} on this machine", target),
+        )
+        .with_help("use 'neitc build' to only build it")
+        .into()),
+        // The temporary directory is gone once `run` returns
+        cli::Command::Run => match run(&args, &target) {
+            Ok(code) => exit(code),
+            Err(failure) => Err(failure),
+        },
+        cli::Command::Check => read_source(&args.input).and_then(|code| {
+            match analyze(&code, &options(&args, &target)) {
+                Ok(analysis) => warn(&analysis.warnings),
+                Err(diags) => return Err(Failure::Program(diags)),
+            }
+            verbose!("No errors found in '{}'", args.input);
+            Ok(())
+        }),
+        cli::Command::Emit(ref stage) => emit(&args, &target, backend, stage),
+        cli::Command::Fix { dry_run } => fix(&args, &target, dry_run),
+    };
+    if let Err(failure) = result {
+        exit(failure.report());
     }
+}
 
-    // Check if a custom compiler is specified
-    let mut compiler = String::from("clang"); // Default compiler is clang
-    if let Some(pos) = args.iter().position(|arg| arg == "--bcompiler" || arg == "-bc") {
-        if pos + 1 < args.len() {
-            compiler = args[pos + 1].clone();
-        } else {
-            eprintln!("Error: No compiler specified after '--bcompiler' or '-bc'");
-            exit(1);
+/// Why a command failed, printed once whatever it was building is cleaned up
+enum Failure {
+    /// An error of the driver itself, boxed to keep results small
+    Driver(Box<Diagnostic>),
+    /// Errors in the program, or those of the tools building it
+    Program(Diagnostics),
+}
+
+impl Failure {
+    /// Print the errors and return the exit code to report them with
+    fn report(self) -> i32 {
+        match self {
+            Failure::Driver(diag) => print_error(*diag),
+            Failure::Program(diags) => print_diagnostics(&diags),
         }
+        1
     }
+}
 
-    // Check for --output or -o flag for the output C file name
-    let mut output_file = String::from("output.c"); // Default output file name
-    if let Some(pos) = args.iter().position(|arg| arg == "--output" || arg == "-o") {
-        if pos + 1 < args.len() {
-            output_file = args[pos + 1].clone();
-        } else {
-            eprintln!("Error: No output file specified after '--output' or '-o'");
-            exit(1);
+impl From<Diagnostic> for Failure {
+    fn from(diag: Diagnostic) -> Self {
+        Failure::Driver(Box::new(diag))
+    }
+}
+
+/// Temporary directory for the generated code, removed when dropped unless
+/// `--keep-c` asks to keep it
+struct TempDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempDir {
+    /// `name` inside the system's temporary directory, created on first use
+    fn new(name: String, keep: bool) -> Self {
+        TempDir {
+            path: temp_dir().join(name),
+            keep,
         }
     }
+}
 
-    match File::open(&file) {
-        Ok(_) => match read_to_string(file) {
-            Ok(code) => {
-                let toks = lexer(&code);
-                let ast = parse(&toks, &code);
-                println!("AST:\n{:?}", ast);
-
-                let ccode = genc(&ast);
-
-                // Write the cleaned C code to the specified output file
-                if let Err(e) = write(&output_file, ccode) {
-                    eprintln!("Error writing C code to file: {}", e);
-                    exit(1);
-                }
-
-                // Compile the C code using the specified compiler
-                match Command::new(&compiler)
-                    .arg(&output_file)
-                    .arg("-o")
-                    .arg("output") // Output binary
-                    .output()
-                {
-                    Ok(output) => {
-                        if !output.status.success() {
-                            eprintln!(
-                                "Error during compilation with {}: {}",
-                                compiler,
-                                String::from_utf8_lossy(&output.stderr)
-                            );
-                            exit(1);
-                        } else {
-                            println!("Compilation successful! Executable created as 'output'.");
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error running the compiler '{}': {}", compiler, e);
-                        exit(1);
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!(
-                    "Error: Unable to read from source file! Exact error: {}",
-                    e
-                );
-                exit(1);
-            }
-        },
-        Err(e) => {
-            eprintln!(
-                "Error: Unable to open source file! Exact error: {}",
-                e
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if !self.path.exists() {
+            return;
+        }
+        if !self.keep {
+            remove_dir_all(&self.path).ok();
+        } else if log::enabled(Level::Normal) {
+            eprintln!("Kept generated code in '{}'", self.path.display());
+        }
+    }
+}
+
+/// Print a compiler stage of the input file
+fn emit(args: &Args, target: &Target, backend: Backend, stage: &EmitStage) -> Result<(), Failure> {
+    check_backend(backend, target)?;
+    let options = Options {
+        backend,
+        ..options(args, target)
+    };
+    let out = compile(options, &read_source(&args.input)?)?;
+    match stage {
+        EmitStage::Tokens => out.tokens.iter().for_each(|tok| println!("{:?}", tok)),
+        EmitStage::Ast => out.ast.iter().for_each(|node| println!("{:?}", node)),
+        EmitStage::Ir => print!("{}", out.ir),
+        EmitStage::IrUnopt => print!("{}", out.lowered),
+        EmitStage::Code(_) => print!("{}", out.code),
+    }
+    Ok(())
+}
+
+/// Read the source file
+fn read_source(file: &str) -> Result<String, Failure> {
+    read_to_string(file).map_err(|e| {
+        Diagnostic::error(format!("Unable to read from source file '{}'", file))
+            .with_note(e.to_string())
+            .into()
+    })
+}
+
+/// Print the diagnostics of a failed compilation
+fn print_diagnostics(diags: &Diagnostics) {
+    eprint!("{}", diags.format(error_format()));
+    // Lints are named by their code, only errors have an explanation
+    let code = diags
+        .diagnostics
+        .iter()
+        .filter_map(|diag| diag.code.as_deref())
+        .find(|code| explain(code).is_some());
+    if let (ErrorFormat::Human, Some(code)) = (error_format(), code) {
+        eprintln!(
+            "For more information about an error, try 'neitc --explain {}'",
+            code
+        );
+    }
+}
+
+/// Print warnings, unless asked to be quiet
+fn warn(warnings: &Diagnostics) {
+    if !warnings.diagnostics.is_empty() && log::enabled(Level::Normal) {
+        eprint!("{}", warnings.format(error_format()));
+    }
+}
+
+/// Print an error of the driver itself
+fn print_error(diag: Diagnostic) {
+    eprint!("{}", report::format(&[diag], None, error_format()));
+}
+
+/// Print an error of the driver itself and exit
+fn fail(diag: Diagnostic) -> ! {
+    print_error(diag);
+    exit(1);
+}
+
+/// Options for compiling the input file
+fn options(args: &Args, target: &Target) -> Options {
+    Options {
+        file_name: args.input.clone(),
+        target: target.clone(),
+        lints: args.lints.clone(),
+        opt_level: args.opt_level,
+        line_directives: args.line_directives,
+        backend: args.backend,
+    }
+}
+
+/// Run the whole pipeline on the input file, whose content is `code`, printing
+/// its warnings
+fn compile(options: Options, code: &str) -> Result<Output, Failure> {
+    let out = compile_str(code, options).map_err(Failure::Program)?;
+    warn(&out.warnings);
+    Ok(out)
+}
+
+/// Apply the suggested fixes to the input file, or print them as a diff
+fn fix(args: &Args, target: &Target, dry_run: bool) -> Result<(), Failure> {
+    let code = read_source(&args.input)?;
+    let options = options(args, target);
+    let fixed = fix::fix(&code, &options);
+    if dry_run {
+        print!("{}", unified_diff(&args.input, &code, &fixed.source));
+        return Ok(());
+    }
+    if fixed.applied > 0 {
+        if let Err(e) = write(&args.input, &fixed.source) {
+            return Err(
+                Diagnostic::error(format!("Unable to write fixes to '{}'", args.input))
+                    .with_note(e.to_string())
+                    .into(),
             );
-            exit(1);
         }
     }
-}
\ No newline at end of file
+    verbose!("Applied {} fixes to '{}'", fixed.applied, args.input);
+    // Whatever couldn't be fixed is left for a human
+    let analysis = analyze(&fixed.source, &options).map_err(Failure::Program)?;
+    warn(&analysis.warnings);
+    Ok(())
+}
+
+/// Fail if `backend` can't generate code for `target`
+fn check_backend(backend: Backend, target: &Target) -> Result<(), Failure> {
+    if backend.supports(target) {
+        return Ok(());
+    }
+    Err(Diagnostic::error(format!(
+        "The {} backend can't generate code for {}",
+        backend, target
+    ))
+    .with_help(format!(
+        "it only supports {}, use '--backend=c' for other platforms",
+        backend.platforms()
+    ))
+    .into())
+}
+
+/// Name the executable after the input file, `hello.nc` builds `hello`
+fn default_binary(input: &str, target: &Target) -> PathBuf {
+    let input = Path::new(input);
+    let stem = input.file_stem().unwrap_or(input.as_os_str());
+    let mut binary = PathBuf::from(stem);
+    binary.set_extension(target.exe_extension());
+    // An input without extension would otherwise be overwritten by its own binary
+    if binary == input {
+        binary.set_extension("out");
+    }
+    binary
+}
+
+/// Compile the input with the chosen backend, then build the generated code
+/// into `binary`
+fn build(args: &Args, target: &Target, code_file: &Path, binary: &Path) -> Result<(), Failure> {
+    check_backend(args.backend, target)?;
+    let code = read_source(&args.input)?;
+    let out = compile(options(args, target), &code)?;
+    verbose!("Parsed {} top level items", out.ast.len());
+
+    // Write the generated code to the specified output file
+    if let Some(dir) = code_file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if let Err(e) = create_dir_all(dir) {
+            return Err(Diagnostic::error(format!(
+                "Unable to create directory '{}'",
+                dir.display()
+            ))
+            .with_note(e.to_string())
+            .into());
+        }
+    }
+    if let Err(e) = write(code_file, out.code) {
+        return Err(Diagnostic::error(format!(
+            "Unable to write generated code to '{}'",
+            code_file.display()
+        ))
+        .with_note(e.to_string())
+        .into());
+    }
+    verbose!("Wrote {} code to '{}'", args.backend, code_file.display());
+
+    // Libraries go after the code needing them, the program's own first
+    let libs: Vec<String> = out.link_libs.iter().chain(&args.libs).cloned().collect();
+    // Without a C library there is nothing to link libraries against
+    if let Some(lib) = libs.first() {
+        if matches!(args.backend, Backend::Asm | Backend::Wasm) {
+            return Err(Diagnostic::error(format!(
+                "Can't link '{}' when building with the {} backend",
+                lib, args.backend
+            ))
+            .with_help("use '--backend=c' to link C libraries")
+            .into());
+        }
+    }
+
+    let (commands, temporaries) = match args.backend {
+        Backend::C => {
+            let toolchain =
+                Toolchain::detect(args.compiler.as_deref(), target).map_err(Diagnostic::error)?;
+            let cmd = toolchain.command(code_file, binary, &args.cflags, &libs);
+            (vec![cmd], Vec::new())
+        }
+        Backend::Llvm => {
+            let toolchain = LlvmToolchain::detect(args.compiler.as_deref(), target)
+                .map_err(Diagnostic::error)?;
+            let commands =
+                toolchain.commands(code_file, binary, &args.cflags, &libs, target);
+            // llc leaves an object file behind for the linker
+            let object = toolchain.llc.as_ref().map(|_| code_file.with_extension("o"));
+            (commands, object.into_iter().collect())
+        }
+        Backend::Asm => {
+            let toolchain = AsmToolchain::detect(target).map_err(Diagnostic::error)?;
+            (
+                toolchain.commands(code_file, binary),
+                vec![code_file.with_extension("o")],
+            )
+        }
+        Backend::Qbe => {
+            let toolchain = QbeToolchain::detect(args.compiler.as_deref(), target)
+                .map_err(Diagnostic::error)?;
+            let qbe_target = qbe_target(target).unwrap_or_default();
+            let commands = toolchain.commands(
+                code_file,
+                binary,
+                &args.cflags,
+                &libs,
+                qbe_target,
+            );
+            let temporaries = vec![code_file.with_extension("s"), code_file.with_extension("o")];
+            (commands, temporaries)
+        }
+        Backend::Wasm => {
+            let toolchain = WasmToolchain::detect().map_err(Diagnostic::error)?;
+            (toolchain.commands(code_file, binary), Vec::new())
+        }
+    };
+
+    // Build the generated code using the detected programs
+    let built = commands.into_iter().try_for_each(|mut cmd| {
+        let program = cmd.get_program().to_string_lossy().into_owned();
+        verbose!("Running {:?}", cmd);
+        let output = cmd.output().map_err(|e| {
+            Diagnostic::error(format!("Unable to run the compiler '{}'", program))
+                .with_note(e.to_string())
+        })?;
+        if output.status.success() {
+            return Ok(());
+        }
+        // `#line` directives let the errors point into the source
+        let file = SourceFile::new(&args.input, &code);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(Failure::Program(Diagnostics {
+            diagnostics: cdiag::translate(&stderr, &file, &program),
+            file,
+        }))
+    });
+    for temporary in temporaries {
+        remove_file(temporary).ok();
+    }
+    // A failed link may leave a partial executable behind
+    if built.is_err() {
+        remove_file(binary).ok();
+    }
+    built
+}
+
+/// Exit code of a finished program, `128 + signal` when a signal killed it,
+/// the way shells report it
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    128
+}
+
+/// Build into a temporary directory, run the program and return its exit code
+fn run(args: &Args, target: &Target) -> Result<i32, Failure> {
+    let dir = TempDir::new(format!("neitc-run-{}", std::process::id()), args.keep_c);
+    if let Err(e) = create_dir_all(&dir.path) {
+        return Err(Diagnostic::error("Unable to create temporary directory")
+            .with_note(e.to_string())
+            .into());
+    }
+    let binary = dir.path.join(default_binary(&args.input, target));
+    let code_file = match &args.emit_c {
+        Some(code_file) => PathBuf::from(code_file),
+        None => binary.with_extension(args.backend.extension()),
+    };
+    // Look for a runtime first, building is pointless without one
+    let mut command = if target.is_wasi() {
+        wasi_runner(&binary, &args.run_args)
+            .map_err(|e| Diagnostic::error(e).with_help("use 'neitc build' to only build it"))?
+    } else {
+        let mut command = Command::new(&binary);
+        command.args(&args.run_args);
+        command
+    };
+    build(args, target, &code_file, &binary)?;
+
+    verbose!("Running '{}'", binary.display());
+    let status = command.status();
+    // The executable is always temporary, the generated code only if not asked for
+    remove_file(&binary).ok();
+    match status {
+        Ok(status) => Ok(exit_code(status)),
+        Err(e) => Err(
+            Diagnostic::error(format!("Unable to run '{}'", binary.display()))
+                .with_note(e.to_string())
+                .into(),
+        ),
+    }
+}
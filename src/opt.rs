@@ -0,0 +1,540 @@
+//! Optimizations on the IR, run between lowering and code generation
+//!
+//! Every function goes through constant propagation and folding, which also
+//! decides branches on constant conditions, then dead code elimination and
+//! control flow cleanup, repeated until none of them changes anything. At
+//! `--opt-level=2` functions `main` never calls and unused data go too.
+
+use std::fmt;
+
+use crate::ir::{BinOp, Block, BlockId, Function, Inst, Module, Temp, Terminator, Type, Value};
+
+/// How hard to optimize, `--opt-level`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptLevel {
+    /// Generate code from the IR as lowered
+    O0,
+    /// Optimize each function on its own
+    O1,
+    /// Also remove functions and data nothing uses
+    #[default]
+    O2,
+}
+
+impl OptLevel {
+    /// Parse the value of `--opt-level`
+    ///
+    /// # Parameters
+    /// - `&str` : `0`, `1` or `2`
+    ///
+    /// # Returns
+    /// - `Result<OptLevel, String>` : The level, or the name that isn't one
+    pub fn parse(name: &str) -> Result<OptLevel, String> {
+        match name {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            _ => Err(name.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            OptLevel::O0 => "0",
+            OptLevel::O1 => "1",
+            OptLevel::O2 => "2",
+        };
+        f.write_str(level)
+    }
+}
+
+/// Optimize a module in place
+///
+/// # Parameters
+/// - `&mut Module` : The module returned from `lower()`
+/// - `OptLevel` : Which optimizations to run
+pub fn optimize(module: &mut Module, level: OptLevel) {
+    if level == OptLevel::O0 {
+        return;
+    }
+    for function in &mut module.functions {
+        optimize_function(function);
+    }
+    if level >= OptLevel::O2 {
+        remove_unused_functions(module);
+        remove_unused_data(module);
+    }
+}
+
+/// Run the function local passes until they stop finding anything to do
+pub fn optimize_function(function: &mut Function) {
+    loop {
+        let mut changed = propagate_constants(function);
+        changed |= remove_dead_code(function);
+        changed |= simplify_cfg(function);
+        if !changed {
+            break;
+        }
+    }
+    compact_temps(function);
+}
+
+/// What is known about a temporary at some point of a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Known {
+    /// Not assigned on any path seen so far
+    Unset,
+    /// Holds this constant on every path
+    Const(Value),
+    /// Holds different values on different paths, or one not known until it runs
+    Varies,
+}
+
+impl Known {
+    /// What is known where two paths meet
+    fn meet(self, other: Known) -> Known {
+        match (self, other) {
+            (Known::Unset, known) | (known, Known::Unset) => known,
+            (Known::Const(a), Known::Const(b)) if a == b => Known::Const(a),
+            _ => Known::Varies,
+        }
+    }
+}
+
+/// Replace temporaries holding a constant by the constant, fold instructions
+/// on constants and turn branches on constants into jumps
+///
+/// Only blocks reachable through branches not already known to go the other
+/// way are looked at, the rest is left for [`simplify_cfg`] to remove.
+///
+/// # Returns
+/// - `bool` : Whether anything changed
+pub fn propagate_constants(function: &mut Function) -> bool {
+    let mut entry = vec![Known::Unset; function.temps.len()];
+    for param in &function.params {
+        entry[param.0] = Known::Varies;
+    }
+    // What is known when entering each block, `None` when it is never reached
+    let mut states: Vec<Option<Vec<Known>>> = vec![None; function.blocks.len()];
+    states[0] = Some(entry);
+    let mut work = vec![BlockId(0)];
+    while let Some(id) = work.pop() {
+        let Some(mut state) = states[id.0].clone() else {
+            continue;
+        };
+        for inst in &function.blocks[id.0].insts {
+            transfer(&mut state, inst, &function.temps);
+        }
+        for succ in feasible_successors(&function.blocks[id.0].term, &state) {
+            let merged = match &states[succ.0] {
+                Some(old) => old.iter().zip(&state).map(|(a, b)| a.meet(*b)).collect(),
+                None => state.clone(),
+            };
+            if states[succ.0].as_ref() != Some(&merged) {
+                states[succ.0] = Some(merged);
+                work.push(succ);
+            }
+        }
+    }
+
+    let mut changed = false;
+    let temps = &function.temps;
+    for (block, state) in function.blocks.iter_mut().zip(states) {
+        let Some(mut state) = state else {
+            continue;
+        };
+        for inst in &mut block.insts {
+            for operand in inst.operands_mut() {
+                changed |= substitute(operand, &state);
+            }
+            if let Inst::Binary { dest, op, lhs, rhs } = inst {
+                if let Some(value) = fold(*op, lhs, rhs, temps[dest.0]) {
+                    *inst = Inst::Copy { dest: *dest, value };
+                    changed = true;
+                }
+            }
+            transfer(&mut state, inst, temps);
+        }
+        if let Some(operand) = block.term.operand_mut() {
+            changed |= substitute(operand, &state);
+        }
+        if let Terminator::Branch {
+            cond: Value::Int(cond, _),
+            then,
+            otherwise,
+        } = block.term
+        {
+            block.term = Terminator::Jump(if cond != 0 { then } else { otherwise });
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Update what is known after `inst` runs, `temps` being the type of every
+/// temporary
+fn transfer(state: &mut [Known], inst: &Inst, temps: &[Type]) {
+    let Some(dest) = inst.dest() else {
+        return;
+    };
+    let known = |value: &Value| match value {
+        Value::Temp(temp) => match state[temp.0] {
+            // Reading a temporary before it is assigned, nothing to rely on
+            Known::Unset => Known::Varies,
+            known => known,
+        },
+        value => Known::Const(*value),
+    };
+    state[dest.0] = match inst {
+        Inst::Copy { value, .. } => known(value),
+        Inst::Binary { op, lhs, rhs, .. } => match (known(lhs), known(rhs)) {
+            (Known::Const(lhs), Known::Const(rhs)) => {
+                fold(*op, &lhs, &rhs, temps[dest.0]).map_or(Known::Varies, Known::Const)
+            }
+            _ => Known::Varies,
+        },
+        Inst::Call { .. } | Inst::Write { .. } | Inst::Loc { .. } => Known::Varies,
+    };
+}
+
+/// Blocks a terminator can go to, given what is known at its end
+fn feasible_successors(term: &Terminator, state: &[Known]) -> Vec<BlockId> {
+    match term {
+        Terminator::Branch {
+            cond,
+            then,
+            otherwise,
+        } => {
+            let cond = match cond {
+                Value::Temp(temp) => state[temp.0],
+                value => Known::Const(*value),
+            };
+            match cond {
+                Known::Const(Value::Int(cond, _)) if cond != 0 => vec![*then],
+                Known::Const(Value::Int(..)) => vec![*otherwise],
+                _ => term.successors(),
+            }
+        }
+        term => term.successors(),
+    }
+}
+
+/// Replace a temporary known to hold a constant by the constant
+fn substitute(operand: &mut Value, state: &[Known]) -> bool {
+    if let Value::Temp(temp) = operand {
+        if let Known::Const(value) = state[temp.0] {
+            *operand = value;
+            return true;
+        }
+    }
+    false
+}
+
+/// Compute `lhs op rhs` if both are integer constants
+///
+/// # Parameters
+/// - `BinOp` : The operator
+/// - `&Value` : Left operand
+/// - `&Value` : Right operand
+/// - `Type` : Type of the result
+///
+/// # Returns
+/// - `Option<Value>` : The result, `None` when it isn't known before running,
+///   or dividing by zero leaves it to the program to fail
+pub fn fold(op: BinOp, lhs: &Value, rhs: &Value, ty: Type) -> Option<Value> {
+    let (Value::Int(a, operands), Value::Int(b, _)) = (*lhs, *rhs) else {
+        return None;
+    };
+    // Arithmetic wraps around at the width of the operands
+    let wrap = |value: i64| match operands {
+        Type::I32 => i64::from(value as i32),
+        _ => value,
+    };
+    let result = match op {
+        BinOp::Add => wrap(a.wrapping_add(b)),
+        BinOp::Sub => wrap(a.wrapping_sub(b)),
+        BinOp::Mul => wrap(a.wrapping_mul(b)),
+        BinOp::Div | BinOp::Rem if b == 0 => return None,
+        BinOp::Div => wrap(a.wrapping_div(b)),
+        BinOp::Rem => wrap(a.wrapping_rem(b)),
+        BinOp::Eq => i64::from(a == b),
+        BinOp::Ne => i64::from(a != b),
+        BinOp::Lt => i64::from(a < b),
+        BinOp::Le => i64::from(a <= b),
+        BinOp::Gt => i64::from(a > b),
+        BinOp::Ge => i64::from(a >= b),
+    };
+    Some(Value::Int(result, ty))
+}
+
+/// Remove instructions whose result is never read and that have no other effect
+///
+/// # Returns
+/// - `bool` : Whether anything changed
+pub fn remove_dead_code(function: &mut Function) -> bool {
+    let mut changed = false;
+    loop {
+        let used = used_temps(function);
+        let mut removed = false;
+        for block in &mut function.blocks {
+            block.insts.retain_mut(|inst| match inst.dest() {
+                Some(dest) if !used[dest.0] => {
+                    if inst.has_side_effects() {
+                        // Keep the call, not its result
+                        if let Inst::Call { dest, .. } = inst {
+                            *dest = None;
+                        }
+                        removed = true;
+                        true
+                    } else {
+                        removed = true;
+                        false
+                    }
+                }
+                _ => true,
+            });
+        }
+        if !removed {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+/// Which temporaries are read anywhere in the function
+fn used_temps(function: &Function) -> Vec<bool> {
+    let mut used = vec![false; function.temps.len()];
+    let mut mark = |value: &Value| {
+        if let Value::Temp(temp) = value {
+            used[temp.0] = true;
+        }
+    };
+    for block in &function.blocks {
+        block
+            .insts
+            .iter()
+            .flat_map(Inst::operands)
+            .for_each(|v| mark(&v));
+        match &block.term {
+            Terminator::Return(Some(value)) | Terminator::Branch { cond: value, .. } => mark(value),
+            _ => {}
+        }
+    }
+    used
+}
+
+/// Turn branches going the same way both times into jumps, remove blocks
+/// nothing jumps to and merge blocks into the one block jumping to them
+///
+/// # Returns
+/// - `bool` : Whether anything changed
+pub fn simplify_cfg(function: &mut Function) -> bool {
+    let mut changed = false;
+    for block in &mut function.blocks {
+        if let Terminator::Branch {
+            then, otherwise, ..
+        } = block.term
+        {
+            if then == otherwise {
+                block.term = Terminator::Jump(then);
+                changed = true;
+            }
+        }
+    }
+    loop {
+        changed |= remove_unreachable(function);
+        if !merge_block(function) {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+/// Remove the blocks control never reaches, keeping the others in order
+fn remove_unreachable(function: &mut Function) -> bool {
+    let mut reached = vec![false; function.blocks.len()];
+    let mut work = vec![BlockId(0)];
+    while let Some(id) = work.pop() {
+        if !std::mem::replace(&mut reached[id.0], true) {
+            work.extend(function.blocks[id.0].term.successors());
+        }
+    }
+    if reached.iter().all(|reached| *reached) {
+        return false;
+    }
+
+    // New number of every block that stays
+    let mut renumbered = vec![BlockId(0); function.blocks.len()];
+    let mut next = 0;
+    for (old, reached) in reached.iter().enumerate() {
+        if *reached {
+            renumbered[old] = BlockId(next);
+            next += 1;
+        }
+    }
+    let mut id = 0;
+    function.blocks.retain(|_| {
+        id += 1;
+        reached[id - 1]
+    });
+    for block in &mut function.blocks {
+        for succ in block.term.successors_mut() {
+            *succ = renumbered[succ.0];
+        }
+    }
+    true
+}
+
+/// Merge one block into the block before it, when that is the only block
+/// jumping to it and does so unconditionally
+fn merge_block(function: &mut Function) -> bool {
+    let mut preds = vec![0usize; function.blocks.len()];
+    for block in &function.blocks {
+        for succ in block.term.successors() {
+            preds[succ.0] += 1;
+        }
+    }
+    for id in 0..function.blocks.len() {
+        let Terminator::Jump(next) = function.blocks[id].term else {
+            continue;
+        };
+        // The entry block is entered from outside as well
+        if next.0 == id || next.0 == 0 || preds[next.0] != 1 {
+            continue;
+        }
+        let merged = std::mem::replace(
+            &mut function.blocks[next.0],
+            Block {
+                insts: Vec::new(),
+                term: Terminator::Return(None),
+            },
+        );
+        let block = &mut function.blocks[id];
+        block.insts.extend(merged.insts);
+        block.term = merged.term;
+        // The emptied block is no longer reached, the next round removes it
+        return true;
+    }
+    false
+}
+
+/// Number the temporaries still in use from zero, parameters first
+pub fn compact_temps(function: &mut Function) {
+    let mut used = used_temps(function);
+    for block in &function.blocks {
+        for dest in block.insts.iter().filter_map(Inst::dest) {
+            used[dest.0] = true;
+        }
+    }
+    for param in &function.params {
+        used[param.0] = true;
+    }
+    let mut renumbered = vec![None; function.temps.len()];
+    let mut temps = Vec::new();
+    let order =
+        function.params.iter().map(|param| param.0).chain(
+            (0..function.temps.len()).filter(|temp| !function.params.contains(&Temp(*temp))),
+        );
+    for temp in order {
+        if used[temp] {
+            renumbered[temp] = Some(Temp(temps.len()));
+            temps.push(function.temps[temp]);
+        }
+    }
+
+    let rename = |temp: &mut Temp| *temp = renumbered[temp.0].unwrap_or(*temp);
+    for param in &mut function.params {
+        rename(param);
+    }
+    for block in &mut function.blocks {
+        for inst in &mut block.insts {
+            if let Some(dest) = inst.dest_mut() {
+                rename(dest);
+            }
+            for operand in inst.operands_mut() {
+                if let Value::Temp(temp) = operand {
+                    rename(temp);
+                }
+            }
+        }
+        if let Some(Value::Temp(temp)) = block.term.operand_mut() {
+            rename(temp);
+        }
+    }
+    function.temps = temps;
+}
+
+/// Remove the functions `main` never calls, directly or not
+pub fn remove_unused_functions(module: &mut Module) {
+    // Without a `main` there is nothing to start from
+    if module.function("main").is_none() {
+        return;
+    }
+    let mut called = vec!["main".to_string()];
+    let mut work = vec!["main".to_string()];
+    while let Some(name) = work.pop() {
+        let Some(function) = module.function(&name) else {
+            continue;
+        };
+        for inst in function.blocks.iter().flat_map(|block| &block.insts) {
+            if let Inst::Call { function, .. } = inst {
+                if !called.contains(function) {
+                    called.push(function.clone());
+                    work.push(function.clone());
+                }
+            }
+        }
+    }
+    module
+        .functions
+        .retain(|function| called.contains(&function.name));
+}
+
+/// Remove the data no instruction refers to
+pub fn remove_unused_data(module: &mut Module) {
+    let mut used = vec![false; module.data.len()];
+    for function in &module.functions {
+        for block in &function.blocks {
+            let term = match &block.term {
+                Terminator::Return(Some(value)) | Terminator::Branch { cond: value, .. } => {
+                    Some(*value)
+                }
+                _ => None,
+            };
+            for value in block.insts.iter().flat_map(Inst::operands).chain(term) {
+                if let Value::Data(data) = value {
+                    used[data.0] = true;
+                }
+            }
+        }
+    }
+
+    let mut renumbered = Vec::with_capacity(used.len());
+    let mut next = 0;
+    for used in &used {
+        renumbered.push(next);
+        next += usize::from(*used);
+    }
+    let mut id = 0;
+    module.data.retain(|_| {
+        id += 1;
+        used[id - 1]
+    });
+    for function in &mut module.functions {
+        for block in &mut function.blocks {
+            let term = block.term.operand_mut();
+            for value in block
+                .insts
+                .iter_mut()
+                .flat_map(Inst::operands_mut)
+                .chain(term)
+            {
+                if let Value::Data(data) = value {
+                    data.0 = renumbered[data.0];
+                }
+            }
+        }
+    }
+}
@@ -1,61 +1,151 @@
-use crate::lexer::Tokens;
+use crate::diag::{Applicability, Diagnostic, Span};
+use crate::lexer::{Token, Tokens};
+use crate::lint::LintLevel;
+use crate::log::Stage;
+use crate::suggest::{best_match, edit_distance, BUILTINS, KEYWORDS, LIBRARIES, STREAMS};
+use crate::trace;
 use colored::*;
-use std::process::exit;
 
-#[derive(Debug)]
+/// Errors found while lexing and parsing, each pointing at the code at fault
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ParseError {
     InvalidCharacter {
-        line: usize,
-        col: usize,
-        code_line: String,
+        span: Span,
     },
     InvalidLibrary {
-        line: usize,
+        span: Span,
         name: String,
-        code_line: String,
     },
     UnexpectedToken {
-        line: usize,
+        span: Span,
         token: String,
-        code_line: String,
     },
     UnterminatedString {
-        line: usize,
-        code_line: String,
+        span: Span,
     },
     InvalidFunction {
-        line: usize,
+        span: Span,
         name: String,
-        code_line: String,
     },
     InvalidArgument {
-        line: usize,
+        span: Span,
         expected: String,
         found: String,
-        code_line: String,
-        code: String,
     },
-    NoCimport,
+    InvalidAttribute {
+        span: Span,
+        name: String,
+    },
+    /// `span` is the first use of something that needs `cimport cstd`
+    NoCimport {
+        span: Span,
+    },
+    /// Attributes at the end of the file, with no item to apply to
+    DanglingAttribute {
+        span: Span,
+    },
 }
 
 #[derive(Debug,PartialEq, Eq)]
 pub enum AST {
-    Cimport(String),
-    CWrt(i32, String, i32),
+    Cimport(String, Span),
+    /// `cimport link:<lib>`, a library the executable gets linked against
+    CLink(String, Span),
+    CWrt(Wrt),
+    /// Items only compiled in when the condition holds for the target
+    Cfg(Cfg, Vec<AST>),
+    /// `#[allow(...)]` and friends, changing lint levels for the items under it
+    Lint(LintAttr, Vec<AST>),
+    /// `#![allow(...)]` and friends, changing lint levels for the whole file
+    InnerLint(LintAttr),
+    /// An item that failed to parse, its errors are already reported. Later
+    /// stages skip it instead of reporting errors caused by its absence
+    Poisoned(Item, Span),
+}
+
+/// What a poisoned item was meant to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item {
+    Import,
+    Call,
+    Unknown,
+}
+
+/// A `__wrt__(stream, "text", size)` call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wrt {
+    pub stream: Stream,
+    /// The string literal, quotes included
+    pub text: String,
+    /// Number of bytes to write
+    pub size: i32,
+    /// The whole call
+    pub span: Span,
+    /// Each of the three arguments
+    pub args: [Span; 3],
+}
+
+/// Streams `__wrt__` can write to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Compile time condition on the target, written as an attribute before an item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cfg {
+    /// `#[target_os = "linux"]`
+    TargetOs(String),
+    /// `#[target_arch = "x86_64"]`
+    TargetArch(String),
+}
+
+/// `allow(...)`, `warn(...)` or `deny(...)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintAttr {
+    pub level: LintLevel,
+    /// Every lint named, and where
+    pub lints: Vec<(String, Span)>,
+    /// The whole attribute
+    pub span: Span,
 }
 
-struct ParseContext {
+/// An attribute waiting for the item it applies to
+enum Attribute {
+    Cfg(Cfg),
+    Lint(LintAttr),
+}
+
+/// Every item in `ast`, including the ones nested under attributes
+pub fn items(ast: &[AST]) -> Vec<&AST> {
+    let mut all = Vec::new();
+    for node in ast {
+        all.push(node);
+        if let AST::Cfg(_, nested) | AST::Lint(_, nested) = node {
+            all.extend(items(nested));
+        }
+    }
+    all
+}
+
+/// What `__wrt__` expects as its first argument
+const EXPECTED_STREAM: &str = "stdout or stderr";
+
+struct ParseContext<'a> {
     errors: Vec<ParseError>,
-    line: usize,
-    col: usize,
+    /// The source code, tokens only carry spans into it
+    code: &'a str,
+    /// Attributes applied to the next item and where they are, in source order
+    attrs: Vec<(Attribute, Span)>,
 }
 
-impl ParseContext {
-    fn new() -> Self {
+impl<'a> ParseContext<'a> {
+    fn new(code: &'a str) -> Self {
         Self {
             errors: Vec::new(),
-            line: 1,
-            col: 0,
+            code,
+            attrs: Vec::new(),
         }
     }
 
@@ -63,395 +153,695 @@ impl ParseContext {
         self.errors.push(error);
     }
 
-    fn print_errors(&self, _code: &String) {
-        for err in &self.errors {
-            match err {
-                ParseError::InvalidCharacter { line, col, code_line } => {
-                    eprintln!(
-                        "{}: {}\n{}",
-                        "Error".red().bold(),
-                        format!("Invalid character at line {}, col {}", line, col),
-                        highlight_code(*line, col, code_line)
-                    );
-                }
-                ParseError::InvalidLibrary { line, name, code_line } => {
-                    eprintln!(
-                        "{}: {}\n{}",
-                        "Error".red().bold(),
-                        format!("Invalid library '{}' at line {}", name, line),
-                        highlight_code(*line, &0, code_line)
-                    );
-                }
-                ParseError::UnexpectedToken { line, token, code_line } => {
-                    eprintln!(
-                        "{}: {}\n{}",
-                        "Error".red().bold(),
-                        format!("Unexpected token '{}' at line {}", token, line),
-                        highlight_code(*line, &0, code_line)
-                    );
-                }
-                ParseError::UnterminatedString { line, code_line } => {
-                    eprintln!(
-                        "{}: {}\n{}",
-                        "Error".red().bold(),
-                        format!("Unterminated string at line {}", line),
-                        highlight_code(*line, &0, code_line)
-                    );
-                }
-                ParseError::InvalidFunction { line, name, code_line } => {
-                    eprintln!(
-                        "{}: {}\n{}",
-                        "Error".red().bold(),
-                        format!("Invalid function '{}' at line {}", name, line),
-                        highlight_code(*line, &0, code_line)
-                    );
-                }
-                ParseError::InvalidArgument { line, expected, found, code_line, code } => {
-                    eprintln!(
-                        "{}: {}\n{}{}",
-                        "Error".red().bold(),
-                        format!(
-                            "Expected '{}' but found '{}' at line {}",
-                            expected, found, line
-                        ),
-                        highlight_code(*line, &0, code_line),
-                        code
-                    );
-                }
-                ParseError::NoCimport => {
-                    eprintln!(
-                        "{}: {}",
-                        "Error".red().bold(),
-                        format!("No import of cstd found")
-                    );
+    /// Source text of a span
+    fn text(&self, span: Span) -> &'a str {
+        &self.code[span.start..span.end]
+    }
+}
+
+impl ParseError {
+    /// Where in the source the error is
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::InvalidCharacter { span }
+            | ParseError::InvalidLibrary { span, .. }
+            | ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnterminatedString { span }
+            | ParseError::InvalidFunction { span, .. }
+            | ParseError::InvalidArgument { span, .. }
+            | ParseError::InvalidAttribute { span, .. }
+            | ParseError::NoCimport { span }
+            | ParseError::DanglingAttribute { span } => *span,
+        }
+    }
+
+    /// Stable code of the error, see `neitc --explain`
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::InvalidCharacter { .. } => "N0001",
+            ParseError::InvalidLibrary { .. } => "N0002",
+            ParseError::UnexpectedToken { .. } => "N0003",
+            ParseError::UnterminatedString { .. } => "N0004",
+            ParseError::InvalidFunction { .. } => "N0005",
+            ParseError::InvalidArgument { .. } => "N0006",
+            ParseError::InvalidAttribute { .. } => "N0007",
+            ParseError::NoCimport { .. } => "N0008",
+            ParseError::DanglingAttribute { .. } => "N0009",
+        }
+    }
+
+    /// Turn the error into a diagnostic
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diag = match self {
+            ParseError::InvalidCharacter { span } => {
+                Diagnostic::error("Invalid character").with_label(*span, "not expected here")
+            }
+            ParseError::InvalidLibrary { span, name } => did_you_mean(
+                Diagnostic::error(format!("Invalid library '{}'", name))
+                    .with_label(*span, "not a known library")
+                    .with_help("the only library is 'cstd', C libraries are linked with 'link:<lib>'"),
+                *span,
+                name,
+                LIBRARIES,
+                "a library",
+            ),
+            ParseError::UnexpectedToken { span, token } => did_you_mean(
+                Diagnostic::error(format!("Unexpected token '{}'", token))
+                    .with_label(*span, "unexpected token"),
+                *span,
+                token,
+                KEYWORDS,
+                "a keyword",
+            ),
+            ParseError::UnterminatedString { span } => {
+                Diagnostic::error("Unterminated string")
+                    .with_label(*span, "string is never closed")
+                    .with_help("strings end at the end of the line, add the closing quote")
+            }
+            ParseError::InvalidFunction { span, name } => did_you_mean(
+                Diagnostic::error(format!("Invalid function '{}'", name))
+                    .with_label(*span, "not a known function"),
+                *span,
+                name,
+                BUILTINS,
+                "a function",
+            ),
+            ParseError::InvalidArgument { span, expected, found } => {
+                let diag = Diagnostic::error(format!("Expected {} but found '{}'", expected, found))
+                    .with_label(*span, format!("expected {}", expected));
+                if expected == EXPECTED_STREAM {
+                    did_you_mean(diag, *span, &found.to_lowercase(), STREAMS, "a stream")
+                } else {
+                    diag
                 }
             }
-        }
+            ParseError::InvalidAttribute { span, name } => {
+                Diagnostic::error(format!("Invalid attribute '{}'", name))
+                    .with_label(*span, "not a known attribute")
+                    .with_help("use '#[target_os = \"<os>\"]', '#[target_arch = \"<arch>\"]' or '#[allow(<lint>)]'")
+            }
+            ParseError::NoCimport { span } => Diagnostic::error("No import of cstd found")
+                .with_label(*span, "needs 'cimport cstd'")
+                .with_note("'__wrt__' is provided by the C standard library")
+                .with_suggestion(
+                    Span::new(0, 0),
+                    "cimport cstd\n",
+                    "import it at the top of the file",
+                    Applicability::MachineApplicable,
+                ),
+            ParseError::DanglingAttribute { span } => {
+                Diagnostic::error("Attribute is not followed by an item")
+                    .with_label(*span, "not followed by an item")
+                    .with_help("attributes apply to the item after them, add one or remove the attribute")
+            }
+        };
+        diag.with_code(self.code())
     }
 }
 
-pub fn parse(toks: &Vec<Tokens>, code: &String) -> Vec<AST> {
-    let mut ctx = ParseContext::new();
+/// Suggest the name out of `candidates` closest to `found`, if there is one
+///
+/// # Parameters
+/// - `Diagnostic` : The diagnostic to add the suggestion to
+/// - `Span` : Where `found` is
+/// - `&str` : The name that isn't valid there
+/// - `&[&str]` : Every name that is
+/// - `&str` : What the names are, e.g. `a function`
+///
+/// # Returns
+/// - `Diagnostic` : The diagnostic, with a suggestion if a name is close enough
+fn did_you_mean(
+    diag: Diagnostic,
+    span: Span,
+    found: &str,
+    candidates: &[&str],
+    what: &str,
+) -> Diagnostic {
+    let Some(name) = best_match(found, candidates) else {
+        return diag;
+    };
+    // Only a single typo away from exactly one name is sure enough for `neitc fix`
+    let typos = |candidate: &str| edit_distance(found, candidate);
+    let unique = candidates.iter().filter(|c| typos(c) == 1).count() == 1;
+    let applicability = if typos(name) == 1 && unique {
+        Applicability::MachineApplicable
+    } else {
+        Applicability::MaybeIncorrect
+    };
+    diag.with_suggestion(
+        span,
+        name,
+        format!("{} with a similar name exists", what),
+        applicability,
+    )
+}
+
+/// Parse the tokens returned by `lexer()` into an AST
+///
+/// Errors don't stop parsing: the statement they are in is skipped up to the
+/// next `;`, `}` or line break and left in the AST as an [`AST::Poisoned`]
+/// node, so every independent mistake is reported in one go.
+///
+/// # Parameters
+/// - `&[Token]` : Tokens of the source file
+/// - `&str` : The source code itself, tokens point into it
+///
+/// # Returns
+/// - `(Vec<AST>, Vec<ParseError>)` : The parsed AST and every error found while parsing
+pub fn parse(toks: &[Token], code: &str) -> (Vec<AST>, Vec<ParseError>) {
+    let mut ctx = ParseContext::new(code);
     let mut ast = Vec::new();
     let mut tok_iter = toks.iter();
+    // A word at the top level that didn't turn out to be a keyword
+    let mut word: Option<Span> = None;
 
     while let Some(tok) = tok_iter.next() {
-        ctx.line += 1;
-
-        if let Tokens::Eof = tok {
-            eprintln!(
+        if let Tokens::Eof = tok.tok {
+            trace!(
+                Stage::Parser,
                 "{}{}{}",
-                "Reached EOF at line: ".blue(),
-                ctx.line,
+                "Reached EOF at offset: ".blue(),
+                tok.span.start,
                 " (End of file reached)".blue().bold()
             );
             break;
         }
 
-        eprintln!(
+        trace!(
+            Stage::Parser,
             "{}{}{}",
             "Parsing token: ".cyan(),
-            format!("{:?}", tok).bold(),
-            format!(" at line {}.", ctx.line).cyan()
+            format!("{:?}", tok.tok).bold(),
+            format!(" at {}..{}.", tok.span.start, tok.span.end).cyan()
         );
 
-        match tok {
+        let start = ast.len();
+        match tok.tok {
             Tokens::CImport => {
-                parse_imports(&mut tok_iter, &mut ast, &mut ctx, code);
+                // The characters of `cimport` itself came before the keyword
+                word = None;
+                parse_imports(&mut tok_iter, tok.span, &mut ast, &mut ctx);
+            }
+            Tokens::Char(_, _, '#') if word.is_none() => {
+                parse_attribute(&mut tok_iter, tok.span, &mut ast, &mut ctx);
+                continue;
+            }
+            Tokens::Char(..) => {
+                word = Some(word.map_or(tok.span, |word| word.to(tok.span)));
+                continue;
+            }
+            Tokens::Underscore if word.is_none() => {
+                parse_function(&mut tok_iter, tok.span, &mut ast, &mut ctx);
+            }
+            // A stray `}` is an error, it closes nothing
+            Tokens::Space | Tokens::SColon | Tokens::Eol if word.is_none() => continue,
+            Tokens::DQ | Tokens::SQ if word.is_none() => {
+                parse_stray_string(&mut tok_iter, tok, &mut ast, &mut ctx);
+            }
+            _ => {
+                // Whatever ended the word belongs to the same statement
+                let span = word.take().unwrap_or(tok.span);
+                ctx.add_error(ParseError::UnexpectedToken {
+                    span,
+                    token: ctx.text(span).to_string(),
+                });
+                let skipped = if is_boundary(&tok.tok) {
+                    span
+                } else {
+                    recover(&mut tok_iter, span)
+                };
+                ast.push(AST::Poisoned(Item::Unknown, skipped));
             }
-            Tokens::Underscore => {
-                parse_function(&mut tok_iter, &mut ast, &mut ctx, code);
+        }
+
+        // Put the item under the attributes before it, the first one outermost
+        if !ctx.attrs.is_empty() {
+            let mut items = ast.split_off(start);
+            for (attr, _) in ctx.attrs.drain(..).rev() {
+                items = vec![match attr {
+                    Attribute::Cfg(cfg) => AST::Cfg(cfg, items),
+                    Attribute::Lint(lint) => AST::Lint(lint, items),
+                }];
             }
-            _ => continue,
+            ast.extend(items);
         }
     }
 
-    if !ctx.errors.is_empty() {
-        ctx.print_errors(code);
-        exit(1);
+    if let (Some((_, first)), Some((_, last))) = (ctx.attrs.first(), ctx.attrs.last()) {
+        let span = first.to(*last);
+        ctx.add_error(ParseError::DanglingAttribute { span });
     }
-    ast
+
+    (ast, ctx.errors)
+}
+
+/// Check whether a token ends a statement
+fn is_boundary(tok: &Tokens) -> bool {
+    matches!(
+        tok,
+        Tokens::SColon | Tokens::RCurlyBrace | Tokens::Eol | Tokens::Eof
+    )
+}
+
+/// Skip the rest of a statement after an error, up to and including the next
+/// `;`, `}` or line break
+///
+/// # Parameters
+/// - `&mut std::slice::Iter<Token>` : The tokens, positioned inside the statement
+/// - `Span` : The part of the statement already consumed
+///
+/// # Returns
+/// - `Span` : The whole statement, without the token ending it
+fn recover(tok_iter: &mut std::slice::Iter<Token>, from: Span) -> Span {
+    let mut span = from;
+    for tok in tok_iter.by_ref() {
+        if is_boundary(&tok.tok) {
+            break;
+        }
+        span = span.to(tok.span);
+    }
+    trace!(
+        Stage::Parser,
+        "{}{}..{}",
+        "Recovered from an error, skipped ".yellow(),
+        span.start,
+        span.end
+    );
+    span
+}
+
+/// A string on its own is reported as a whole, unless it is never closed,
+/// which the lexer already reported
+fn parse_stray_string(
+    tok_iter: &mut std::slice::Iter<Token>,
+    quote: &Token,
+    ast: &mut Vec<AST>,
+    ctx: &mut ParseContext,
+) {
+    let mut span = quote.span;
+    for tok in tok_iter.by_ref() {
+        if matches!(tok.tok, Tokens::Eol | Tokens::Eof) {
+            ast.push(AST::Poisoned(Item::Unknown, span));
+            return;
+        }
+        span = span.to(tok.span);
+        if tok.tok == quote.tok {
+            break;
+        }
+    }
+    ctx.add_error(ParseError::UnexpectedToken {
+        span,
+        token: ctx.text(span).to_string(),
+    });
+    ast.push(AST::Poisoned(Item::Unknown, recover(tok_iter, span)));
+}
+
+/// Parse an attribute, the `#` is already consumed:
+/// - `#[target_os = "linux"]` or `#[target_arch = "x86_64"]`, making the next item conditional
+/// - `#[allow(<lint>, ...)]`, `warn` or `deny`, setting lint levels for the next item
+/// - `#![allow(<lint>, ...)]`, `warn` or `deny`, setting lint levels for the whole file
+fn parse_attribute(
+    tok_iter: &mut std::slice::Iter<Token>,
+    hash: Span,
+    ast: &mut Vec<AST>,
+    ctx: &mut ParseContext,
+) {
+    let mut span = hash;
+    let mut closed = false;
+    for tok in tok_iter.by_ref() {
+        match tok.tok {
+            Tokens::Eol | Tokens::Eof => break,
+            Tokens::RBBracket => {
+                span = span.to(tok.span);
+                closed = true;
+                break;
+            }
+            _ => span = span.to(tok.span),
+        }
+    }
+    let attr = ctx.text(span);
+    trace!(Stage::Parser, "{}{}", "Attribute parsed: ".green(), attr.bold());
+
+    let is_inner = attr.starts_with("#!");
+    let prefix = if is_inner { "#![" } else { "#[" };
+    let inner = attr
+        .strip_prefix(prefix)
+        .and_then(|attr| attr.strip_suffix(']'))
+        .filter(|_| closed);
+    let offset = span.start + prefix.len();
+    let parsed = inner.and_then(|inner| match parse_lint_attr(inner, offset, span) {
+        Some(lint) => Some(Attribute::Lint(lint)),
+        // Conditions only make sense on an item
+        None if !is_inner => parse_cfg(inner).map(Attribute::Cfg),
+        None => None,
+    });
+    match parsed {
+        Some(Attribute::Lint(lint)) if is_inner => ast.push(AST::InnerLint(lint)),
+        Some(attr) => ctx.attrs.push((attr, span)),
+        None => ctx.add_error(ParseError::InvalidAttribute {
+            span,
+            name: inner.unwrap_or(attr).trim().to_string(),
+        }),
+    }
+}
+
+/// Parse `target_os = "linux"` or `target_arch = "x86_64"`
+fn parse_cfg(inner: &str) -> Option<Cfg> {
+    let (key, value) = inner.split_once('=')?;
+    let value = value.trim().trim_matches('"');
+    match key.trim() {
+        _ if value.is_empty() => None,
+        "target_os" => Some(Cfg::TargetOs(value.to_string())),
+        "target_arch" => Some(Cfg::TargetArch(value.to_string())),
+        _ => None,
+    }
+}
+
+/// Parse `allow(a, b)`, `warn(...)` or `deny(...)`, `offset` being where
+/// `inner` starts in the source
+fn parse_lint_attr(inner: &str, offset: usize, span: Span) -> Option<LintAttr> {
+    let (level, rest) = inner.split_once('(')?;
+    let level = LintLevel::parse(level.trim())?;
+    let list = rest.trim_end().strip_suffix(')')?;
+    let mut start = offset + inner.len() - rest.len();
+    let mut lints = Vec::new();
+    for name in list.split(',') {
+        let trimmed = name.trim();
+        let lead = name.len() - name.trim_start().len();
+        if trimmed.is_empty() {
+            return None;
+        }
+        let name_start = start + lead;
+        lints.push((
+            trimmed.to_string(),
+            Span::new(name_start, name_start + trimmed.len()),
+        ));
+        start += name.len() + 1;
+    }
+    Some(LintAttr { level, lints, span })
 }
 
 fn parse_imports(
-    tok_iter: &mut std::slice::Iter<Tokens>,
+    tok_iter: &mut std::slice::Iter<Token>,
+    keyword: Span,
     ast: &mut Vec<AST>,
     ctx: &mut ParseContext,
-    code: &String,
 ) {
     let mut curlib = String::new();
-
-    while let Some(citok) = tok_iter.next() {
-        if let Tokens::Eof = citok {
-            eprintln!(
+    let mut libspan: Option<Span> = None;
+    // The current library had an invalid character, skip to the next one
+    let mut bad = false;
+    let mut found = false;
+
+    for citok in tok_iter.by_ref() {
+        if let Tokens::Eof = citok.tok {
+            trace!(
+                Stage::Parser,
                 "{}{}{}",
-                "Reached EOF while parsing import at line: ".blue(),
-                ctx.line,
+                "Reached EOF while parsing import at offset: ".blue(),
+                citok.span.start,
                 " (End of file reached)".blue().bold()
             );
-            break;
         }
 
-        match citok {
-            Tokens::Char(_, _, c) => curlib.push(*c),
-            Tokens::Comma | Tokens::Eol => {
-                if !curlib.is_empty() {
-                    if curlib == "cstd" {
-                        ast.push(AST::Cimport(curlib.clone()));
+        match citok.tok {
+            Tokens::Char(_, _, c) => {
+                curlib.push(c);
+                libspan = Some(libspan.map_or(citok.span, |span| span.to(citok.span)));
+            }
+            Tokens::Comma | Tokens::SColon | Tokens::Eol | Tokens::Eof => {
+                if let Some(span) = libspan.take() {
+                    found = true;
+                    let link = curlib.strip_prefix("link:").filter(|lib| is_lib_name(lib));
+                    if bad {
+                        ast.push(AST::Poisoned(Item::Import, span));
+                    } else if curlib == "cstd" {
+                        ast.push(AST::Cimport(curlib.clone(), span));
+                    } else if let Some(lib) = link {
+                        ast.push(AST::CLink(lib.to_string(), span));
                     } else {
                         ctx.add_error(ParseError::InvalidLibrary {
-                            line: ctx.line,
+                            span,
                             name: curlib.clone(),
-                            code_line: code
-                                .lines()
-                                .nth(ctx.line - 1)
-                                .unwrap_or_default()
-                                .to_string(),
                         });
+                        ast.push(AST::Poisoned(Item::Import, span));
                     }
                     curlib.clear();
+                    bad = false;
                 }
-                if matches!(citok, Tokens::Eol) {
+                if !matches!(citok.tok, Tokens::Comma) {
+                    if !found {
+                        ctx.add_error(ParseError::InvalidArgument {
+                            span: citok.span,
+                            expected: String::from("a library name"),
+                            found: String::from("end of line"),
+                        });
+                        ast.push(AST::Poisoned(Item::Import, keyword));
+                    }
                     break;
                 }
             }
             Tokens::Space => continue,
             _ => {
-                ctx.add_error(ParseError::InvalidCharacter {
-                    line: ctx.line,
-                    col: ctx.col,
-                    code_line: code
-                        .lines()
-                        .nth(ctx.line - 1)
-                        .unwrap_or_default()
-                        .to_string(),
-                });
+                // One error per library is enough
+                if !bad {
+                    ctx.add_error(ParseError::InvalidCharacter { span: citok.span });
+                }
+                bad = true;
+                libspan = Some(libspan.map_or(citok.span, |span| span.to(citok.span)));
             }
         }
     }
 }
 
+/// Library names end up as `-l<lib>` on the compiler command line, keep them to
+/// characters that can't be mistaken for another flag
+fn is_lib_name(lib: &str) -> bool {
+    !lib.is_empty()
+        && !lib.starts_with('-')
+        && lib.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '+'))
+}
+
 fn parse_function(
-    tok_iter: &mut std::slice::Iter<Tokens>,
+    tok_iter: &mut std::slice::Iter<Token>,
+    start: Span,
     ast: &mut Vec<AST>,
     ctx: &mut ParseContext,
-    code: &String,
 ) {
-    let mut fn_name = String::new();
-
-    while let Some(tok) = tok_iter.next() {
-        if let Tokens::Eof = tok {
-            eprintln!(
-                "{}{}{}",
-                "Reached EOF while parsing function at line: ".blue(),
-                ctx.line,
-                " (End of file reached)".blue().bold()
-            );
-            break;
-        }
-
-        match tok {
-            Tokens::Char(_, _, c) => fn_name.push(*c),
-            Tokens::LSB => break,
-            _ => continue,
+    let mut name_span = start;
+
+    for tok in tok_iter.by_ref() {
+        match tok.tok {
+            Tokens::Char(..) | Tokens::Underscore => name_span = name_span.to(tok.span),
+            Tokens::LSB => {
+                let fn_name = ctx.text(name_span);
+                trace!(
+                    Stage::Parser,
+                    "{}{}{}",
+                    "Function parsed: ".green(),
+                    fn_name.bold(),
+                    " Proceeding to arguments.".green()
+                );
+                match fn_name {
+                    "__wrt__" => parse_write(tok_iter, name_span, ast, ctx),
+                    _ => {
+                        ctx.add_error(ParseError::InvalidFunction {
+                            span: name_span,
+                            name: fn_name.to_string(),
+                        });
+                        ast.push(AST::Poisoned(Item::Call, recover(tok_iter, name_span)));
+                    }
+                }
+                return;
+            }
+            _ if is_boundary(&tok.tok) => {
+                trace!(
+                    Stage::Parser,
+                    "{}{}",
+                    "Reached the end of the statement while parsing function at offset: ".blue(),
+                    tok.span.start
+                );
+                break;
+            }
+            _ => {
+                ctx.add_error(ParseError::InvalidArgument {
+                    span: tok.span,
+                    expected: String::from("'('"),
+                    found: ctx.text(tok.span).to_string(),
+                });
+                ast.push(AST::Poisoned(Item::Call, recover(tok_iter, name_span)));
+                return;
+            }
         }
     }
 
-    eprintln!(
-        "{}{}{}",
-        "Function parsed: ".green(),
-        fn_name.bold(),
-        " Proceeding to arguments.".green()
-    );
-    checkci(ast,ctx);
-    match fn_name.trim() {
-        "wrt" => parse_write(tok_iter, ast, ctx, code),
-        _ => ctx.add_error(ParseError::InvalidFunction {
-            line: ctx.line,
-            name: fn_name,
-            code_line: code
-                .lines()
-                .nth(ctx.line - 1)
-                .unwrap_or_default()
-                .to_string(),
-        }),
-    }
-}
-fn checkci(ast: &mut Vec<AST>, ctx: &mut ParseContext){
-    let mut cif = false;
-    for i in &mut *ast{
-        if i == &AST::Cimport("cstd".to_string()){
-            cif = true;
-        }
-    }
-    if !cif{
-        ctx.add_error(ParseError::NoCimport);
-    }
+    ctx.add_error(ParseError::InvalidArgument {
+        span: Span::new(name_span.end, name_span.end),
+        expected: String::from("'('"),
+        found: String::from("end of line"),
+    });
+    ast.push(AST::Poisoned(Item::Call, name_span));
 }
+
 fn parse_write(
-    tok_iter: &mut std::slice::Iter<Tokens>,
+    tok_iter: &mut std::slice::Iter<Token>,
+    name: Span,
     ast: &mut Vec<AST>,
     ctx: &mut ParseContext,
-    code: &String,
 ) {
-    
-    let mut argv = Vec::new();
+    // Span of every argument, from its first to its last token
+    let mut args: Vec<Span> = Vec::new();
+    let mut current: Option<Span> = None;
     let mut in_quotes = false;
-    let mut quote_char = ' ';
+    let mut quote_char = Tokens::DQ;
+    let mut close: Option<Span> = None;
+    let mut end = name;
 
-    eprintln!("{}", "Parsing write arguments.".yellow());
+    trace!(Stage::Parser, "{}", "Parsing write arguments.".yellow());
 
-    while let Some(tok) = tok_iter.next() {
-        if let Tokens::Eof = tok {
-            eprintln!(
+    for tok in tok_iter.by_ref() {
+        if let Tokens::Eof = tok.tok {
+            trace!(
+                Stage::Parser,
                 "{}{}{}",
-                "Reached EOF while parsing write arguments at line: ".blue(),
-                ctx.line,
+                "Reached EOF while parsing write arguments at offset: ".blue(),
+                tok.span.start,
                 " (End of file reached)".blue().bold()
             );
+        }
+        // A string left open at the end of the line was reported by the lexer
+        if in_quotes && matches!(tok.tok, Tokens::Eol | Tokens::Eof) {
+            ast.push(AST::Poisoned(Item::Call, name.to(end)));
+            return;
+        }
+        if !in_quotes && is_boundary(&tok.tok) {
             break;
         }
+        // Anything after the closing bracket is out of place
+        if close.is_some() {
+            if !matches!(tok.tok, Tokens::Space) {
+                let span = recover(tok_iter, tok.span);
+                ctx.add_error(ParseError::UnexpectedToken {
+                    span,
+                    token: ctx.text(span).to_string(),
+                });
+                break;
+            }
+            continue;
+        }
+        end = tok.span;
 
-        match tok {
+        match tok.tok {
             Tokens::DQ | Tokens::SQ => {
-                let current_quote = if matches!(tok, Tokens::DQ) { '"' } else { '\'' };
                 if !in_quotes {
                     in_quotes = true;
-                    quote_char = current_quote;
-                } else if quote_char == current_quote {
+                    quote_char = if tok.tok == Tokens::DQ { Tokens::DQ } else { Tokens::SQ };
+                } else if tok.tok == quote_char {
                     in_quotes = false;
                 }
-                argv.push(current_quote);
             }
-            Tokens::Char(_, _, c) => argv.push(*c),
-            Tokens::Space => {
-                if in_quotes {
-                    argv.push(' ');
+            Tokens::Space => continue,
+            Tokens::Comma | Tokens::RSB if !in_quotes => {
+                // An empty argument is an empty span where it should have been
+                args.push(current.take().unwrap_or(Span::new(tok.span.start, tok.span.start)));
+                if let Tokens::RSB = tok.tok {
+                    close = Some(tok.span);
                 }
+                continue;
             }
-            Tokens::Comma => {
-                argv.push(','); // Collect token
-            }
-            Tokens::Eol => break,
-            _ => continue,
+            _ => {}
         }
+        current = Some(current.map_or(tok.span, |span| span.to(tok.span)));
     }
 
-    if !argv.is_empty() {
-        process_write_args(&argv, ast, ctx, code);
-    }
-}
-
-fn process_write_args(argv: &Vec<char>, ast: &mut Vec<AST>, ctx: &mut ParseContext, code: &String) {
-    let args = argv.iter().collect::<String>();
-    let mut parts = Vec::new();
-    let mut current_part = String::new();
-    let mut in_quotes = false;
-    let mut quote_char = ' ';
-    //let inside_period = false;
-
-    // Iterate through the string and split on commas unless inside quotes
-    for c in args.chars() {
-        if in_quotes {
-            current_part.push(c);
-
-            if c == quote_char {
-                in_quotes = false;
-            }
-        } else {
-            if c == '"' || c == '\'' {
-                if !in_quotes {
-                    in_quotes = true;
-                    quote_char = c;
-                }
-                current_part.push(c);
-            } else if c == ',' {
-                // Only split on comma if we're not inside quotes
-                if !in_quotes {
-                    parts.push(current_part.trim().to_string());
-                    current_part.clear();
-                } else {
-                    current_part.push(c);
-                }
-            } else {
-                current_part.push(c);
-            }
-        }
-    }
+    let Some(close) = close else {
+        ctx.add_error(ParseError::InvalidArgument {
+            span: Span::new(end.end, end.end),
+            expected: String::from("')'"),
+            found: String::from("end of line"),
+        });
+        ast.push(AST::Poisoned(Item::Call, name.to(end)));
+        return;
+    };
 
-    // Push the last part
-    if !current_part.is_empty() {
-        parts.push(current_part.trim().to_string());
-    }
+    process_write_args(&args, name.to(close), ast, ctx);
+}
 
+fn process_write_args(args: &[Span], call: Span, ast: &mut Vec<AST>, ctx: &mut ParseContext) {
     // Check if we have exactly 3 arguments, otherwise report an error
-    if parts.len() != 3 {
+    if args.len() != 3 {
         ctx.add_error(ParseError::InvalidArgument {
-            line: ctx.line,
+            span: call,
             expected: String::from("3 arguments"),
-            found: parts.len().to_string(),
-            code_line: code
-                .lines()
-                .nth(ctx.line - 1)
-                .unwrap_or_default()
-                .to_string(),
-            code: code.clone(),
+            found: args.len().to_string(),
         });
+        ast.push(AST::Poisoned(Item::Call, call));
         return;
     }
+    let errors = ctx.errors.len();
 
     // Check if the first argument is "stdout" or "stderr"
-    let first = parts[0].trim().to_lowercase();
-    let (stdout_stderr, text) = match first.as_str() {
-        "stdout" => (1, parts[1].clone()),
-        "stderr" => (0, parts[1].clone()),
+    let first = ctx.text(args[0]).to_lowercase();
+    let stdout_stderr = match first.as_str() {
+        "stdout" => Some(Stream::Stdout),
+        "stderr" => Some(Stream::Stderr),
         _ => {
             ctx.add_error(ParseError::InvalidArgument {
-                line: ctx.line,
-                expected: String::from("stdout or stderr"),
-                found: first.to_string(),
-                code_line: code
-                    .lines()
-                    .nth(ctx.line - 1)
-                    .unwrap_or_default()
-                    .to_string(),
-                code: code.clone(),
+                span: args[0],
+                expected: String::from(EXPECTED_STREAM),
+                found: ctx.text(args[0]).to_string(),
             });
-            return;
+            None
         }
     };
 
-    // Check the third argument (should be an integer)
-    let size_str = parts[2].trim().to_string();
-    let size: i32 = match size_str.parse::<i32>() {
-        Ok(parsed_size) => parsed_size,
+    // Check the second argument (should be a quoted string)
+    let text = ctx.text(args[1]);
+    let quoted = text.len() >= 2
+        && (text.starts_with('"') && text.ends_with('"')
+            || text.starts_with('\'') && text.ends_with('\''));
+    if !quoted {
+        ctx.add_error(ParseError::InvalidArgument {
+            span: args[1],
+            expected: String::from("a string"),
+            found: text.to_string(),
+        });
+    }
+
+    // Check the third argument (should be an integer, and not a negative one)
+    let size_str = ctx.text(args[2]);
+    let size = match size_str.parse::<i32>() {
+        Ok(parsed_size) if parsed_size >= 0 => Some(parsed_size),
+        Ok(_) => {
+            ctx.add_error(ParseError::InvalidArgument {
+                span: args[2],
+                expected: String::from("a size of 0 or more"),
+                found: size_str.to_string(),
+            });
+            None
+        }
         Err(_) => {
             ctx.add_error(ParseError::InvalidArgument {
-                line: ctx.line,
+                span: args[2],
                 expected: String::from("integer"),
-                found: size_str,
-                code_line: code
-                    .lines()
-                    .nth(ctx.line - 1)
-                    .unwrap_or_default()
-                    .to_string(),
-                code: code.clone(),
+                found: size_str.to_string(),
             });
-            return;
+            None
         }
     };
 
-    // Add the CWrt AST node
-    ast.push(AST::CWrt(stdout_stderr, text, size));
-}
-
-fn highlight_code(line_num: usize, col_num: &usize, code_line: &str) -> String {
-    format!(
-        "{}{}\n{}{}{}",
-        "Line:".blue(),
-        line_num,
-        "Code:".blue(),
-        &code_line[..*col_num],
-        format!("{}", &code_line[*col_num..]).red().bold()
-    )
+    // Each argument is checked on its own, so all of them get reported
+    match (stdout_stderr, size) {
+        (Some(stream), Some(size)) if ctx.errors.len() == errors => {
+            // Add the CWrt AST node
+            ast.push(AST::CWrt(Wrt {
+                stream,
+                text: text.to_string(),
+                size,
+                span: call,
+                args: [args[0], args[1], args[2]],
+            }));
+        }
+        _ => ast.push(AST::Poisoned(Item::Call, call)),
+    }
 }
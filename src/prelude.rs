@@ -0,0 +1,53 @@
+/// A C helper function that generated code may call
+///
+/// Helpers are only emitted into the output when the generated code actually
+/// references them, see [`used_helpers`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Helper {
+    /// Name the generated code calls the helper by
+    pub name: &'static str,
+    /// Headers the definition depends on
+    pub includes: &'static [&'static str],
+    /// Full C definition of the helper
+    pub def: &'static str,
+}
+
+/// `strlen` equivalent, counts the bytes of a NUL terminated string
+pub const NC_STRLEN: Helper = Helper {
+    name: "nc_strlen",
+    includes: &["<stddef.h>"],
+    def: "size_t nc_strlen(const char *str) {\n    size_t c = 0;\n    while (*str++) {\n        c += 1;\n    }\n    return c;\n}\n",
+};
+
+/// Every helper the prelude knows about, in the order they get emitted
+pub const HELPERS: &[&Helper] = &[&NC_STRLEN];
+
+/// Look up a helper by the name generated code calls it by
+pub fn helper(name: &str) -> Option<&'static Helper> {
+    HELPERS.iter().copied().find(|helper| helper.name == name)
+}
+
+/// Find the prelude helpers referenced by a piece of generated C code
+///
+/// # Parameters
+/// - `&str` : The generated C code to scan
+///
+/// # Returns
+/// - `Vec<&Helper>` : Helpers called from `code`, in [`HELPERS`] order
+pub fn used_helpers(code: &str) -> Vec<&'static Helper> {
+    HELPERS
+        .iter()
+        .copied()
+        .filter(|helper| references(code, helper.name))
+        .collect()
+}
+
+/// Check whether `code` contains a call to `name`, ignoring identifiers that
+/// merely end with it (`my_nc_strlen(` does not count)
+fn references(code: &str, name: &str) -> bool {
+    code.match_indices(name).any(|(pos, _)| {
+        let before = code[..pos].chars().next_back();
+        let after = code[pos + name.len()..].trim_start().chars().next();
+        !before.is_some_and(|c| c.is_alphanumeric() || c == '_') && after == Some('(')
+    })
+}
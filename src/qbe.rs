@@ -0,0 +1,308 @@
+//! QBE intermediate language backend
+//!
+//! Generates QBE IL (`.ssa`) for `qbe` to compile to assembly. QBE builds
+//! SSA form itself, so temporaries are assigned as often as the IR assigns
+//! them. Words (`w`) hold `i32`, longs (`l`) hold `i64` and pointers; a long
+//! can be used where a word is expected, the other way around needs `extsw`.
+//! [`Inst::Loc`] becomes `dbgloc`, giving debuggers the source line.
+
+use crate::ir::{BinOp, BlockId, Function, Inst, Module, Terminator, Type, Value};
+use crate::log::Stage;
+use crate::target::Target;
+use crate::trace;
+
+/// Name of the QBE target generating code for `target`, `None` when QBE
+/// can't
+pub fn qbe_target(target: &Target) -> Option<&'static str> {
+    match (target.arch.as_str(), target.os.as_str()) {
+        (_, "windows") => None,
+        ("x86_64", "macos") => Some("amd64_apple"),
+        ("x86_64", _) => Some("amd64_sysv"),
+        ("aarch64" | "arm64", "macos") => Some("arm64_apple"),
+        ("aarch64", _) => Some("arm64"),
+        ("riscv64" | "riscv64gc", _) => Some("rv64"),
+        _ => None,
+    }
+}
+
+/// Generate QBE IL out of an IR module
+///
+/// # Parameters
+/// - `&Module` : The module returned from `lower()`
+/// - `&Target` : Platform the IL will be compiled for, see [`qbe_target`]
+/// - `bool` : Whether to add `dbgloc` instructions pointing back at the source
+///
+/// # Returns
+/// - `String` : The generated QBE IL
+pub fn genqbe(module: &Module, target: &Target, debug_info: bool) -> String {
+    let mut gen = Gen {
+        module,
+        target,
+        // Without a file name there is nothing to point at
+        debug: debug_info && !module.file.is_empty(),
+        out: String::new(),
+        next: 0,
+    };
+    gen.module();
+    gen.out
+}
+
+/// State of code generation for a module
+struct Gen<'a> {
+    module: &'a Module,
+    target: &'a Target,
+    debug: bool,
+    out: String,
+    /// Number of the next conversion temporary of the current function
+    next: usize,
+}
+
+impl Gen<'_> {
+    fn module(&mut self) {
+        if self.debug {
+            let file = self.module.file.replace('\\', "\\\\").replace('"', "\\\"");
+            self.out.push_str(&format!("dbgfile \"{}\"\n\n", file));
+        }
+        for (id, data) in self.module.data.iter().enumerate() {
+            self.out
+                .push_str(&format!("data $d{} = {{ {} }}\n", id, items(&data.bytes)));
+        }
+        for function in &self.module.functions {
+            trace!(Stage::Gen, "Generating QBE IL for {}", function.name);
+            if !self.out.is_empty() {
+                self.out.push('\n');
+            }
+            self.function(function);
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.next = 0;
+        let params = function
+            .params
+            .iter()
+            .map(|param| format!("{} %t{}", base(function.temps[param.0]), param.0))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let ret = function
+            .ret
+            .map(|ty| format!("{} ", base(ty)))
+            .unwrap_or_default();
+        let export = if function.name == "main" {
+            "export "
+        } else {
+            ""
+        };
+        self.out.push_str(&format!(
+            "{}function {}${}({}) {{\n",
+            export, ret, function.name, params
+        ));
+        // The first block is entered from the start, it can't be jumped to
+        self.out.push_str("@start\n");
+        for (id, block) in function.blocks.iter().enumerate() {
+            self.out.push_str(&format!("{}\n", label(BlockId(id))));
+            for inst in &block.insts {
+                self.gen_inst(function, inst);
+            }
+            self.terminator(function, &block.term, BlockId(id + 1));
+        }
+        self.out.push_str("}\n");
+    }
+
+    fn inst(&mut self, inst: String) {
+        self.out.push_str(&format!("    {}\n", inst));
+    }
+
+    fn gen_inst(&mut self, function: &Function, inst: &Inst) {
+        match inst {
+            Inst::Loc { line, .. } => {
+                if self.debug {
+                    self.inst(format!("dbgloc {}", line));
+                }
+            }
+            Inst::Copy { dest, value } => {
+                let ty = function.temps[dest.0];
+                let value = self.value(function, value, ty);
+                self.inst(format!("%t{} ={} copy {}", dest.0, base(ty), value));
+            }
+            Inst::Binary { dest, op, lhs, rhs } => {
+                let ty = function.temps[dest.0];
+                // Comparisons are made in the wider of their operand types, like in C
+                let operands = if !op.is_comparison() {
+                    ty
+                } else if [lhs, rhs].iter().all(|v| function.type_of(v) == Type::I32) {
+                    Type::I32
+                } else {
+                    Type::I64
+                };
+                let lhs = self.value(function, lhs, operands);
+                let rhs = self.value(function, rhs, operands);
+                let name = if op.is_comparison() {
+                    format!("{}{}", instruction(*op), base(operands))
+                } else {
+                    instruction(*op).to_string()
+                };
+                self.inst(format!(
+                    "%t{} ={} {} {}, {}",
+                    dest.0,
+                    base(ty),
+                    name,
+                    lhs,
+                    rhs
+                ));
+            }
+            Inst::Call {
+                dest,
+                function: callee,
+                args,
+            } => {
+                // Arguments take the callee's parameter types when it is known
+                let types: Vec<Type> = match self.module.function(callee) {
+                    Some(callee) => callee
+                        .params
+                        .iter()
+                        .map(|param| callee.temps[param.0])
+                        .collect(),
+                    None => args.iter().map(|arg| function.type_of(arg)).collect(),
+                };
+                let args = args
+                    .iter()
+                    .zip(types)
+                    .map(|(arg, ty)| format!("{} {}", base(ty), self.value(function, arg, ty)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let call = format!("call ${}({})", callee, args);
+                match dest {
+                    Some(dest) => {
+                        let ty = base(function.temps[dest.0]);
+                        self.inst(format!("%t{} ={} {}", dest.0, ty, call));
+                    }
+                    None => self.inst(call),
+                }
+            }
+            Inst::Write { fd, data, len } => {
+                let fd = self.value(function, fd, Type::I32);
+                let data = self.value(function, data, Type::Ptr);
+                let len = self.value(function, len, Type::I64);
+                let write = self.target.write_call().function;
+                self.inst(format!("call ${}(w {}, l {}, l {})", write, fd, data, len));
+            }
+        }
+    }
+
+    fn terminator(&mut self, function: &Function, term: &Terminator, next: BlockId) {
+        match term {
+            Terminator::Return(value) => match (value, function.ret) {
+                (Some(value), Some(ty)) => {
+                    let value = self.value(function, value, ty);
+                    self.inst(format!("ret {}", value));
+                }
+                _ => self.inst(String::from("ret")),
+            },
+            // Falling through to the next block needs no jump
+            Terminator::Jump(to) if *to == next => {}
+            Terminator::Jump(to) => self.inst(format!("jmp {}", label(*to))),
+            Terminator::Branch {
+                cond,
+                then,
+                otherwise,
+            } => {
+                // `jnz` only looks at a word, a long has to be compared to 0
+                let cond = match function.type_of(cond) {
+                    Type::I32 => self.value(function, cond, Type::I32),
+                    ty => {
+                        let value = self.value(function, cond, ty);
+                        let temp = self.fresh();
+                        self.inst(format!("{} =w cnel {}, 0", temp, value));
+                        temp
+                    }
+                };
+                self.inst(format!(
+                    "jnz {}, {}, {}",
+                    cond,
+                    label(*then),
+                    label(*otherwise)
+                ));
+            }
+        }
+    }
+
+    /// Name for a new conversion temporary
+    fn fresh(&mut self) -> String {
+        self.next += 1;
+        format!("%c{}", self.next - 1)
+    }
+
+    /// Operand for a value, converted to `ty`
+    fn value(&mut self, function: &Function, value: &Value, ty: Type) -> String {
+        match value {
+            Value::Int(value, _) => match ty {
+                Type::I32 => (*value as i32).to_string(),
+                Type::I64 | Type::Ptr => value.to_string(),
+            },
+            // Words are sign extended into longs, longs are used as words as is
+            Value::Temp(temp) if function.temps[temp.0] == Type::I32 && ty != Type::I32 => {
+                let extended = self.fresh();
+                self.inst(format!("{} =l extsw %t{}", extended, temp.0));
+                extended
+            }
+            Value::Temp(temp) => format!("%t{}", temp.0),
+            Value::Data(data) => format!("$d{}", data.0),
+        }
+    }
+}
+
+/// QBE base type of an IR type
+fn base(ty: Type) -> &'static str {
+    match ty {
+        Type::I32 => "w",
+        Type::I64 | Type::Ptr => "l",
+    }
+}
+
+/// Label of a block
+fn label(id: BlockId) -> String {
+    format!("@bb{}", id.0)
+}
+
+/// Instruction computing an operator, comparisons still need the type of
+/// their operands appended
+fn instruction(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::Div => "div",
+        BinOp::Rem => "rem",
+        BinOp::Eq => "ceq",
+        BinOp::Ne => "cne",
+        BinOp::Lt => "cslt",
+        BinOp::Le => "csle",
+        BinOp::Gt => "csgt",
+        BinOp::Ge => "csge",
+    }
+}
+
+/// Data items spelling out bytes, NUL terminated like the C backend's string
+/// literals: printable runs as strings, anything else byte by byte
+fn items(bytes: &[u8]) -> String {
+    let mut items = Vec::new();
+    let mut run = String::new();
+    for &byte in bytes {
+        match byte {
+            0x20..=0x7e if byte != b'"' && byte != b'\\' => run.push(char::from(byte)),
+            _ => {
+                if !run.is_empty() {
+                    items.push(format!("b \"{}\"", run));
+                    run.clear();
+                }
+                items.push(format!("b {}", byte));
+            }
+        }
+    }
+    if !run.is_empty() {
+        items.push(format!("b \"{}\"", run));
+    }
+    items.push(String::from("b 0"));
+    items.join(", ")
+}
@@ -0,0 +1,281 @@
+//! Diagnostics in the formats selected with `--error-format`
+//!
+//! Besides the rendering meant for people, diagnostics can be written as JSON,
+//! one object per line, or as a single [SARIF] log for code scanning tools.
+//! Both only depend on the diagnostics themselves, so the same input always
+//! produces the same output.
+//!
+//! [SARIF]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+
+use crate::diag::{Diagnostic, Severity, SourceFile, Span};
+use crate::json::Json;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// How diagnostics are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// Rendered with source snippets, the default
+    #[default]
+    Human,
+    /// One JSON object per diagnostic and line
+    Json,
+    /// A SARIF 2.1.0 log holding every diagnostic
+    Sarif,
+}
+
+impl ErrorFormat {
+    /// Parse the value of `--error-format`
+    ///
+    /// # Parameters
+    /// - `&str` : The name of the format
+    ///
+    /// # Returns
+    /// - `Result<ErrorFormat, String>` : The format, or the name if it is unknown
+    pub fn parse(name: &str) -> Result<ErrorFormat, String> {
+        match name {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            "sarif" => Ok(ErrorFormat::Sarif),
+            _ => Err(name.to_string()),
+        }
+    }
+}
+
+/// Write diagnostics in the given format
+///
+/// # Parameters
+/// - `&[Diagnostic]` : The diagnostics, in the order they were found
+/// - `Option<&SourceFile>` : File the diagnostics point into, if any
+/// - `ErrorFormat` : How to write them
+///
+/// # Returns
+/// - `String` : The formatted diagnostics, ending in a newline
+pub fn format(diags: &[Diagnostic], file: Option<&SourceFile>, format: ErrorFormat) -> String {
+    match format {
+        ErrorFormat::Human => diags.iter().map(|diag| diag.render(file)).collect(),
+        ErrorFormat::Json => diags
+            .iter()
+            .map(|diag| json(diag, file).compact() + "\n")
+            .collect(),
+        ErrorFormat::Sarif => sarif(diags, file).pretty() + "\n",
+    }
+}
+
+/// A diagnostic as a JSON object
+///
+/// ```text
+/// {"code":null,"severity":"error","message":"...","file":"hello.nc",
+///  "spans":[{"start":8,"end":13,"line_start":1,"column_start":9,"line_end":1,
+///  "column_end":14,"primary":true,"label":"..."}],"notes":[],"help":[],
+///  "suggestions":[{"message":"...","replacement":"cstd","applicability":"machine-applicable",
+///  "span":{...}}]}
+/// ```
+///
+/// Lines and columns start at 1, `end` and `column_end` are exclusive.
+pub fn json(diag: &Diagnostic, file: Option<&SourceFile>) -> Json {
+    let strings = |list: &[String]| Json::Array(list.iter().map(Json::string).collect());
+    Json::object([
+        ("code", diag.code.clone().map_or(Json::Null, Json::String)),
+        ("severity", Json::string(diag.severity.name())),
+        ("message", Json::string(&diag.message)),
+        ("file", file_name(file).map_or(Json::Null, Json::string)),
+        (
+            "spans",
+            Json::Array(
+                diag.labels
+                    .iter()
+                    .map(|label| {
+                        let mut span = span(label.span, file);
+                        if let Json::Object(fields) = &mut span {
+                            fields.push(("primary".to_string(), Json::Bool(label.primary)));
+                            fields.push(("label".to_string(), Json::string(&label.message)));
+                        }
+                        span
+                    })
+                    .collect(),
+            ),
+        ),
+        ("notes", strings(&diag.notes)),
+        ("help", strings(&diag.help)),
+        (
+            "suggestions",
+            Json::Array(
+                diag.suggestions
+                    .iter()
+                    .map(|suggestion| {
+                        Json::object([
+                            ("message", Json::string(&suggestion.message)),
+                            ("replacement", Json::string(&suggestion.replacement)),
+                            ("applicability", Json::string(suggestion.applicability.name())),
+                            ("span", span(suggestion.span, file)),
+                        ])
+                    })
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+/// Every diagnostic as a single SARIF log with one run
+pub fn sarif(diags: &[Diagnostic], file: Option<&SourceFile>) -> Json {
+    let mut rules: Vec<&str> = diags
+        .iter()
+        .filter_map(|diag| diag.code.as_deref())
+        .collect();
+    rules.sort_unstable();
+    rules.dedup();
+
+    let driver = Json::object([
+        ("name", Json::string("neitc")),
+        ("version", Json::string(env!("CARGO_PKG_VERSION"))),
+        (
+            "rules",
+            Json::Array(
+                rules
+                    .iter()
+                    .map(|rule| Json::object([("id", Json::string(*rule))]))
+                    .collect(),
+            ),
+        ),
+    ]);
+    let results = diags.iter().map(|diag| sarif_result(diag, file)).collect();
+
+    Json::object([
+        ("$schema", Json::string(SARIF_SCHEMA)),
+        ("version", Json::string("2.1.0")),
+        (
+            "runs",
+            Json::Array(vec![Json::object([
+                ("tool", Json::object([("driver", driver)])),
+                ("results", Json::Array(results)),
+            ])]),
+        ),
+    ])
+}
+
+fn sarif_result(diag: &Diagnostic, file: Option<&SourceFile>) -> Json {
+    let level = match diag.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note | Severity::Help => "note",
+    };
+    // Notes and help have nowhere else to go, keep them with the message
+    let mut text = diag.message.clone();
+    for note in &diag.notes {
+        text.push_str(&format!("\nnote: {}", note));
+    }
+    for help in &diag.help {
+        text.push_str(&format!("\nhelp: {}", help));
+    }
+
+    let location = |span: Span, message: Option<&str>| {
+        let mut fields = vec![(
+            "physicalLocation",
+            Json::object([
+                ("artifactLocation", artifact(file)),
+                ("region", region(span, file)),
+            ]),
+        )];
+        if let Some(message) = message {
+            fields.push(("message", Json::object([("text", Json::string(message))])));
+        }
+        Json::object(fields)
+    };
+    let locations = diag
+        .labels
+        .iter()
+        .filter(|label| label.primary)
+        .map(|label| location(label.span, None))
+        .collect();
+    let related = diag
+        .labels
+        .iter()
+        .filter(|label| !label.primary)
+        .map(|label| location(label.span, Some(&label.message)))
+        .collect();
+    let fixes = diag
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            Json::object([
+                (
+                    "description",
+                    Json::object([("text", Json::string(&suggestion.message))]),
+                ),
+                (
+                    "artifactChanges",
+                    Json::Array(vec![Json::object([
+                        ("artifactLocation", artifact(file)),
+                        (
+                            "replacements",
+                            Json::Array(vec![Json::object([
+                                ("deletedRegion", region(suggestion.span, file)),
+                                (
+                                    "insertedContent",
+                                    Json::object([("text", Json::string(&suggestion.replacement))]),
+                                ),
+                            ])]),
+                        ),
+                    ])]),
+                ),
+            ])
+        })
+        .collect();
+
+    let mut fields = Vec::new();
+    if let Some(code) = &diag.code {
+        fields.push(("ruleId", Json::string(code)));
+    }
+    fields.push(("level", Json::string(level)));
+    fields.push(("message", Json::object([("text", Json::String(text))])));
+    fields.push(("locations", Json::Array(locations)));
+    fields.push(("relatedLocations", Json::Array(related)));
+    fields.push(("fixes", Json::Array(fixes)));
+    Json::object(fields)
+}
+
+/// Name of the file diagnostics point into, `None` when it has none
+fn file_name(file: Option<&SourceFile>) -> Option<&str> {
+    file.map(|file| file.name.as_str())
+        .filter(|name| !name.is_empty())
+}
+
+/// Byte offsets of a span along with its lines and columns, when the file is known
+fn span(span: Span, file: Option<&SourceFile>) -> Json {
+    let mut fields = vec![
+        ("start", Json::Number(span.start as i64)),
+        ("end", Json::Number(span.end as i64)),
+    ];
+    if let Some(file) = file {
+        let (start_line, start_col) = file.line_col(span.start);
+        let (end_line, end_col) = file.line_col(span.end);
+        fields.push(("line_start", Json::Number(start_line as i64)));
+        fields.push(("column_start", Json::Number(start_col as i64)));
+        fields.push(("line_end", Json::Number(end_line as i64)));
+        fields.push(("column_end", Json::Number(end_col as i64)));
+    }
+    Json::object(fields)
+}
+
+fn artifact(file: Option<&SourceFile>) -> Json {
+    Json::object([("uri", file_name(file).map_or(Json::Null, Json::string))])
+}
+
+/// A SARIF region, 1 based with an exclusive end column
+fn region(span: Span, file: Option<&SourceFile>) -> Json {
+    let Some(file) = file else {
+        return Json::object([
+            ("charOffset", Json::Number(span.start as i64)),
+            ("charLength", Json::Number((span.end - span.start) as i64)),
+        ]);
+    };
+    let (start_line, start_col) = file.line_col(span.start);
+    let (end_line, end_col) = file.line_col(span.end);
+    Json::object([
+        ("startLine", Json::Number(start_line as i64)),
+        ("startColumn", Json::Number(start_col as i64)),
+        ("endLine", Json::Number(end_line as i64)),
+        ("endColumn", Json::Number(end_col as i64)),
+    ])
+}
@@ -0,0 +1,72 @@
+//! "Did you mean" suggestions for misspelled names
+//!
+//! Neit has no user defined names yet, so every name is one of a few closed
+//! sets: builtin functions, libraries, keywords and streams. A name that is
+//! close enough to exactly one of them was almost certainly meant as that one.
+
+/// Builtin functions
+pub const BUILTINS: &[&str] = &["__wrt__"];
+
+/// Libraries `cimport` knows, besides `link:<lib>`
+pub const LIBRARIES: &[&str] = &["cstd"];
+
+/// Keywords
+pub const KEYWORDS: &[&str] = &["cimport"];
+
+/// Streams `__wrt__` can write to
+pub const STREAMS: &[&str] = &["stdout", "stderr"];
+
+/// Number of single character insertions, deletions, substitutions and
+/// swaps of adjacent characters needed to turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Rows i - 2, i - 1 and i of the distance matrix
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut row = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        row[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before, &mut prev);
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev[b.len()]
+}
+
+/// Find the candidate `name` was most likely meant to be
+///
+/// # Parameters
+/// - `&str` : The unknown name
+/// - `&[&str]` : Names that are valid in its place
+///
+/// # Returns
+/// - `Option<&str>` : The closest candidate, `None` when none is close enough
+pub fn best_match<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    // Wrong case or number of underscores, like `_wrt_`, always counts as a match
+    let bare = |name: &str| name.replace('_', "").to_lowercase();
+    if let Some(candidate) = candidates
+        .iter()
+        .find(|candidate| **candidate != name && bare(candidate) == bare(name))
+    {
+        return Some(candidate);
+    }
+    // Allow about one typo per three characters, not counting the underscores
+    // builtins are wrapped in
+    let limit = (bare(name).chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .copied()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
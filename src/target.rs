@@ -0,0 +1,223 @@
+//! Platforms neitc can generate code for
+//!
+//! A [`Target`] is described by an LLVM style triple such as
+//! `x86_64-unknown-linux-gnu`. It decides how streams map onto the platform's
+//! I/O calls and which `#[target_os = "..."]` / `#[target_arch = "..."]` items
+//! of a program are compiled in.
+
+use std::{env::consts, fmt};
+
+use crate::parse1::{Cfg, Stream, AST};
+
+/// A platform to generate code for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    /// The full triple, as given on the command line
+    pub triple: String,
+    /// CPU architecture, e.g. `x86_64`
+    pub arch: String,
+    /// Operating system, e.g. `linux` or `windows`
+    pub os: String,
+    /// ABI / C library, e.g. `gnu` or `msvc`, empty when the triple has none
+    pub env: String,
+}
+
+/// How a target writes to a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteCall {
+    /// Header declaring the function
+    pub include: &'static str,
+    /// Function taking `(fd, buf, len)`
+    pub function: &'static str,
+}
+
+/// Operating systems recognised in a triple, with the name they go by in Neit
+const KNOWN_OS: &[(&str, &str)] = &[
+    ("linux", "linux"),
+    ("windows", "windows"),
+    ("mingw32", "windows"),
+    ("darwin", "macos"),
+    ("macos", "macos"),
+    ("freebsd", "freebsd"),
+    ("netbsd", "netbsd"),
+    ("openbsd", "openbsd"),
+    ("wasi", "wasi"),
+];
+
+impl Target {
+    /// Parse a target triple like `x86_64-unknown-linux-gnu` or `x86_64-w64-mingw32`
+    ///
+    /// # Parameters
+    /// - `&str` : The triple
+    ///
+    /// # Returns
+    /// - `Result<Target, String>` : The target, or why the triple isn't understood
+    pub fn parse(triple: &str) -> Result<Target, String> {
+        let parts: Vec<&str> = triple.split('-').collect();
+        if parts.len() < 2 || parts.iter().any(|part| part.is_empty()) {
+            return Err(format!(
+                "Invalid target '{}', expected a triple like 'x86_64-unknown-linux-gnu'",
+                triple
+            ));
+        }
+        let Some(pos) = parts[1..]
+            .iter()
+            .position(|part| KNOWN_OS.iter().any(|(name, _)| part.starts_with(name)))
+        else {
+            return Err(format!("Unknown operating system in target '{}'", triple));
+        };
+        let os_part = parts[pos + 1];
+        let os = KNOWN_OS
+            .iter()
+            .find(|(name, _)| os_part.starts_with(name))
+            .map(|(_, os)| os.to_string())
+            .unwrap_or_default();
+        // mingw32 names the C library, not an environment after it
+        let env = if os_part == "mingw32" {
+            String::from("gnu")
+        } else {
+            parts.get(pos + 2).copied().unwrap_or_default().to_string()
+        };
+        Ok(Target {
+            triple: triple.to_string(),
+            arch: parts[0].to_string(),
+            os,
+            env,
+        })
+    }
+
+    /// The machine neitc itself runs on
+    pub fn host() -> Target {
+        let triple = match (consts::OS, consts::ARCH) {
+            ("linux", arch) if cfg!(target_env = "musl") => format!("{}-unknown-linux-musl", arch),
+            ("linux", arch) => format!("{}-unknown-linux-gnu", arch),
+            ("windows", arch) if cfg!(target_env = "gnu") => format!("{}-pc-windows-gnu", arch),
+            ("windows", arch) => format!("{}-pc-windows-msvc", arch),
+            ("macos", arch) => format!("{}-apple-darwin", arch),
+            (os, arch) => format!("{}-unknown-{}", arch, os),
+        };
+        Target::parse(&triple).unwrap_or(Target {
+            triple,
+            arch: consts::ARCH.to_string(),
+            os: consts::OS.to_string(),
+            env: String::new(),
+        })
+    }
+
+    /// Check whether this is the machine neitc runs on, a triple without an
+    /// environment taking the host's
+    pub fn is_host(&self) -> bool {
+        let host = Target::host();
+        self.arch == host.arch
+            && self.os == host.os
+            && (self.env.is_empty() || self.env == host.env)
+    }
+
+    /// Check whether programs run on Windows, where writes go through the CRT
+    pub fn is_windows(&self) -> bool {
+        self.os == "windows"
+    }
+
+    /// Check whether programs run on a WebAssembly runtime through WASI
+    pub fn is_wasi(&self) -> bool {
+        self.os == "wasi"
+    }
+
+    /// Extension of executables, without the dot
+    pub fn exe_extension(&self) -> &'static str {
+        if self.is_windows() {
+            "exe"
+        } else if self.is_wasi() {
+            "wasm"
+        } else {
+            ""
+        }
+    }
+
+    /// Name prefix of a gcc cross compiler for this target, `<prefix>-gcc`
+    pub fn gcc_prefix(&self) -> String {
+        if self.is_windows() && self.env == "gnu" {
+            format!("{}-w64-mingw32", self.arch)
+        } else {
+            self.triple.clone()
+        }
+    }
+
+    /// Size of a pointer in bits, 64 for architectures with 64 in their name
+    pub fn pointer_width(&self) -> u32 {
+        if self.arch.contains("64") || self.arch == "s390x" || self.arch == "sparcv9" {
+            64
+        } else {
+            32
+        }
+    }
+
+    /// File descriptor a stream is written through, the same on every target
+    pub fn fd(stream: Stream) -> i32 {
+        match stream {
+            Stream::Stdout => 1,
+            Stream::Stderr => 2,
+        }
+    }
+
+    /// Function used to write to a stream, POSIX `write` or the CRT's `_write`
+    /// on Windows, where `<unistd.h>` doesn't exist
+    pub fn write_call(&self) -> WriteCall {
+        if self.is_windows() {
+            WriteCall {
+                include: "<io.h>",
+                function: "_write",
+            }
+        } else {
+            WriteCall {
+                include: "<unistd.h>",
+                function: "write",
+            }
+        }
+    }
+
+    /// Check whether a `#[target_...]` condition holds for this target
+    pub fn matches(&self, cfg: &Cfg) -> bool {
+        match cfg {
+            Cfg::TargetOs(os) => *os == self.os,
+            Cfg::TargetArch(arch) => *arch == self.arch,
+        }
+    }
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target::host()
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.triple)
+    }
+}
+
+/// Drop the items whose `#[target_...]` condition doesn't hold for `target`
+/// and unwrap the rest
+///
+/// # Parameters
+/// - `Vec<AST>` : The AST returned from `parse()`
+/// - `&Target` : The target being compiled for
+///
+/// # Returns
+/// - `Vec<AST>` : The AST without any `AST::Cfg` nodes
+pub fn resolve_cfg(ast: Vec<AST>, target: &Target) -> Vec<AST> {
+    let mut resolved = Vec::new();
+    for item in ast {
+        match item {
+            AST::Cfg(cfg, items) => {
+                if target.matches(&cfg) {
+                    resolved.extend(resolve_cfg(items, target));
+                }
+            }
+            AST::Lint(attr, items) => resolved.push(AST::Lint(attr, resolve_cfg(items, target))),
+            item => resolved.push(item),
+        }
+    }
+    resolved
+}
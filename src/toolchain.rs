@@ -0,0 +1,503 @@
+//! C compilers neitc knows how to drive
+//!
+//! Without `--bcompiler` the first working compiler out of `$CC`, `clang`,
+//! `gcc`, `cc` and `tcc` is used. Each one is identified by its [`Flavor`] so
+//! flags can be spelled the way that compiler expects. When cross compiling
+//! only `$CC`, `clang --target=<triple>` and a `<triple>-gcc` cross compiler
+//! are considered.
+//!
+//! LLVM IR from `--backend=llvm` is built by clang, or compiled by `llc` and
+//! linked by whichever C compiler is found. Assembly from `--backend=asm`
+//! only needs binutils' `as` and `ld`, QBE IL from `--backend=qbe` needs
+//! `qbe` and `as`, and a C compiler to link. WebAssembly text from
+//! `--backend=wasm` is turned into a `.wasm` module by `wat2wasm` or
+//! `wasm-tools`, and run by `wasmtime` or `wasm-interp`.
+
+use std::{env, fmt, path::Path, process::Command};
+
+use crate::target::Target;
+use crate::verbose;
+
+/// Compilers probed when none is given, after `$CC`
+pub const CANDIDATES: &[&str] = &["clang", "gcc", "cc", "tcc"];
+
+/// Command line dialect of a C compiler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    Clang,
+    /// gcc and anything claiming to be compatible with it
+    Gcc,
+    /// Tiny C Compiler, no optimizer
+    Tcc,
+}
+
+impl fmt::Display for Flavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Flavor::Clang => "clang",
+            Flavor::Gcc => "gcc",
+            Flavor::Tcc => "tcc",
+        })
+    }
+}
+
+/// A C compiler found on this machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toolchain {
+    /// Program to run
+    pub program: String,
+    /// Arguments that come with the program, e.g. from `CC="ccache gcc"`
+    pub args: Vec<String>,
+    pub flavor: Flavor,
+}
+
+impl Toolchain {
+    /// Find a C compiler to build with
+    ///
+    /// # Parameters
+    /// - `Option<&str>` : Compiler given with `--bcompiler`, used instead of probing
+    /// - `&Target` : Platform the executable is built for
+    ///
+    /// # Returns
+    /// - `Result<Toolchain, String>` : The compiler, or why none could be used
+    pub fn detect(bcompiler: Option<&str>, target: &Target) -> Result<Toolchain, String> {
+        if let Some(compiler) = bcompiler {
+            return Toolchain::probe(compiler)
+                .map(|toolchain| toolchain.targeting(target))
+                .ok_or_else(|| format!("Unable to run the C compiler '{}'", compiler));
+        }
+
+        let from_env = env::var("CC").ok().filter(|cc| !cc.trim().is_empty());
+        let mut candidates: Vec<String> = from_env.into_iter().collect();
+        if target.is_host() {
+            candidates.extend(CANDIDATES.iter().map(|cc| cc.to_string()));
+        } else {
+            candidates.push(String::from("clang"));
+            candidates.push(format!("{}-gcc", target.gcc_prefix()));
+        }
+        for compiler in &candidates {
+            let Some(toolchain) = Toolchain::probe(compiler) else {
+                verbose!("C compiler '{}' is not available", compiler);
+                continue;
+            };
+            // `$CC` is usually a compiler for the host
+            if !target.is_host() && !toolchain.builds_for(target) {
+                verbose!("C compiler '{}' does not build for {}", compiler, target);
+                continue;
+            }
+            verbose!(
+                "Using C compiler '{}' ({}) for {}",
+                compiler,
+                toolchain.flavor,
+                target
+            );
+            return Ok(toolchain.targeting(target));
+        }
+        Err(format!(
+            "No C compiler for {} found, tried {}. Install one or pass '--bcompiler'",
+            target,
+            candidates.join(", ")
+        ))
+    }
+
+    /// Check that `compiler` runs and find out which flavor it is
+    pub fn probe(compiler: &str) -> Option<Toolchain> {
+        let mut words = compiler.split_whitespace().map(String::from);
+        let program = words.next()?;
+        let args: Vec<String> = words.collect();
+        // tcc has no --version, but prints its version with -v
+        let flag = if name_of(&program).contains("tcc") {
+            "-v"
+        } else {
+            "--version"
+        };
+        let output = Command::new(&program).args(&args).arg(flag).output().ok()?;
+        let version = String::from_utf8_lossy(&output.stdout).to_lowercase();
+        let flavor = if version.contains("clang") {
+            Flavor::Clang
+        } else if version.contains("tcc") {
+            Flavor::Tcc
+        } else {
+            Flavor::Gcc
+        };
+        Some(Toolchain {
+            program,
+            args,
+            flavor,
+        })
+    }
+
+    /// Triple the compiler builds for by default, as printed by `-dumpmachine`
+    pub fn machine(&self) -> Option<String> {
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .arg("-dumpmachine")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        let machine = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (!machine.is_empty()).then_some(machine)
+    }
+
+    /// Check whether the compiler can build for `target`, clang builds for
+    /// any target it is given, other compilers only for their own machine
+    pub fn builds_for(&self, target: &Target) -> bool {
+        if self.flavor == Flavor::Clang {
+            return true;
+        }
+        let Some(machine) = self.machine().and_then(|machine| Target::parse(&machine).ok())
+        else {
+            return false;
+        };
+        machine.arch == target.arch
+            && machine.os == target.os
+            && (target.env.is_empty() || machine.env == target.env)
+    }
+
+    /// Make the compiler build for `target`, only clang needs to be told,
+    /// gcc cross compilers are separate programs
+    fn targeting(mut self, target: &Target) -> Toolchain {
+        if self.flavor == Flavor::Clang && !target.is_host() {
+            self.args.push(format!("--target={}", target.triple));
+        }
+        self
+    }
+
+    /// Translate a flag from the neitc command line into this compiler's
+    /// dialect, `None` when the compiler has no equivalent
+    pub fn flag(&self, flag: &str) -> Option<String> {
+        match (self.flavor, flag) {
+            (Flavor::Tcc, "-O1" | "-O2" | "-O3") => {
+                verbose!("tcc does not optimize, ignoring '{}'", flag);
+                None
+            }
+            _ => Some(flag.to_string()),
+        }
+    }
+
+    /// Build the command compiling `c_file` into `binary`
+    ///
+    /// # Parameters
+    /// - `&Path` : The generated C code
+    /// - `&Path` : Executable to create
+    /// - `&[String]` : Extra flags from the command line
+    /// - `&[String]` : Libraries to link, without the `-l`
+    ///
+    /// # Returns
+    /// - `Command` : The compiler invocation, ready to run
+    pub fn command(
+        &self,
+        c_file: &Path,
+        binary: &Path,
+        cflags: &[String],
+        libs: &[String],
+    ) -> Command {
+        let mut cmd = Command::new(&self.program);
+        // Flags go before the source, libraries after it so the linker can resolve them
+        cmd.args(&self.args)
+            .args(cflags.iter().filter_map(|flag| self.flag(flag)))
+            .arg(c_file)
+            .arg("-o")
+            .arg(binary)
+            .args(libs.iter().map(|lib| format!("-l{}", lib)));
+        cmd
+    }
+}
+
+/// Programs building LLVM IR into an executable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlvmToolchain {
+    /// `llc`, compiling the IR to an object file when the linker can't
+    pub llc: Option<String>,
+    /// Compiler driver linking the program, clang also compiles the IR itself
+    pub linker: Toolchain,
+}
+
+impl LlvmToolchain {
+    /// Find the programs to build LLVM IR with, clang when available and
+    /// otherwise `llc` along with a C compiler to link
+    ///
+    /// # Parameters
+    /// - `Option<&str>` : Compiler given with `--bcompiler`, used to link
+    /// - `&Target` : Platform the executable is built for
+    ///
+    /// # Returns
+    /// - `Result<LlvmToolchain, String>` : The programs, or why LLVM IR can't be built
+    pub fn detect(bcompiler: Option<&str>, target: &Target) -> Result<LlvmToolchain, String> {
+        let clang = match bcompiler {
+            Some(_) => None,
+            None => Toolchain::probe("clang").filter(|clang| clang.flavor == Flavor::Clang),
+        };
+        let linker = match clang {
+            Some(clang) => clang.targeting(target),
+            None => Toolchain::detect(bcompiler, target)?,
+        };
+        if linker.flavor == Flavor::Clang {
+            verbose!("Building LLVM IR with '{}'", linker.program);
+            return Ok(LlvmToolchain { llc: None, linker });
+        }
+
+        let llc = Command::new("llc")
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if !llc {
+            return Err(format!(
+                "Building LLVM IR needs clang, or llc to compile it and a C compiler to link, but '{}' can't compile it and llc is not available",
+                linker.program
+            ));
+        }
+        verbose!(
+            "Compiling LLVM IR with 'llc', linking with '{}'",
+            linker.program
+        );
+        Ok(LlvmToolchain {
+            llc: Some(String::from("llc")),
+            linker,
+        })
+    }
+
+    /// Build the commands turning `ll_file` into `binary`, to be run in order
+    ///
+    /// With `llc` the object file is written next to `ll_file`, see [`Toolchain::command`]
+    /// for the other parameters.
+    pub fn commands(
+        &self,
+        ll_file: &Path,
+        binary: &Path,
+        cflags: &[String],
+        libs: &[String],
+        target: &Target,
+    ) -> Vec<Command> {
+        let llc = match &self.llc {
+            Some(llc) => llc,
+            None => return vec![self.linker.command(ll_file, binary, cflags, libs)],
+        };
+        let object = ll_file.with_extension("o");
+        let mut compile = Command::new(llc);
+        // Position independent, for linkers building PIE executables by default
+        compile
+            .arg("-filetype=obj")
+            .arg("-relocation-model=pic")
+            .arg(format!("-mtriple={}", target.triple))
+            .args(
+                cflags
+                    .iter()
+                    .filter(|flag| matches!(flag.as_str(), "-O0" | "-O1" | "-O2" | "-O3")),
+            )
+            .arg(ll_file)
+            .arg("-o")
+            .arg(&object);
+        vec![compile, self.linker.command(&object, binary, cflags, libs)]
+    }
+}
+
+/// Assembler and linker building the output of `--backend=asm`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmToolchain {
+    pub assembler: String,
+    pub linker: String,
+}
+
+impl AsmToolchain {
+    /// Find `as` and `ld` for `target`
+    ///
+    /// # Parameters
+    /// - `&Target` : Platform the executable is built for
+    ///
+    /// # Returns
+    /// - `Result<AsmToolchain, String>` : The programs, or which one is missing
+    pub fn detect(target: &Target) -> Result<AsmToolchain, String> {
+        let toolchain = AsmToolchain {
+            assembler: binutil("as", target)?,
+            linker: binutil("ld", target)?,
+        };
+        verbose!(
+            "Assembling with '{}', linking with '{}'",
+            toolchain.assembler,
+            toolchain.linker
+        );
+        Ok(toolchain)
+    }
+
+    /// Build the commands turning `s_file` into `binary`, to be run in order,
+    /// the object file is written next to `s_file`
+    pub fn commands(&self, s_file: &Path, binary: &Path) -> Vec<Command> {
+        let object = s_file.with_extension("o");
+        let mut assemble = Command::new(&self.assembler);
+        assemble.arg(s_file).arg("-o").arg(&object);
+        let mut link = Command::new(&self.linker);
+        link.arg(&object).arg("-o").arg(binary);
+        vec![assemble, link]
+    }
+}
+
+/// Programs building the output of `--backend=qbe`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QbeToolchain {
+    pub qbe: String,
+    pub assembler: String,
+    /// C compiler linking the program against the C library
+    pub linker: Toolchain,
+}
+
+impl QbeToolchain {
+    /// Find `qbe`, `as` and a C compiler to link with
+    ///
+    /// # Parameters
+    /// - `Option<&str>` : Compiler given with `--bcompiler`, used to link
+    /// - `&Target` : Platform the executable is built for
+    ///
+    /// # Returns
+    /// - `Result<QbeToolchain, String>` : The programs, or which one is missing
+    pub fn detect(bcompiler: Option<&str>, target: &Target) -> Result<QbeToolchain, String> {
+        // qbe has no `--version`, finding it is enough
+        if Command::new("qbe").arg("-h").output().is_err() {
+            return Err(String::from(
+                "Building QBE IL needs 'qbe', which is not available",
+            ));
+        }
+        let toolchain = QbeToolchain {
+            qbe: String::from("qbe"),
+            assembler: binutil("as", target)?,
+            linker: Toolchain::detect(bcompiler, target)?,
+        };
+        verbose!(
+            "Compiling QBE IL with 'qbe' and '{}', linking with '{}'",
+            toolchain.assembler,
+            toolchain.linker.program
+        );
+        Ok(toolchain)
+    }
+
+    /// Build the commands turning `ssa_file` into `binary`, to be run in
+    /// order, the assembly and object file are written next to `ssa_file`
+    ///
+    /// # Parameters
+    /// - `&Path` : The generated QBE IL
+    /// - `&Path` : Executable to create
+    /// - `&[String]` : Extra flags from the command line, for the linker
+    /// - `&[String]` : Libraries to link, without the `-l`
+    /// - `&str` : QBE's name for the target, see [`crate::qbe::qbe_target`]
+    ///
+    /// # Returns
+    /// - `Vec<Command>` : The invocations of qbe, the assembler and the linker
+    pub fn commands(
+        &self,
+        ssa_file: &Path,
+        binary: &Path,
+        cflags: &[String],
+        libs: &[String],
+        qbe_target: &str,
+    ) -> Vec<Command> {
+        let (s_file, object) = (ssa_file.with_extension("s"), ssa_file.with_extension("o"));
+        let mut compile = Command::new(&self.qbe);
+        compile
+            .args(["-t", qbe_target, "-o"])
+            .arg(&s_file)
+            .arg(ssa_file);
+        let mut assemble = Command::new(&self.assembler);
+        assemble.arg(&s_file).arg("-o").arg(&object);
+        vec![
+            compile,
+            assemble,
+            self.linker.command(&object, binary, cflags, libs),
+        ]
+    }
+}
+
+/// Program building the output of `--backend=wasm`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmToolchain {
+    /// `wat2wasm` from wabt or `wasm-tools`
+    pub assembler: String,
+}
+
+impl WasmToolchain {
+    /// Find `wat2wasm` or `wasm-tools`
+    ///
+    /// # Returns
+    /// - `Result<WasmToolchain, String>` : The program, or that neither is available
+    pub fn detect() -> Result<WasmToolchain, String> {
+        let assembler = ["wat2wasm", "wasm-tools"]
+            .into_iter()
+            .find(|program| runs(program))
+            .ok_or_else(|| {
+                String::from(
+                    "Building WebAssembly text needs 'wat2wasm' or 'wasm-tools', neither is available",
+                )
+            })?;
+        verbose!("Assembling WebAssembly with '{}'", assembler);
+        Ok(WasmToolchain {
+            assembler: assembler.to_string(),
+        })
+    }
+
+    /// Build the commands turning `wat_file` into the module `binary`
+    pub fn commands(&self, wat_file: &Path, binary: &Path) -> Vec<Command> {
+        let mut assemble = Command::new(&self.assembler);
+        if name_of(&self.assembler) == "wasm-tools" {
+            assemble.arg("parse");
+        }
+        assemble.arg(wat_file).arg("-o").arg(binary);
+        vec![assemble]
+    }
+}
+
+/// Find a WASI runtime and build the command running `module` with `args`
+///
+/// # Parameters
+/// - `&Path` : The `.wasm` module
+/// - `&[String]` : Arguments for the program
+///
+/// # Returns
+/// - `Result<Command, String>` : The command, or that no runtime is available
+pub fn wasi_runner(module: &Path, args: &[String]) -> Result<Command, String> {
+    if runs("wasmtime") {
+        let mut run = Command::new("wasmtime");
+        run.arg("run").arg(module).args(args);
+        Ok(run)
+    } else if runs("wasm-interp") {
+        let mut run = Command::new("wasm-interp");
+        run.arg("--wasi").arg(module);
+        for arg in args {
+            run.arg(format!("--arg={}", arg));
+        }
+        Ok(run)
+    } else {
+        Err(String::from(
+            "Running WebAssembly needs 'wasmtime' or 'wasm-interp', neither is available",
+        ))
+    }
+}
+
+/// Check whether `program` exists and answers `--version`
+fn runs(program: &str) -> bool {
+    Command::new(program)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Find a binutils program for `target`, cross binutils being prefixed with
+/// the triple they build for
+fn binutil(name: &str, target: &Target) -> Result<String, String> {
+    let program = if target.is_host() {
+        name.to_string()
+    } else {
+        format!("{}-{}", target.gcc_prefix(), name)
+    };
+    if runs(&program) {
+        Ok(program)
+    } else {
+        Err(format!("'{}' is needed to build, but it is not available", program))
+    }
+}
+
+/// File name of a program, without directories
+fn name_of(program: &str) -> &str {
+    Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program)
+}
@@ -0,0 +1,416 @@
+//! WebAssembly text backend
+//!
+//! Generates a WebAssembly module in the text format (`.wat`) for WASI
+//! runtimes. `__wrt__` calls the `fd_write` import and the exit code of `main`
+//! goes to `proc_exit`, called from the exported `_start`. `i32` and pointers
+//! are `i32`, `i64` stays `i64`. Blocks are nested so that jumping forward is a
+//! `br` out of the jumping block's enclosing blocks, functions jumping
+//! backwards run their blocks in a loop dispatching on the next block number.
+//! WebAssembly text has no line tables, [`Inst::Loc`] becomes a comment.
+
+use crate::ir::{BinOp, BlockId, Function, Inst, Module, Terminator, Type, Value};
+use crate::log::Stage;
+use crate::target::Target;
+use crate::trace;
+
+/// Module WASI functions are imported from
+const WASI: &str = "wasi_snapshot_preview1";
+
+/// Memory below this address holds the `fd_write` arguments: the buffer at 0,
+/// its length at 4, and the number of bytes written at 8
+const SCRATCH: usize = 16;
+
+/// Size of a WebAssembly memory page
+const PAGE: usize = 65536;
+
+/// Check whether the WebAssembly backend can generate code for `target`
+pub fn supports(target: &Target) -> bool {
+    target.arch == "wasm32" && target.is_wasi()
+}
+
+/// Generate a WebAssembly text module out of an IR module
+///
+/// # Parameters
+/// - `&Module` : The module returned from `lower()`
+/// - `bool` : Whether to add comments pointing back at the source
+///
+/// # Returns
+/// - `String` : The generated WebAssembly text
+pub fn genwasm(module: &Module, debug_info: bool) -> String {
+    // Constant data is laid out one after the other, past the scratch memory
+    let mut offsets = Vec::new();
+    let mut end = SCRATCH;
+    for data in &module.data {
+        offsets.push(end);
+        end += data.bytes.len() + 1;
+    }
+    let mut gen = Gen {
+        module,
+        debug: debug_info && !module.file.is_empty(),
+        offsets,
+        out: String::new(),
+        depth: 0,
+    };
+    gen.module(end.div_ceil(PAGE));
+    gen.out
+}
+
+/// State of code generation for a module
+struct Gen<'a> {
+    module: &'a Module,
+    debug: bool,
+    /// Address of each piece of constant data
+    offsets: Vec<usize>,
+    out: String,
+    /// Nesting of the instruction being generated, for indentation
+    depth: usize,
+}
+
+impl Gen<'_> {
+    fn module(&mut self, pages: usize) {
+        self.out.push_str("(module\n");
+        let writes = self
+            .module
+            .functions
+            .iter()
+            .flat_map(|function| &function.blocks)
+            .flat_map(|block| &block.insts)
+            .any(|inst| matches!(inst, Inst::Write { .. }));
+        if writes {
+            self.out.push_str(&format!(
+                "  (import \"{}\" \"fd_write\" (func $fd_write (param i32 i32 i32 i32) (result i32)))\n",
+                WASI
+            ));
+        }
+        let main = self.module.function("main");
+        let exits = main.is_some_and(|main| main.ret.is_some());
+        if exits {
+            self.out.push_str(&format!(
+                "  (import \"{}\" \"proc_exit\" (func $proc_exit (param i32)))\n",
+                WASI
+            ));
+        }
+        // WASI runtimes look for the memory under this name
+        self.out
+            .push_str(&format!("  (memory (export \"memory\") {})\n", pages));
+        for (data, offset) in self.module.data.iter().zip(&self.offsets) {
+            self.out.push_str(&format!(
+                "  (data (i32.const {}) \"{}\")\n",
+                offset,
+                string(&data.bytes)
+            ));
+        }
+        for function in &self.module.functions {
+            trace!(Stage::Gen, "Generating WebAssembly for {}", function.name);
+            self.out.push('\n');
+            self.function(function);
+        }
+        if let Some(main) = main {
+            self.out
+                .push_str("\n  (func $_start (export \"_start\")\n    call $main\n");
+            match main.ret {
+                Some(Type::I64) => self.out.push_str("    i32.wrap_i64\n    call $proc_exit\n"),
+                Some(_) => self.out.push_str("    call $proc_exit\n"),
+                None => {}
+            }
+            self.out.push_str("  )\n");
+        }
+        self.out.push_str(")\n");
+    }
+
+    fn function(&mut self, function: &Function) {
+        let mut header = format!("  (func ${}", function.name);
+        for param in &function.params {
+            header.push_str(&format!(
+                " (param $t{} {})",
+                param.0,
+                wasm_type(function.temps[param.0])
+            ));
+        }
+        if let Some(ty) = function.ret {
+            header.push_str(&format!(" (result {})", wasm_type(ty)));
+        }
+        self.out.push_str(&header);
+        self.out.push('\n');
+        for (temp, ty) in function.temps.iter().enumerate() {
+            if !function.params.iter().any(|param| param.0 == temp) {
+                self.out
+                    .push_str(&format!("    (local $t{} {})\n", temp, wasm_type(*ty)));
+            }
+        }
+        // Jumping backwards needs a loop, every block then starts from the
+        // dispatch on `$bb`, which is 0 on entry
+        let looping = function
+            .blocks
+            .iter()
+            .enumerate()
+            .any(|(id, block)| block.term.successors().iter().any(|to| to.0 <= id));
+        let first = if looping {
+            self.out.push_str("    (local $bb i32)\n");
+            0
+        } else {
+            1
+        };
+        self.depth = 2;
+        if looping {
+            self.inst("loop $dispatch");
+            self.depth += 1;
+        }
+        for id in (first..function.blocks.len()).rev() {
+            self.inst(&format!("block {}", label(BlockId(id))));
+            self.depth += 1;
+        }
+        if looping {
+            let labels = (0..function.blocks.len())
+                .map(|id| label(BlockId(id)))
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.inst("local.get $bb");
+            self.inst(&format!("br_table {}", labels));
+        }
+        for (id, block) in function.blocks.iter().enumerate() {
+            if id >= first {
+                self.depth -= 1;
+                self.inst("end");
+            }
+            for inst in &block.insts {
+                self.gen_inst(function, inst);
+            }
+            self.terminator(function, &block.term, BlockId(id));
+        }
+        if looping {
+            self.depth -= 1;
+            self.inst("end");
+        }
+        // Blocks always end in a jump or a return, but a function with a
+        // result has to show it can't fall off its end
+        let returns = !looping
+            && matches!(
+                function.blocks.last().map(|block| &block.term),
+                Some(Terminator::Return(_))
+            );
+        if function.ret.is_some() && !returns {
+            self.inst("unreachable");
+        }
+        self.out.push_str("  )\n");
+    }
+
+    fn inst(&mut self, inst: &str) {
+        self.out
+            .push_str(&format!("{}{}\n", "  ".repeat(self.depth), inst));
+    }
+
+    fn gen_inst(&mut self, function: &Function, inst: &Inst) {
+        match inst {
+            Inst::Loc { line, col } => {
+                if self.debug {
+                    let loc = format!(";; {}:{}:{}", self.module.file, line, col);
+                    self.inst(&loc);
+                }
+            }
+            Inst::Copy { dest, value } => {
+                self.value(function, value, function.temps[dest.0]);
+                self.inst(&format!("local.set $t{}", dest.0));
+            }
+            Inst::Binary { dest, op, lhs, rhs } => {
+                let ty = function.temps[dest.0];
+                // Comparisons are made in the wider of their operand types, like in C
+                let operands = if !op.is_comparison() {
+                    ty
+                } else if [lhs, rhs].iter().all(|v| function.type_of(v) != Type::I64) {
+                    Type::I32
+                } else {
+                    Type::I64
+                };
+                self.value(function, lhs, operands);
+                self.value(function, rhs, operands);
+                self.inst(&format!("{}.{}", wasm_type(operands), instruction(*op)));
+                // Comparisons give an i32
+                if op.is_comparison() {
+                    self.convert(Type::I32, ty);
+                }
+                self.inst(&format!("local.set $t{}", dest.0));
+            }
+            Inst::Call {
+                dest,
+                function: callee,
+                args,
+            } => {
+                // Arguments take the callee's parameter types when it is known
+                let callee_fn = self.module.function(callee);
+                let types: Vec<Type> = match callee_fn {
+                    Some(callee) => callee
+                        .params
+                        .iter()
+                        .map(|param| callee.temps[param.0])
+                        .collect(),
+                    None => args.iter().map(|arg| function.type_of(arg)).collect(),
+                };
+                for (arg, ty) in args.iter().zip(types) {
+                    self.value(function, arg, ty);
+                }
+                self.inst(&format!("call ${}", callee));
+                let ret = callee_fn.and_then(|callee| callee.ret);
+                match (dest, ret) {
+                    (Some(dest), Some(ret)) => {
+                        self.convert(ret, function.temps[dest.0]);
+                        self.inst(&format!("local.set $t{}", dest.0));
+                    }
+                    (None, Some(_)) => self.inst("drop"),
+                    _ => {}
+                }
+            }
+            Inst::Write { fd, data, len } => {
+                // `fd_write` takes a list of buffers, here a list of one
+                self.inst("i32.const 0");
+                self.value(function, data, Type::Ptr);
+                self.inst("i32.store");
+                self.inst("i32.const 0");
+                self.value(function, len, Type::I32);
+                self.inst("i32.store offset=4");
+                self.value(function, fd, Type::I32);
+                self.inst("i32.const 0");
+                self.inst("i32.const 1");
+                self.inst("i32.const 8");
+                self.inst("call $fd_write");
+                self.inst("drop");
+            }
+        }
+    }
+
+    fn terminator(&mut self, function: &Function, term: &Terminator, current: BlockId) {
+        match term {
+            Terminator::Return(value) => {
+                if let (Some(value), Some(ty)) = (value, function.ret) {
+                    self.value(function, value, ty);
+                }
+                self.inst("return");
+            }
+            Terminator::Jump(to) => self.jump(current, *to),
+            Terminator::Branch {
+                cond,
+                then,
+                otherwise,
+            } => {
+                // Conditions are an i32, an i64 has to be compared to 0
+                match function.type_of(cond) {
+                    Type::I64 => {
+                        self.value(function, cond, Type::I64);
+                        self.inst("i64.const 0");
+                        self.inst("i64.ne");
+                    }
+                    _ => self.value(function, cond, Type::I32),
+                }
+                // Jump when the condition holds, or when it doesn't if the
+                // other way is falling through
+                let next = BlockId(current.0 + 1);
+                let (taken, other) = if *then == next {
+                    self.inst("i32.eqz");
+                    (*otherwise, *then)
+                } else {
+                    (*then, *otherwise)
+                };
+                if other == next && taken > current {
+                    self.inst(&format!("br_if {}", label(taken)));
+                    return;
+                }
+                self.inst("if");
+                self.depth += 1;
+                self.jump(current, taken);
+                self.depth -= 1;
+                if other != next {
+                    self.inst("else");
+                    self.depth += 1;
+                    self.jump(current, other);
+                    self.depth -= 1;
+                }
+                self.inst("end");
+            }
+        }
+    }
+
+    /// Continue at block `to` from the end of block `from`
+    fn jump(&mut self, from: BlockId, to: BlockId) {
+        if to.0 == from.0 + 1 {
+            // Falling through to the next block needs no jump
+        } else if to > from {
+            self.inst(&format!("br {}", label(to)));
+        } else {
+            self.inst(&format!("i32.const {}", to.0));
+            self.inst("local.set $bb");
+            self.inst("br $dispatch");
+        }
+    }
+
+    /// Push a value, converted to `ty`
+    fn value(&mut self, function: &Function, value: &Value, ty: Type) {
+        match value {
+            Value::Int(value, _) => match wasm_type(ty) {
+                "i64" => self.inst(&format!("i64.const {}", value)),
+                _ => self.inst(&format!("i32.const {}", *value as i32)),
+            },
+            Value::Temp(temp) => {
+                self.inst(&format!("local.get $t{}", temp.0));
+                self.convert(function.temps[temp.0], ty);
+            }
+            Value::Data(data) => {
+                self.inst(&format!("i32.const {}", self.offsets[data.0]));
+                self.convert(Type::Ptr, ty);
+            }
+        }
+    }
+
+    /// Convert the value on top of the stack from `from` to `to`, integers
+    /// are sign extended, pointers zero extended
+    fn convert(&mut self, from: Type, to: Type) {
+        match (from, wasm_type(to)) {
+            (Type::I32, "i64") => self.inst("i64.extend_i32_s"),
+            (Type::Ptr, "i64") => self.inst("i64.extend_i32_u"),
+            (Type::I64, "i32") => self.inst("i32.wrap_i64"),
+            _ => {}
+        }
+    }
+}
+
+/// WebAssembly type of an IR type, pointers being 32 bit
+fn wasm_type(ty: Type) -> &'static str {
+    match ty {
+        Type::I32 | Type::Ptr => "i32",
+        Type::I64 => "i64",
+    }
+}
+
+/// Label of a block
+fn label(id: BlockId) -> String {
+    format!("$bb{}", id.0)
+}
+
+/// Instruction computing an operator, without the type in front
+fn instruction(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::Div => "div_s",
+        BinOp::Rem => "rem_s",
+        BinOp::Eq => "eq",
+        BinOp::Ne => "ne",
+        BinOp::Lt => "lt_s",
+        BinOp::Le => "le_s",
+        BinOp::Gt => "gt_s",
+        BinOp::Ge => "ge_s",
+    }
+}
+
+/// Contents of a data segment, NUL terminated like the C backend's string
+/// literals: printable characters as is, anything else escaped in hex
+fn string(bytes: &[u8]) -> String {
+    let mut string = String::new();
+    for &byte in bytes.iter().chain(&[0]) {
+        match byte {
+            0x20..=0x7e if byte != b'"' && byte != b'\\' => string.push(char::from(byte)),
+            _ => string.push_str(&format!("\\{:02x}", byte)),
+        }
+    }
+    string
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use common::{available, dir, neitc, SOURCE};
+use neitc::{
+    asm,
+    backend::Backend,
+    emit_asm,
+    ir::{BinOp, Function, Inst, Module, Terminator, Type, Value},
+    target::Target,
+};
+
+/// `main` returns `sum(1, 2, ..., 8) - 30` where `sum` adds its eight
+/// parameters, two of them passed on the stack
+fn calls() -> Module {
+    let params = [Type::I32; 8];
+    let mut sum = Function::new("sum", &params, Some(Type::I32));
+    let mut total = Value::Temp(sum.params[0]);
+    for i in 1..8 {
+        let dest = sum.temp(Type::I32);
+        sum.blocks[0].insts.push(Inst::Binary {
+            dest,
+            op: BinOp::Add,
+            lhs: total,
+            rhs: Value::Temp(sum.params[i]),
+        });
+        total = Value::Temp(dest);
+    }
+    sum.blocks[0].term = Terminator::Return(Some(total));
+
+    let mut main = Function::new("main", &[], Some(Type::I32));
+    let (result, small) = (main.temp(Type::I32), main.temp(Type::I32));
+    main.blocks[0].insts = vec![
+        Inst::Call {
+            dest: Some(result),
+            function: "sum".to_string(),
+            args: (1..=8).map(|i| Value::Int(i, Type::I32)).collect(),
+        },
+        Inst::Binary {
+            dest: small,
+            op: BinOp::Sub,
+            lhs: Value::Temp(result),
+            rhs: Value::Int(30, Type::I32),
+        },
+    ];
+    main.blocks[0].term = Terminator::Return(Some(Value::Temp(small)));
+    Module {
+        functions: vec![main, sum],
+        ..Module::default()
+    }
+}
+
+#[test]
+fn programs_use_system_calls() {
+    let s = common::compile(SOURCE, Backend::Asm, "x86_64-unknown-linux-gnu", true);
+    assert!(s.starts_with("    .file 1 \"hello.nc\"\n"), "{}", s);
+    assert!(s.contains(".Ld0:\n    .asciz \"hi\\012\"\n"), "{}", s);
+    assert!(s.contains("_start:\n    xorl %ebp, %ebp\n    call main\n"));
+    assert!(s.contains(
+        "    .loc 1 2 1\n    \
+         movq $1, %rdi\n    \
+         leaq .Ld0(%rip), %rsi\n    \
+         movq $3, %rdx\n    \
+         movl $1, %eax\n    \
+         syscall\n"
+    ));
+    assert!(!s.contains("call write"));
+}
+
+#[test]
+fn calls_pass_arguments_on_the_stack() {
+    let s = emit_asm(&calls(), &Target::host(), false);
+    // The seventh and eighth arguments, pushed right to left
+    assert!(s.contains("    movq $8, %rax\n    pushq %rax\n    movq $7, %rax\n    pushq %rax\n"));
+    assert!(s.contains("    call sum\n    addq $16, %rsp\n"));
+    assert!(s.contains("    movq 16(%rbp), %rax\n"));
+
+    if !asm::supports(&Target::host()) || !available("as") || !available("ld") {
+        return;
+    }
+    let dir = dir("asm");
+    fs::write(dir.join("calls.s"), &s).unwrap();
+    let status = Command::new("as")
+        .arg(dir.join("calls.s"))
+        .arg("-o")
+        .arg(dir.join("calls.o"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = Command::new("ld")
+        .arg(dir.join("calls.o"))
+        .arg("-o")
+        .arg(dir.join("calls"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // 36 - 30
+    let status = Command::new(dir.join("calls")).status().unwrap();
+    assert_eq!(status.code(), Some(6));
+}
+
+#[test]
+fn built_programs_run_without_a_c_compiler() {
+    let file = dir("asm").join("hello.nc");
+    fs::write(&file, SOURCE).unwrap();
+    if asm::supports(&Target::host()) && available("as") && available("ld") {
+        let out = neitc(
+            &["run", "--backend=asm", "--bcompiler", "no-such-cc"],
+            &file,
+        );
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        assert_eq!(out.stdout, b"hi\n");
+    }
+
+    let out = neitc(
+        &["emit", "--stage=asm", "--target", "x86_64-pc-windows-gnu"],
+        &file,
+    );
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr)
+        .contains("The asm backend can't generate code for x86_64-pc-windows-gnu"));
+}
@@ -0,0 +1,106 @@
+use std::{env, fs, process::Command};
+
+use neitc::{
+    cdiag::{parse_line, translate, Message},
+    compile_str,
+    diag::{Severity, SourceFile, Span},
+    Options,
+};
+
+const SOURCE: &str = "cimport cstd\n\n  __wrt__(stdout, \"hi\", 2)  \n";
+
+fn file() -> SourceFile {
+    SourceFile::new("t.nc", SOURCE)
+}
+
+#[test]
+fn compiler_lines_are_parsed() {
+    assert_eq!(
+        parse_line("dir/a:b.nc:4:12: error: 'bar' undeclared (first use in this function)"),
+        Some(Message {
+            file: "dir/a:b.nc".to_string(),
+            line: Some(4),
+            col: Some(12),
+            severity: Severity::Error,
+            text: "'bar' undeclared (first use in this function)".to_string(),
+        })
+    );
+    let fatal = parse_line("t.c:1:10: fatal error: nope.h: No such file or directory").unwrap();
+    assert_eq!(
+        (fatal.severity, fatal.text.as_str()),
+        (Severity::Error, "nope.h: No such file or directory")
+    );
+    let summary = parse_line("collect2: error: ld returned 1 exit status").unwrap();
+    assert_eq!((summary.file.as_str(), summary.line), ("collect2", None));
+    assert_eq!(parse_line("t.nc: In function 'main':"), None);
+    assert_eq!(parse_line("    3 |     foo(1);"), None);
+}
+
+#[test]
+fn errors_point_back_into_the_source() {
+    // clang style output, code excerpts included
+    let stderr = "t.nc:3:5: error: use of undeclared identifier 'write'\n    \
+                  3 |     write(1, \"hi\", 2);\n      \
+                  |     ^\n\
+                  t.nc:3:5: note: did you mean 'fwrite'?\n\
+                  1 error generated.\n";
+    let diags = translate(stderr, &file(), "clang");
+    assert_eq!(diags.len(), 1);
+    let diag = &diags[0];
+    assert!(diag.is_error());
+    assert_eq!(diag.message, "use of undeclared identifier 'write'");
+    // The whole line, without its surrounding blanks
+    assert_eq!(diag.primary_span(), Some(Span::new(16, 40)));
+    assert!(diag.notes[0].contains("backend error"));
+    assert_eq!(diag.notes[1], "did you mean 'fwrite'?");
+    assert!(diag.help[0].contains("neitc bug"));
+}
+
+#[test]
+fn errors_in_the_generated_c_keep_their_location() {
+    let diags = translate("t.c:12:3: error: expected ';'\n", &file(), "gcc");
+    assert_eq!(diags[0].primary_span(), None);
+    assert!(diags[0].notes.contains(&"at t.c:12:3".to_string()));
+    assert!(diags[0].help[0].contains("neitc bug"));
+}
+
+#[test]
+fn linker_errors_are_shown_as_is() {
+    let stderr = "/usr/bin/ld: cannot find -lnope: No such file or directory\n\
+                  collect2: error: ld returned 1 exit status\n";
+    let diags = translate(stderr, &file(), "gcc");
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].message, "Compilation with gcc failed");
+    assert!(diags[0].notes[0].contains("cannot find -lnope"));
+    assert!(diags[0].help.is_empty());
+}
+
+#[test]
+fn invalid_c_is_reported_against_the_source() {
+    if Command::new("gcc").arg("--version").output().is_err() {
+        return;
+    }
+    let options = Options {
+        file_name: "t.nc".to_string(),
+        line_directives: true,
+        ..Options::default()
+    };
+    let c = compile_str(SOURCE, options).unwrap().code;
+    let dir = env::temp_dir().join(format!("neitc-backend-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let c_file = dir.join("broken.c");
+    // Break the generated write call
+    fs::write(&c_file, c.replace("write(1,", "write(1 +,")).unwrap();
+    let out = Command::new("gcc")
+        .arg("-c")
+        .arg(&c_file)
+        .arg("-o")
+        .arg(dir.join("broken.o"))
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+
+    let diags = translate(&String::from_utf8_lossy(&out.stderr), &file(), "gcc");
+    assert!(diags[0].is_error());
+    assert_eq!(diags[0].primary_span(), Some(Span::new(16, 40)));
+}
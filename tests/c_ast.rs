@@ -0,0 +1,130 @@
+use neitc::c_ast::{BinOp, CType, Expr, Item, Signature, Stmt, UnOp, Unit};
+
+fn var(name: &str) -> Expr {
+    Expr::ident(name)
+}
+
+#[test]
+fn parentheses_follow_precedence() {
+    let sum = Expr::binary(BinOp::Add, var("a"), var("b"));
+    assert_eq!(
+        Expr::binary(BinOp::Mul, sum.clone(), var("c")).to_string(),
+        "(a + b) * c"
+    );
+    assert_eq!(
+        Expr::binary(BinOp::Add, sum.clone(), var("c")).to_string(),
+        "a + b + c"
+    );
+    assert_eq!(
+        Expr::binary(BinOp::Sub, var("c"), sum).to_string(),
+        "c - (a + b)"
+    );
+    let product = Expr::binary(BinOp::Mul, var("a"), var("b"));
+    assert_eq!(
+        Expr::binary(BinOp::Add, product, var("c")).to_string(),
+        "a * b + c"
+    );
+    assert_eq!(
+        Expr::binary(BinOp::Sub, var("a"), Expr::Int(-1)).to_string(),
+        "a - -1"
+    );
+    let neg = Expr::Unary(UnOp::Neg, Box::new(Expr::Int(-1)));
+    assert_eq!(neg.to_string(), "- -1");
+    let cast = Expr::Cast(
+        CType::LongLong,
+        Box::new(Expr::binary(BinOp::Add, var("a"), Expr::Int(1))),
+    );
+    assert_eq!(cast.to_string(), "(long long)(a + 1)");
+    let nested = Expr::assign(var("x"), Expr::assign(var("y"), Expr::Int(i64::MIN)));
+    assert_eq!(nested.to_string(), "x = y = (-9223372036854775807LL - 1)");
+    assert_eq!(
+        Expr::Str(b"a\"\\\n\x01??".to_vec()).to_string(),
+        "\"a\\\"\\\\\\n\\001?\\?\""
+    );
+}
+
+#[test]
+fn declarators_nest() {
+    let argv = CType::Array(Box::new(CType::const_char_ptr()));
+    assert_eq!(argv.declare("argv"), "const char *argv[]");
+    let fixed = CType::Const(Box::new(CType::Ptr(Box::new(CType::Char))));
+    assert_eq!(fixed.declare("p"), "char *const p");
+    let to_array = CType::Ptr(Box::new(CType::Array(Box::new(CType::Int))));
+    assert_eq!(to_array.declare("p"), "int (*p)[]");
+    assert_eq!(CType::const_char_ptr().declare(""), "const char *");
+}
+
+#[test]
+fn statements_are_indented() {
+    let write = |text: &str| Stmt::Expr(Expr::Call("puts".into(), vec![Expr::Str(text.into())]));
+    let body = vec![
+        Stmt::Decl {
+            ty: CType::Int,
+            name: "i".into(),
+            init: Some(Expr::Int(0)),
+        },
+        Stmt::While {
+            cond: Expr::binary(BinOp::Lt, var("i"), Expr::Int(3)),
+            body: vec![
+                Stmt::If {
+                    cond: Expr::binary(BinOp::Eq, var("i"), Expr::Int(0)),
+                    then: vec![write("zero")],
+                    otherwise: vec![Stmt::If {
+                        cond: Expr::binary(BinOp::Eq, var("i"), Expr::Int(1)),
+                        then: vec![write("one")],
+                        otherwise: vec![Stmt::Block(vec![write("more")])],
+                    }],
+                },
+                Stmt::Expr(Expr::assign(
+                    var("i"),
+                    Expr::binary(BinOp::Add, var("i"), Expr::Int(1)),
+                )),
+            ],
+        },
+        Stmt::Goto("done".into()),
+        Stmt::Label("done".into()),
+    ];
+    let signature = Signature {
+        ret: CType::Void,
+        name: "count".into(),
+        params: vec![],
+    };
+    let unit = Unit {
+        includes: vec!["<stdio.h>".into()],
+        items: vec![
+            Item::Prototype(signature.clone()),
+            Item::Prototype(Signature {
+                ret: CType::Int,
+                name: "other".into(),
+                params: vec![(CType::Int, "x".into())],
+            }),
+            Item::Function(signature, body),
+        ],
+    };
+    assert_eq!(
+        unit.to_string(),
+        "#include <stdio.h>\n\
+         \n\
+         void count(void);\n\
+         int other(int x);\n\
+         \n\
+         void count(void) {\n    \
+             int i = 0;\n    \
+             while (i < 3) {\n        \
+                 if (i == 0) {\n            \
+                     puts(\"zero\");\n        \
+                 } else if (i == 1) {\n            \
+                     puts(\"one\");\n        \
+                 } else {\n            \
+                     {\n                \
+                         puts(\"more\");\n            \
+                     }\n        \
+                 }\n        \
+                 i = i + 1;\n    \
+             }\n    \
+             goto done;\n\
+         done:\n    \
+             ;\n\
+         }\n"
+    );
+}
@@ -0,0 +1,208 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+use neitc::{target::Target, toolchain::Toolchain};
+
+fn neitc() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_neitc"))
+}
+
+/// Write `code` to a fresh source file named `name`
+fn source(name: &str, code: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("neitc-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, code).unwrap();
+    path
+}
+
+#[test]
+fn check_reports_errors_through_exit_code() {
+    let good = source("good.nc", "cimport cstd\n__wrt__(stdout,\"hi\",2)");
+    let out = neitc().arg("check").arg(&good).output().unwrap();
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty() && out.stderr.is_empty());
+
+    let bad = source("bad.nc", "__wrt__(stdout,\"hi\",2)");
+    let out = neitc().arg("check").arg(&bad).output().unwrap();
+    assert!(!out.status.success());
+}
+
+#[test]
+fn emit_prints_the_requested_stage() {
+    let file = source("emit.nc", "cimport cstd\n__wrt__(stdout,\"hi\",2)");
+    let out = neitc()
+        .args(["emit", "--stage=ast"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.starts_with("Cimport(\"cstd\""));
+
+    let out = neitc()
+        .args(["emit", "--stage=c"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&out.stdout).contains("int main("));
+
+    let out = neitc().arg("emit").arg(&file).output().unwrap();
+    assert!(!out.status.success());
+}
+
+#[test]
+fn build_names_binary_after_input_and_keeps_c_only_on_request() {
+    let file = source("named.nc", "cimport cstd\n__wrt__(stdout,\"hi\",2)");
+    let dir = file.parent().unwrap();
+    let Ok(toolchain) = Toolchain::detect(None, &Target::host()) else {
+        eprintln!("skipping: no C compiler installed");
+        return;
+    };
+    let cc = [toolchain.program.as_str()]
+        .into_iter()
+        .chain(toolchain.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let out = neitc()
+        .current_dir(dir)
+        .args([
+            "build",
+            "named.nc",
+            "-bc",
+            &cc,
+            "-O2",
+            "--emit-c",
+            "c/named.c",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(dir
+        .join("named")
+        .with_extension(env::consts::EXE_EXTENSION)
+        .exists());
+    assert!(dir.join("c/named.c").exists());
+    assert!(!dir.join("output.c").exists());
+}
+
+#[test]
+fn libraries_are_linked_after_the_code() {
+    if Toolchain::detect(None, &Target::host()).is_err() {
+        eprintln!("skipping: no C compiler installed");
+        return;
+    }
+    let file = source("libs.nc", "cimport cstd\n__wrt__(stdout,\"hi\",2)");
+    let out = neitc()
+        .args(["build", "-v", "-lm", "-l", "m", "-o"])
+        .arg(file.with_extension(""))
+        .arg(&file)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{}", stderr);
+    let command = stderr
+        .lines()
+        .find(|line| line.starts_with("Running") && line.contains("libs.c"))
+        .unwrap();
+    let code = command.find("libs.c").unwrap();
+    assert_eq!(command.matches("\"-lm\"").count(), 2, "{}", command);
+    assert!(command.find("\"-lm\"").unwrap() > code, "{}", command);
+}
+
+#[test]
+fn quiet_hides_where_the_code_was_kept() {
+    if Toolchain::detect(None, &Target::host()).is_err() {
+        eprintln!("skipping: no C compiler installed");
+        return;
+    }
+    let file = source("kept.nc", "cimport cstd\n__wrt__(stdout,\"hi\",2)");
+    let build = |flags: &[&str]| {
+        let out = neitc()
+            .args(["build", "--keep-c", "-o"])
+            .arg(file.with_extension(""))
+            .args(flags)
+            .arg(&file)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8_lossy(&out.stderr).into_owned()
+    };
+    assert!(build(&[]).contains("Kept generated code in '"));
+    assert_eq!(build(&["-q"]), "");
+}
+
+/// `false` runs as a C compiler but fails every build
+#[cfg(unix)]
+#[test]
+fn failed_builds_leave_no_temporary_files() {
+    let file = source("fails.nc", "cimport cstd\n__wrt__(stdout,\"hi\",2)");
+    let tmp = file.with_file_name("tmp");
+    fs::create_dir_all(&tmp).unwrap();
+    for command in ["build", "run"] {
+        let out = neitc()
+            .env("TMPDIR", &tmp)
+            .args([command, "--bcompiler", "false", "-o"])
+            .arg(file.with_extension(""))
+            .arg(&file)
+            .output()
+            .unwrap();
+        assert!(!out.status.success());
+        assert_eq!(fs::read_dir(&tmp).unwrap().count(), 0, "{}", command);
+    }
+}
+
+/// The program a stand-in C compiler "builds" echoes its arguments and exits
+/// with 3, or kills itself when its first argument is `die`
+#[cfg(unix)]
+#[test]
+fn run_forwards_arguments_and_exit_codes() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = source("args.nc", "cimport cstd\n__wrt__(stdout,\"hi\",2)");
+    let cc = file.with_file_name("fake-cc");
+    fs::write(
+        &cc,
+        "#!/bin/sh\n\
+         [ \"$1\" = --version ] && exit 0\n\
+         while [ $# -gt 0 ]; do [ \"$1\" = -o ] && out=$2; shift; done\n\
+         printf '#!/bin/sh\\necho \"$@\"\\n[ \"$1\" = die ] && kill -9 $$\\nexit 3\\n' > \"$out\"\n\
+         chmod +x \"$out\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&cc, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let run = |args: &[&str]| {
+        neitc()
+            .arg("run")
+            .arg("--bcompiler")
+            .arg(&cc)
+            .arg(&file)
+            .arg("--")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let out = run(&["a", "b c"]);
+    assert_eq!(
+        out.status.code(),
+        Some(3),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(out.stdout, b"a b c\n");
+    // SIGKILL is 9
+    assert_eq!(run(&["die"]).status.code(), Some(137));
+}
+
+#[test]
+fn explain_prints_the_long_form() {
+    let out = neitc().args(["--explain", "N0008"]).output().unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).starts_with("N0008: Missing import of cstd\n"));
+
+    let out = neitc().args(["--explain", "N9999"]).output().unwrap();
+    assert!(!out.status.success());
+}
@@ -0,0 +1,122 @@
+//! Helpers and IR fixtures shared by the backend tests
+
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use neitc::{
+    backend::Backend,
+    compile_str,
+    ir::{BinOp, Function, Inst, Module, Terminator, Type, Value},
+    target::Target,
+    Options,
+};
+
+pub const SOURCE: &str = "cimport cstd\n__wrt__(stdout, \"hi\\n\", 3)\n";
+
+/// Compile `source` as `hello.nc` with a backend, returning the generated code
+pub fn compile(source: &str, backend: Backend, triple: &str, line_directives: bool) -> String {
+    let options = Options {
+        file_name: "hello.nc".to_string(),
+        backend,
+        line_directives,
+        target: Target::parse(triple).unwrap(),
+        ..Options::default()
+    };
+    compile_with(source, options)
+}
+
+/// Compile `source` with `options`, returning the generated code
+pub fn compile_with(source: &str, options: Options) -> String {
+    compile_str(source, options).unwrap().code
+}
+
+/// Whether `program` is installed
+pub fn available(program: &str) -> bool {
+    Command::new(program).arg("--version").output().is_ok()
+}
+
+/// A scratch directory for the test process, `name` telling tests apart
+pub fn dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("neitc-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Run `neitc` with `args` on `file`
+pub fn neitc(args: &[&str], file: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_neitc"))
+        .args(args)
+        .arg(file)
+        .output()
+        .unwrap()
+}
+
+/// `main` adds 0 to 4 up through `add(a, b)` and returns the sum, 10
+pub fn looping() -> Module {
+    let mut add = Function::new("add", &[Type::I32, Type::I32], Some(Type::I32));
+    let sum = add.temp(Type::I32);
+    add.blocks[0].insts = vec![Inst::Binary {
+        dest: sum,
+        op: BinOp::Add,
+        lhs: Value::Temp(add.params[0]),
+        rhs: Value::Temp(add.params[1]),
+    }];
+    add.blocks[0].term = Terminator::Return(Some(Value::Temp(sum)));
+
+    let mut main = Function::new("main", &[], Some(Type::I32));
+    let (i, total, more) = (
+        main.temp(Type::I32),
+        main.temp(Type::I32),
+        main.temp(Type::I32),
+    );
+    let head = main.block();
+    let body = main.block();
+    let exit = main.block();
+    main.blocks[0].insts = vec![
+        Inst::Copy {
+            dest: i,
+            value: Value::Int(0, Type::I32),
+        },
+        Inst::Copy {
+            dest: total,
+            value: Value::Int(0, Type::I32),
+        },
+    ];
+    main.blocks[0].term = Terminator::Jump(head);
+    // `i` is compared with a long, so backends have to widen it
+    main.blocks[head.0].insts = vec![Inst::Binary {
+        dest: more,
+        op: BinOp::Lt,
+        lhs: Value::Temp(i),
+        rhs: Value::Int(5, Type::I64),
+    }];
+    main.blocks[head.0].term = Terminator::Branch {
+        cond: Value::Temp(more),
+        then: body,
+        otherwise: exit,
+    };
+    main.blocks[body.0].insts = vec![
+        Inst::Call {
+            dest: Some(total),
+            function: "add".to_string(),
+            args: vec![Value::Temp(total), Value::Temp(i)],
+        },
+        Inst::Binary {
+            dest: i,
+            op: BinOp::Add,
+            lhs: Value::Temp(i),
+            rhs: Value::Int(1, Type::I32),
+        },
+    ];
+    main.blocks[body.0].term = Terminator::Jump(head);
+    main.blocks[exit.0].term = Terminator::Return(Some(Value::Temp(total)));
+    Module {
+        functions: vec![main, add],
+        ..Module::default()
+    }
+}
@@ -0,0 +1,64 @@
+use neitc::{check, compile_str, lex, parse, parse1::AST, Options};
+
+#[test]
+fn compiles_hello_world() {
+    let out = compile_str(
+        "cimport cstd\n__wrt__(stdout,\"hi\\n\",3)",
+        Options::default(),
+    )
+    .unwrap();
+    assert!(matches!(&out.ast[0], AST::Cimport(lib, _) if lib == "cstd"));
+    assert!(out.code.contains("#include <unistd.h>"));
+    assert!(out.code.contains("write(1, \"hi\\n\", 3);"));
+}
+
+#[test]
+fn stages_report_errors_instead_of_exiting() {
+    let source = "cimport cstdd";
+    let err = parse(&lex(source).unwrap(), source).unwrap_err();
+    assert_eq!(err.diagnostics[0].message, "Invalid library 'cstdd'");
+
+    let source = "__wrt__(stdout,\"hi\",2)";
+    let ast = parse(&lex(source).unwrap(), source).unwrap();
+    let err = check(&ast).unwrap_err();
+    assert_eq!(err.diagnostics[0].message, "No import of cstd found");
+
+    let source = "__wrt__(stdout,\"hi,2)";
+    let err = lex(source).unwrap_err();
+    assert_eq!(err.diagnostics[0].message, "Unterminated string");
+}
+
+#[test]
+fn negative_sizes_are_rejected() {
+    let err = compile_str(
+        "cimport cstd\n__wrt__(stdout,\"hi\\n\",-1)",
+        Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.diagnostics[0].message,
+        "Expected a size of 0 or more but found '-1'"
+    );
+    assert!(compile_str("cimport cstd\n__wrt__(stdout,\"hi\",0)", Options::default()).is_ok());
+}
+
+#[test]
+fn diagnostics_carry_the_file_name() {
+    let options = Options {
+        file_name: "bad.nc".to_string(),
+        ..Options::default()
+    };
+    let err = compile_str("cimport nope", options).unwrap_err();
+    assert_eq!(err.file.name, "bad.nc");
+}
+
+#[test]
+fn cimport_can_request_link_libraries() {
+    let out = compile_str(
+        "cimport cstd, link:m\n__wrt__(stdout,\"hi\",2)",
+        Options::default(),
+    )
+    .unwrap();
+    assert_eq!(out.link_libs, vec!["m".to_string()]);
+    assert!(compile_str("cimport link:-rf", Options::default()).is_err());
+}
@@ -0,0 +1,70 @@
+use neitc::diag::{Diagnostic, SourceFile, Span};
+
+fn render(diag: &Diagnostic, file: &SourceFile) -> String {
+    colored::control::set_override(false);
+    diag.render(Some(file))
+}
+
+#[test]
+fn points_at_the_span_with_carets() {
+    let file = SourceFile::new("hello.nc", "cimport cstdd\n");
+    let diag = Diagnostic::error("Invalid library 'cstdd'")
+        .with_code("N0002")
+        .with_label(Span::new(8, 13), "not a known library")
+        .with_note("libraries are imported by name")
+        .with_help("did you mean 'cstd'?");
+    assert_eq!(
+        render(&diag, &file),
+        "error[N0002]: Invalid library 'cstdd'\n \
+         --> hello.nc:1:9\n  \
+         |\n\
+         1 | cimport cstdd\n  \
+         |         ^^^^^ not a known library\n  \
+         |\n  \
+         = note: libraries are imported by name\n  \
+         = help: did you mean 'cstd'?\n"
+    );
+}
+
+#[test]
+fn renders_secondary_labels_and_gaps() {
+    let source = "cimport cstd\n\n\n\n\n\n\n\n\n__wrt__(stdout,\"hi\",3)\n";
+    let file = SourceFile::new("a.nc", source);
+    let size = source.find('3').unwrap();
+    let text = source.find("\"hi\"").unwrap();
+    let diag = Diagnostic::warning("Size doesn't match the text")
+        .with_label(Span::new(size, size + 1), "3 bytes")
+        .with_secondary(Span::new(text, text + 4), "2 bytes")
+        .with_secondary(Span::new(0, 7), "imported here");
+    let out = render(&diag, &file);
+    assert!(out.starts_with("warning: Size doesn't match the text\n  --> a.nc:10:21\n"));
+    assert!(out.contains(" 1 | cimport cstd\n   | ------- imported here\n...\n10 |"));
+    assert!(out.contains("   |                     ^ 3 bytes\n"));
+    assert!(out.contains("   |                ---- 2 bytes\n"));
+}
+
+#[test]
+fn underlines_every_line_of_a_multi_line_span() {
+    let file = SourceFile::new("m.nc", "cimport cstd\n  __wrt__(stdout,\n  \"hi\",2)\n");
+    let diag = Diagnostic::error("Broken call").with_label(Span::new(15, 40), "in this call");
+    let out = render(&diag, &file);
+    assert!(out.contains("2 |   __wrt__(stdout,\n  |   ^^^^^^^^^^^^^^^\n"));
+    assert!(out.contains("3 |   \"hi\",2)\n  |   ^^^^^^^ in this call\n"));
+}
+
+#[test]
+fn handles_spans_past_the_end_of_a_line() {
+    let file = SourceFile::new("e.nc", "cimport");
+    let diag = Diagnostic::error("Expected a library").with_label(Span::new(7, 7), "here");
+    assert!(render(&diag, &file).contains("1 | cimport\n  |        ^ here\n"));
+}
+
+#[test]
+fn diagnostics_without_spans_have_no_snippet() {
+    let diag = Diagnostic::error("No C compiler found").with_help("install clang or gcc");
+    colored::control::set_override(false);
+    assert_eq!(
+        diag.render(None),
+        "error: No C compiler found\n  = help: install clang or gcc\n"
+    );
+}
@@ -0,0 +1,32 @@
+use neitc::{
+    compile_str,
+    explain::{explain, EXPLANATIONS},
+    Options,
+};
+
+#[test]
+fn examples_fail_and_compile_as_described() {
+    for explanation in EXPLANATIONS {
+        let err = compile_str(explanation.incorrect, Options::default()).unwrap_err();
+        assert!(
+            err.diagnostics
+                .iter()
+                .any(|diag| diag.code.as_deref() == Some(explanation.code)),
+            "{} example reported {:?}",
+            explanation.code,
+            err.diagnostics
+        );
+        if let Err(err) = compile_str(explanation.correct, Options::default()) {
+            panic!("corrected {} example fails:\n{}", explanation.code, err);
+        }
+    }
+}
+
+#[test]
+fn codes_are_unique_and_in_order() {
+    for (i, explanation) in EXPLANATIONS.iter().enumerate() {
+        assert_eq!(explanation.code, format!("N{:04}", i + 1));
+    }
+    assert_eq!(explain("n0003").map(|e| e.code), Some("N0003"));
+    assert!(explain("N9999").is_none());
+}
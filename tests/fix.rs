@@ -0,0 +1,85 @@
+use std::{env, fs, process::Command};
+
+use neitc::{
+    diag::{Applicability, Diagnostic, Span},
+    diff::unified_diff,
+    fix::{apply, fix},
+    Options,
+};
+
+#[test]
+fn every_fix_is_applied_in_passes() {
+    let source = "__wrte__(stdout, \"hi\\n\", 5)\n__wrt__(stderr, \"x\", 1)\n";
+    let fixed = fix(source, &Options::default());
+    assert_eq!(
+        fixed.source,
+        "cimport cstd\n__wrt__(stdout, \"hi\\n\", 3)\n__wrt__(stderr, \"x\", 1)\n"
+    );
+    assert_eq!(fixed.applied, 3);
+}
+
+#[test]
+fn only_machine_applicable_fixes_are_applied() {
+    let diag = |start, end, replacement, applicability| {
+        Diagnostic::error("").with_suggestion(Span::new(start, end), replacement, "", applicability)
+    };
+    let diags = [
+        diag(0, 3, "one", Applicability::MachineApplicable),
+        diag(2, 5, "overlapping", Applicability::MachineApplicable),
+        diag(6, 9, "maybe", Applicability::MaybeIncorrect),
+        diag(12, 12, "new ", Applicability::MachineApplicable),
+    ];
+    assert_eq!(
+        apply("111 222 333 444", &diags),
+        ("one 222 333 new 444".to_string(), 2)
+    );
+}
+
+#[test]
+fn correct_programs_are_left_alone() {
+    let source = "cimport cstd\n__wrt__(stdout, \"hi\", 2)\n";
+    let fixed = fix(source, &Options::default());
+    assert_eq!((fixed.source.as_str(), fixed.applied), (source, 0));
+    assert_eq!(unified_diff("hello.nc", source, source), "");
+}
+
+#[test]
+fn diffs_are_unified() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl";
+    let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+    assert_eq!(
+        unified_diff("x.nc", old, new),
+        "--- x.nc\n+++ x.nc\n\
+         @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+         @@ -9,4 +9,5 @@\n i\n j\n k\n-l\n\\ No newline at end of file\n+l\n+m\n"
+    );
+}
+
+#[test]
+fn fix_command_rewrites_the_file() {
+    let dir = env::temp_dir().join(format!("neitc-fix-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("fix.nc");
+    fs::write(&file, "__wrt__(stdout, \"hi\", 5)\n").unwrap();
+    let neitc = || Command::new(env!("CARGO_BIN_EXE_neitc"));
+
+    let out = neitc()
+        .args(["fix", "--dry-run"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let diff = String::from_utf8_lossy(&out.stdout);
+    assert!(diff.contains("+cimport cstd\n") && diff.contains("+__wrt__(stdout, \"hi\", 2)\n"));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "__wrt__(stdout, \"hi\", 5)\n"
+    );
+
+    let out = neitc().arg("fix").arg(&file).output().unwrap();
+    assert!(out.status.success() && out.stderr.is_empty());
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "cimport cstd\n__wrt__(stdout, \"hi\", 2)\n"
+    );
+}
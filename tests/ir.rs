@@ -0,0 +1,66 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use common::{dir, looping};
+use neitc::{compile_str, emit_c, ir::string_bytes, target::Target, toolchain::Toolchain, Options};
+
+#[test]
+fn writes_lower_to_main() {
+    let source = "cimport cstd\n__wrt__(stdout, \"hi\\n\", 3)\n__wrt__(stderr, \"hi\\n\", 3)\n";
+    let out = compile_str(source, Options::default()).unwrap();
+    assert_eq!(
+        out.ir.to_string(),
+        "data @d0 = \"hi\\n\"\n\
+         \n\
+         fn main() -> i32 {\n\
+         bb0:\n    \
+         loc 2:1\n    \
+         write i32 1, @d0, i64 3\n    \
+         loc 3:1\n    \
+         write i32 2, @d0, i64 3\n    \
+         ret i32 0\n\
+         }\n"
+    );
+}
+
+#[test]
+fn escapes_are_resolved() {
+    assert_eq!(string_bytes("\"a\\n\\t\\\\\""), b"a\n\t\\");
+    assert_eq!(string_bytes("'\\x41\\101\\0'"), b"AA\0");
+    assert_eq!(string_bytes("'it\"s'"), b"it\"s");
+}
+
+#[test]
+fn control_flow_becomes_c() {
+    let target = Target::host();
+    let c = emit_c(&looping(), &target, true);
+    assert!(c.contains("int add(int t0, int t1);\n"), "{}", c);
+    assert!(
+        c.contains("bb1:\n    t2 = t0 < 5;\n    if (!t2) {\n        goto bb3;\n    }\n"),
+        "{}",
+        c
+    );
+
+    let Ok(toolchain) = Toolchain::detect(None, &target) else {
+        eprintln!("skipping: no C compiler installed");
+        return;
+    };
+    let dir = dir("ir");
+    let c_file = dir.join("loop.c");
+    let binary = dir.join("loop");
+    fs::write(&c_file, c).unwrap();
+    let out = toolchain
+        .command(&c_file, &binary, &[], &[])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let run = Command::new(&binary).output().unwrap();
+    // 0 + 1 + 2 + 3 + 4
+    assert_eq!(run.status.code(), Some(10));
+    fs::remove_dir_all(&dir).ok();
+}
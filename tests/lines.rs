@@ -0,0 +1,79 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use neitc::{compile_str, target::Target, toolchain::Toolchain, Options};
+
+const SOURCE: &str =
+    "cimport cstd\n\n__wrt__(stdout, \"hi\\n\", 3)\n__wrt__(stderr, \"oops\\n\", 5)\n";
+
+fn compile(line_directives: bool) -> String {
+    let options = Options {
+        file_name: "dir/hello \"world\".nc".to_string(),
+        line_directives,
+        ..Options::default()
+    };
+    common::compile_with(SOURCE, options)
+}
+
+#[test]
+fn statements_point_at_their_source_line() {
+    let c = compile(true);
+    assert!(
+        c.contains("#line 3 \"dir/hello \\\"world\\\".nc\"\n    write(1, \"hi\\n\", 3);\n"),
+        "{}",
+        c
+    );
+    assert!(
+        c.contains("#line 4 \"dir/hello \\\"world\\\".nc\"\n    write(2,"),
+        "{}",
+        c
+    );
+    assert!(!compile(false).contains("#line"));
+    // On by default as on the command line, but without a file name there is
+    // nothing to point at
+    assert!(Options::default().line_directives);
+    assert!(!compile_str(SOURCE, Options::default())
+        .unwrap()
+        .code
+        .contains("#line"));
+}
+
+#[test]
+fn c_compiler_sees_neit_locations() {
+    let target = Target::host();
+    let Ok(toolchain) = Toolchain::detect(None, &target) else {
+        eprintln!("skipping: no C compiler installed");
+        return;
+    };
+    let dir = common::dir("lines");
+    let c_file = dir.join("hello.c");
+    fs::write(&c_file, compile(true)).unwrap();
+    // The preprocessor keeps the mapping as line markers
+    let out = Command::new(&toolchain.program)
+        .args(&toolchain.args)
+        .arg("-E")
+        .arg(&c_file)
+        .output()
+        .unwrap();
+    let preprocessed = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        preprocessed.contains("# 3 \"dir/hello \\\"world\\\".nc\""),
+        "{}",
+        preprocessed
+    );
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn cli_can_turn_them_off() {
+    let dir = common::dir("lines-cli");
+    let file = dir.join("hello.nc");
+    fs::write(&file, SOURCE).unwrap();
+    let emit = |extra: &[&str]| {
+        let out = common::neitc(&[&["emit", "--stage=c"], extra].concat(), &file);
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+    assert!(emit(&[]).contains(&format!("#line 3 \"{}\"", file.display())));
+    assert!(!emit(&["--no-line-directives"]).contains("#line"));
+}
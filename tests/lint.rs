@@ -0,0 +1,153 @@
+use std::{env, fs, process::Command};
+
+use neitc::{
+    analyze,
+    diag::Severity,
+    lint::{literal_len, LintLevel, LintLevels, LINTS, PLANNED},
+    Options,
+};
+
+/// Analyze `source` with the lint levels `levels`
+fn lints(source: &str, levels: LintLevels) -> Result<Vec<(String, Severity)>, Vec<String>> {
+    let options = Options {
+        lints: levels,
+        ..Options::default()
+    };
+    match analyze(source, &options) {
+        Ok(analysis) => Ok(analysis
+            .warnings
+            .diagnostics
+            .into_iter()
+            .map(|diag| (diag.code.unwrap_or_default(), diag.severity))
+            .collect()),
+        Err(diags) => Err(diags
+            .diagnostics
+            .into_iter()
+            .map(|diag| diag.code.unwrap_or_default())
+            .collect()),
+    }
+}
+
+#[test]
+fn size_mismatch_warns_with_a_fix() {
+    let source = "cimport cstd\n__wrt__(stdout, \"hi\\n\", 5)\n";
+    let analysis = analyze(source, &Options::default()).unwrap();
+    let diag = &analysis.warnings.diagnostics[0];
+    assert_eq!(diag.code.as_deref(), Some("wrt_size_mismatch"));
+    assert_eq!(diag.severity, Severity::Warning);
+    let fix = &diag.suggestions[0];
+    assert_eq!(fix.replacement, "3");
+    assert_eq!(&source[fix.span.start..fix.span.end], "5");
+}
+
+#[test]
+fn unused_cimport_warns() {
+    assert_eq!(
+        lints("cimport cstd\n", LintLevels::default()),
+        Ok(vec![("unused_cimport".to_string(), Severity::Warning)])
+    );
+}
+
+#[test]
+fn attributes_change_levels() {
+    let allowed = "cimport cstd\n#[allow(wrt_size_mismatch)]\n__wrt__(stdout, \"hi\", 1)\n";
+    assert_eq!(lints(allowed, LintLevels::default()), Ok(vec![]));
+
+    let denied = "#![deny(wrt_size_mismatch)]\ncimport cstd\n__wrt__(stdout, \"hi\", 1)\n";
+    assert_eq!(
+        lints(denied, LintLevels::default()),
+        Err(vec!["wrt_size_mismatch".to_string()])
+    );
+}
+
+#[test]
+fn warnings_group_sets_every_warning() {
+    let mut levels = LintLevels::default();
+    levels.set("warnings", LintLevel::Deny).unwrap();
+    assert_eq!(
+        lints("cimport cstd\n", levels.clone()),
+        Err(vec!["unused_cimport".to_string()])
+    );
+    // A lint allowed in the source stays allowed
+    assert_eq!(
+        lints("#![allow(unused_cimport)]\ncimport cstd\n", levels),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn unknown_lints_are_reported() {
+    let source = "#![allow(wrt_size_mismtch)]\ncimport cstd\n__wrt__(stdout, \"hi\", 2)\n";
+    let analysis = analyze(source, &Options::default()).unwrap();
+    let diag = &analysis.warnings.diagnostics[0];
+    assert_eq!(diag.code.as_deref(), Some("unknown_lints"));
+    assert_eq!(diag.suggestions[0].replacement, "wrt_size_mismatch");
+
+    let mut levels = LintLevels::default();
+    assert_eq!(
+        levels.set("unused_import", LintLevel::Allow),
+        Err("Unknown lint 'unused_import', did you mean 'unused_cimport'?".to_string())
+    );
+}
+
+#[test]
+fn planned_lints_say_what_they_wait_for() {
+    let mut levels = LintLevels::default();
+    assert_eq!(
+        levels.set("shadowing", LintLevel::Deny),
+        Err("Lint 'shadowing' isn't available yet, Neit has no variables".to_string())
+    );
+    let source = "#![allow(unreachable_code)]\ncimport cstd\n__wrt__(stdout, \"hi\", 2)\n";
+    let analysis = analyze(source, &Options::default()).unwrap();
+    assert_eq!(
+        analysis.warnings.diagnostics[0].notes[0],
+        "it isn't available yet, Neit has no control flow"
+    );
+
+    // --help lists every lint, the planned ones too
+    let out = Command::new(env!("CARGO_BIN_EXE_neitc"))
+        .arg("--help")
+        .output()
+        .unwrap();
+    let help = String::from_utf8_lossy(&out.stdout);
+    for name in LINTS.iter().map(|lint| lint.name) {
+        assert!(help.contains(name), "{}", name);
+    }
+    for (name, _) in PLANNED {
+        assert!(help.contains(name), "{}", name);
+    }
+}
+
+#[test]
+fn command_line_sets_levels() {
+    let dir = env::temp_dir().join(format!("neitc-lint-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("unused.nc");
+    fs::write(&file, "cimport cstd\n").unwrap();
+    let neitc = || Command::new(env!("CARGO_BIN_EXE_neitc"));
+
+    let out = neitc().arg("check").arg(&file).output().unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("unused_cimport"));
+
+    let out = neitc()
+        .args(["check", "-A", "unused_cimport"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(out.status.success() && out.stderr.is_empty());
+
+    let out = neitc()
+        .args(["check", "-Dwarnings"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+}
+
+#[test]
+fn escapes_count_as_one_byte() {
+    assert_eq!(literal_len("\"hi\\n\""), 3);
+    assert_eq!(literal_len("'\\x41\\101\\\\'"), 3);
+    assert_eq!(literal_len("\"\""), 0);
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use common::{available, dir, looping, neitc, SOURCE};
+use neitc::{backend::Backend, emit_llvm, target::Target};
+
+fn compile(line_directives: bool) -> String {
+    common::compile(
+        SOURCE,
+        Backend::Llvm,
+        "x86_64-unknown-linux-gnu",
+        line_directives,
+    )
+}
+
+#[test]
+fn programs_become_llvm_ir() {
+    assert_eq!(
+        compile(false),
+        "; ModuleID = 'hello.nc'\n\
+         source_filename = \"hello.nc\"\n\
+         target triple = \"x86_64-unknown-linux-gnu\"\n\
+         \n\
+         @d0 = private unnamed_addr constant [4 x i8] c\"hi\\0A\\00\", align 1\n\
+         \n\
+         declare i64 @write(i32, i8*, i64)\n\
+         \n\
+         define i32 @main() {\n\
+         entry:\n  \
+         br label %bb0\n\
+         \n\
+         bb0:\n  \
+         call i64 @write(i32 1, i8* getelementptr inbounds ([4 x i8], [4 x i8]* @d0, i64 0, i64 0), i64 3)\n  \
+         ret i32 0\n\
+         }\n"
+    );
+}
+
+#[test]
+fn statements_carry_their_line() {
+    let ll = compile(true);
+    assert!(ll.contains("define i32 @main() !dbg !6 {\n"), "{}", ll);
+    assert!(ll.contains("i64 3), !dbg !8\n"), "{}", ll);
+    assert!(
+        ll.contains("!8 = !DILocation(line: 2, column: 1, scope: !6)\n"),
+        "{}",
+        ll
+    );
+    assert!(ll.contains("!1 = !DIFile(filename: \"hello.nc\", directory: \"\")\n"));
+}
+
+#[test]
+fn functions_loops_and_calls_compile() {
+    let ll = emit_llvm(&looping(), &Target::host(), false);
+    assert!(ll.contains("define internal fastcc i32 @add(i32 %p0, i32 %p1) {\n"));
+    assert!(ll.contains("= call fastcc i32 @add(i32 "));
+    // The comparison is made in the type of its widest operand
+    assert!(ll.contains("%v1 = sext i32 %v0 to i64\n  %v2 = icmp slt i64 %v1, 5\n"));
+    assert!(ll.contains("br i1 %v5, label %bb2, label %bb3\n"), "{}", ll);
+
+    if !available("llc") {
+        return;
+    }
+    let dir = dir("llvm");
+    fs::write(dir.join("loop.ll"), &ll).unwrap();
+    let out = Command::new("llc")
+        .args(["-filetype=obj", "-relocation-model=pic"])
+        .arg(dir.join("loop.ll"))
+        .arg("-o")
+        .arg(dir.join("loop.o"))
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    if !available("cc") {
+        return;
+    }
+    let status = Command::new("cc")
+        .arg(dir.join("loop.o"))
+        .arg("-o")
+        .arg(dir.join("loop"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // 0 + 1 + 2 + 3 + 4
+    let status = Command::new(dir.join("loop")).status().unwrap();
+    assert_eq!(status.code(), Some(10));
+}
+
+#[test]
+fn built_programs_run() {
+    if !available("clang") && !available("llc") {
+        return;
+    }
+    let file = dir("llvm-run").join("hello.nc");
+    fs::write(&file, SOURCE).unwrap();
+    let out = neitc(&["run", "--backend=llvm"], &file);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(out.stdout, b"hi\n");
+
+    let out = neitc(&["build", "--backend=jvm"], &file);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Unknown backend 'jvm'"));
+}
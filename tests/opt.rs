@@ -0,0 +1,213 @@
+use std::process::Command;
+
+use neitc::{
+    compile_str,
+    ir::{BinOp, Function, Inst, Module, Terminator, Type, Value},
+    opt::{fold, optimize, OptLevel},
+    Options,
+};
+
+fn int(value: i64) -> Value {
+    Value::Int(value, Type::I32)
+}
+
+fn write(data: Value) -> Inst {
+    Inst::Write {
+        fd: int(1),
+        data,
+        len: Value::Int(4, Type::I64),
+    }
+}
+
+/// `main` computes `2 * 3 > 5` and writes "yes" or "no" depending on it,
+/// `unused` is never called
+fn branchy() -> Module {
+    let mut module = Module::default();
+    let yes = Value::Data(module.data(b"yes\n".to_vec()));
+    let no = Value::Data(module.data(b"no\n".to_vec()));
+
+    let mut main = Function::new("main", &[], Some(Type::I32));
+    let (two, six, big) = (
+        main.temp(Type::I32),
+        main.temp(Type::I32),
+        main.temp(Type::I32),
+    );
+    let then = main.block();
+    let otherwise = main.block();
+    let done = main.block();
+    main.blocks[0].insts = vec![
+        Inst::Copy {
+            dest: two,
+            value: int(2),
+        },
+        Inst::Binary {
+            dest: six,
+            op: BinOp::Mul,
+            lhs: Value::Temp(two),
+            rhs: int(3),
+        },
+        Inst::Binary {
+            dest: big,
+            op: BinOp::Gt,
+            lhs: Value::Temp(six),
+            rhs: int(5),
+        },
+    ];
+    main.blocks[0].term = Terminator::Branch {
+        cond: Value::Temp(big),
+        then,
+        otherwise,
+    };
+    main.blocks[then.0].insts = vec![write(yes)];
+    main.blocks[then.0].term = Terminator::Jump(done);
+    main.blocks[otherwise.0].insts = vec![write(no)];
+    main.blocks[otherwise.0].term = Terminator::Jump(done);
+    main.blocks[done.0].term = Terminator::Return(Some(Value::Temp(six)));
+
+    let mut unused = Function::new("unused", &[], None);
+    unused.blocks[0].insts = vec![write(no)];
+    module.functions = vec![main, unused];
+    module
+}
+
+#[test]
+fn constant_branches_are_decided() {
+    let mut module = branchy();
+    optimize(&mut module, OptLevel::O2);
+    assert_eq!(
+        module.to_string(),
+        "data @d0 = \"yes\\n\"\n\
+         \n\
+         fn main() -> i32 {\n\
+         bb0:\n    \
+         write i32 1, @d0, i64 4\n    \
+         ret i32 6\n\
+         }\n"
+    );
+}
+
+#[test]
+fn levels_decide_what_runs() {
+    let mut module = branchy();
+    optimize(&mut module, OptLevel::O0);
+    assert_eq!(module, branchy());
+
+    optimize(&mut module, OptLevel::O1);
+    assert_eq!(module.functions.len(), 2);
+    assert_eq!(module.data.len(), 2);
+    assert_eq!(module.functions[0].blocks.len(), 1);
+}
+
+#[test]
+fn loops_keep_their_branches() {
+    // `i` starts at 0 but changes on every round, so it isn't a constant
+    let mut function = Function::new("main", &[], Some(Type::I32));
+    let (i, more) = (function.temp(Type::I32), function.temp(Type::I32));
+    let head = function.block();
+    let body = function.block();
+    let exit = function.block();
+    function.blocks[0].insts = vec![Inst::Copy {
+        dest: i,
+        value: int(0),
+    }];
+    function.blocks[0].term = Terminator::Jump(head);
+    function.blocks[head.0].insts = vec![Inst::Binary {
+        dest: more,
+        op: BinOp::Lt,
+        lhs: Value::Temp(i),
+        rhs: int(3),
+    }];
+    function.blocks[head.0].term = Terminator::Branch {
+        cond: Value::Temp(more),
+        then: body,
+        otherwise: exit,
+    };
+    function.blocks[body.0].insts = vec![Inst::Binary {
+        dest: i,
+        op: BinOp::Add,
+        lhs: Value::Temp(i),
+        rhs: int(1),
+    }];
+    function.blocks[body.0].term = Terminator::Jump(head);
+    function.blocks[exit.0].term = Terminator::Return(Some(Value::Temp(i)));
+    let mut module = Module {
+        functions: vec![function],
+        ..Module::default()
+    };
+
+    optimize(&mut module, OptLevel::O2);
+    let main = &module.functions[0];
+    assert_eq!(main.blocks.len(), 4);
+    assert!(matches!(main.blocks[1].term, Terminator::Branch { .. }));
+}
+
+#[test]
+fn folding_wraps_and_leaves_division_by_zero() {
+    assert_eq!(
+        fold(BinOp::Add, &int(i64::from(i32::MAX)), &int(1), Type::I32),
+        Some(int(i64::from(i32::MIN)))
+    );
+    assert_eq!(
+        fold(BinOp::Div, &int(7), &int(-2), Type::I32),
+        Some(int(-3))
+    );
+    assert_eq!(fold(BinOp::Rem, &int(7), &int(0), Type::I32), None);
+    assert_eq!(fold(BinOp::Le, &int(2), &int(2), Type::I32), Some(int(1)));
+}
+
+#[test]
+fn lowered_programs_are_optimized() {
+    // 5 bytes of "hi" would read past its NUL, lowering checks for it
+    let source = "cimport cstd\n__wrt__(stdout, \"hi\", 5)\n__wrt__(stdout, \"hi\", 2)\n";
+    let out = compile_str(source, Options::default()).unwrap();
+    let main = &out.lowered.functions[0];
+    assert_eq!(main.blocks.len(), 7);
+    assert!(matches!(main.blocks[0].term, Terminator::Branch { .. }));
+    assert_eq!(
+        out.ir.to_string(),
+        "data @d0 = \"hi\"\n\
+         \n\
+         fn main() -> i32 {\n\
+         bb0:\n    \
+         loc 2:1\n    \
+         write i32 1, @d0, i64 3\n    \
+         loc 3:1\n    \
+         write i32 1, @d0, i64 2\n    \
+         ret i32 0\n\
+         }\n"
+    );
+
+    let options = Options {
+        opt_level: OptLevel::O0,
+        ..Options::default()
+    };
+    let out = compile_str(source, options).unwrap();
+    assert_eq!(out.ir, out.lowered);
+}
+
+#[test]
+fn emit_prints_the_ir() {
+    let dir = std::env::temp_dir().join(format!("neitc-opt-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("ir.nc");
+    std::fs::write(&file, "cimport cstd\n__wrt__(stdout, \"hi\", 2)\n").unwrap();
+    let emit = |stage: &str| {
+        let out = Command::new(env!("CARGO_BIN_EXE_neitc"))
+            .args(["emit", stage])
+            .arg(&file)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+    let lowered = emit("--stage=ir-unopt");
+    assert!(lowered.contains("br %t0, bb1, bb2\n"), "{}", lowered);
+    assert!(lowered.contains("write i32 1, @d0, %t1\n"), "{}", lowered);
+    let optimized = emit("--stage=ir");
+    assert!(
+        optimized.contains("write i32 1, @d0, i64 2\n"),
+        "{}",
+        optimized
+    );
+    assert!(!optimized.contains("br "), "{}", optimized);
+}
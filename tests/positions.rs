@@ -0,0 +1,91 @@
+use neitc::{check, diag::SourceFile, lex, parse, Diagnostics};
+
+/// Line and column the first diagnostic of `source` points at
+fn location(source: &str) -> (usize, usize) {
+    let err = lex(source)
+        .and_then(|tokens| parse(&tokens, source))
+        .and_then(|ast| check(&ast))
+        .unwrap_err();
+    at(&err, source)
+}
+
+fn at(err: &Diagnostics, source: &str) -> (usize, usize) {
+    let span = err.diagnostics[0]
+        .primary_span()
+        .expect("diagnostic has no label");
+    SourceFile::new("", source).line_col(span.start)
+}
+
+#[test]
+fn invalid_character() {
+    assert_eq!(location("cimport cstd\n\ncimport cstd(\n"), (3, 13));
+}
+
+#[test]
+fn invalid_library() {
+    assert_eq!(location("cimport cstd\ncimport  cstdd\n"), (2, 10));
+}
+
+#[test]
+fn unexpected_token() {
+    assert_eq!(location("cimport cstd\n\n  hello\n"), (3, 3));
+    assert_eq!(
+        location("cimport cstd\n__wrt__(stdout,\"hi\",2) x\n"),
+        (2, 24)
+    );
+}
+
+#[test]
+fn unterminated_string() {
+    let source = "cimport cstd\n__wrt__(stdout, \"hi, 2)\n";
+    assert_eq!(at(&lex(source).unwrap_err(), source), (2, 17));
+}
+
+#[test]
+fn invalid_function() {
+    assert_eq!(
+        location("cimport cstd\n\n    __wrte__(stdout,\"hi\",2)"),
+        (3, 5)
+    );
+}
+
+#[test]
+fn invalid_argument() {
+    assert_eq!(
+        location("cimport cstd\n__wrt__(stdin, \"hi\", 2)\n"),
+        (2, 9)
+    );
+    assert_eq!(
+        location("cimport cstd\n__wrt__(stdout, \"hi\", two)\n"),
+        (2, 23)
+    );
+}
+
+#[test]
+fn invalid_attribute() {
+    assert_eq!(
+        location("cimport cstd\n #[target_family = \"unix\"]\n"),
+        (2, 2)
+    );
+}
+
+#[test]
+fn dangling_attribute() {
+    assert_eq!(
+        location("cimport cstd\n__wrt__(stdout,\"hi\",2)\n\n  #[target_os = \"linux\"]\n"),
+        (4, 3)
+    );
+}
+
+#[test]
+fn no_cimport() {
+    assert_eq!(location("\n\n__wrt__(stdout,\"hi\",2)\n"), (3, 1));
+}
+
+#[test]
+fn columns_count_characters() {
+    assert_eq!(
+        location("cimport cstd\n__wrt__(stdout, \"héllo\", x)\n"),
+        (2, 26)
+    );
+}
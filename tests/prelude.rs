@@ -0,0 +1,44 @@
+use std::{env, fs, process::Command};
+
+use neitc::prelude::{used_helpers, NC_STRLEN};
+
+#[test]
+fn helpers_are_emitted_on_demand() {
+    assert!(used_helpers("    write(1, \"hi\", 2);\n").is_empty());
+    assert_eq!(
+        used_helpers("    write(1, s, nc_strlen(s));\n"),
+        vec![&NC_STRLEN]
+    );
+    assert!(used_helpers("    my_nc_strlen(s);\n").is_empty());
+}
+
+#[test]
+fn nc_strlen_counts_bytes() {
+    let cc = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+    let dir = env::temp_dir().join(format!("neitc-prelude-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let src = dir.join("strlen.c");
+    let bin = dir.join("strlen");
+
+    let mut code = String::new();
+    for include in NC_STRLEN.includes {
+        code.push_str(&format!("#include {}\n", include));
+    }
+    code.push_str(NC_STRLEN.def);
+    code.push_str(
+        "int main(void) {\n    return !(nc_strlen(\"\") == 0 && nc_strlen(\"hello world\\n\") == 12);\n}\n",
+    );
+    fs::write(&src, code).unwrap();
+
+    let Ok(out) = Command::new(&cc).arg(&src).arg("-o").arg(&bin).output() else {
+        eprintln!("skipping: no C compiler '{}' available", cc);
+        return;
+    };
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(Command::new(&bin).status().unwrap().success());
+    fs::remove_dir_all(&dir).ok();
+}
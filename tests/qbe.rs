@@ -0,0 +1,104 @@
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{dir, looping, neitc};
+use neitc::{backend::Backend, emit_qbe, target::Target};
+
+const SOURCE: &str = "cimport cstd\n__wrt__(stdout, \"say \\\"hi\\\"\\n\", 9)\n";
+
+fn compile(line_directives: bool) -> String {
+    common::compile(
+        SOURCE,
+        Backend::Qbe,
+        "x86_64-unknown-linux-gnu",
+        line_directives,
+    )
+}
+
+/// Build `file` with a backend and run it, `None` when the backend's tools
+/// are missing
+fn run(file: &Path, backend: &str) -> Option<Vec<u8>> {
+    let out = neitc(&["run", &format!("--backend={}", backend)], file);
+    if String::from_utf8_lossy(&out.stderr).contains("not available") {
+        return None;
+    }
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    Some(out.stdout)
+}
+
+#[test]
+fn programs_become_qbe_il() {
+    assert_eq!(
+        compile(false),
+        "data $d0 = { b \"say \", b 34, b \"hi\", b 34, b 10, b 0 }\n\
+         \n\
+         export function w $main() {\n\
+         @start\n\
+         @bb0\n    \
+         call $write(w 1, l $d0, l 9)\n    \
+         ret 0\n\
+         }\n"
+    );
+}
+
+#[test]
+fn statements_carry_their_line() {
+    let ssa = compile(true);
+    assert!(ssa.starts_with("dbgfile \"hello.nc\"\n\n"), "{}", ssa);
+    assert!(
+        ssa.contains("@bb0\n    dbgloc 2\n    call $write("),
+        "{}",
+        ssa
+    );
+}
+
+#[test]
+fn words_are_extended_to_compare_with_longs() {
+    let ssa = emit_qbe(&looping(), &Target::host(), false);
+    assert!(
+        ssa.contains(
+            "@bb1\n    \
+         %c0 =l extsw %t0\n    \
+         %t2 =w csltl %c0, 5\n    \
+         jnz %t2, @bb2, @bb3\n\
+         @bb2\n    \
+         %t1 =w call $add(w %t1, w %t0)\n    \
+         %t0 =w add %t0, 1\n    \
+         jmp @bb1\n\
+         @bb3\n    \
+         ret %t1\n"
+        ),
+        "{}",
+        ssa
+    );
+}
+
+#[test]
+fn backends_agree_on_program_output() {
+    let dir = dir("qbe");
+    let programs = [
+        SOURCE,
+        "cimport cstd\n__wrt__(stdout, \"a\\tb\\x41\\101\\n\", 7)\n__wrt__(stdout, \"more\\n\", 5)\n",
+        "cimport cstd\n__wrt__(stdout, \"cut short\", 3)\n",
+    ];
+    for (i, program) in programs.iter().enumerate() {
+        let file = dir.join(format!("program{}.nc", i));
+        fs::write(&file, program).unwrap();
+        let c = run(&file, "c").unwrap();
+        if let Some(qbe) = run(&file, "qbe") {
+            assert_eq!(qbe, c, "{}", program);
+        }
+    }
+
+    let out = neitc(
+        &["emit", "--stage=qbe", "--target", "x86_64-pc-windows-msvc"],
+        &dir.join("program0.nc"),
+    );
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("outside Windows"));
+}
@@ -0,0 +1,58 @@
+use neitc::{analyze, lexer::lexer, parse1, Options};
+
+/// Codes of every error in `source`, in order
+fn codes(source: &str) -> Vec<String> {
+    analyze(source, &Options::default())
+        .unwrap_err()
+        .diagnostics
+        .into_iter()
+        .map(|diag| diag.code.unwrap_or_default())
+        .collect()
+}
+
+#[test]
+fn every_independent_mistake_is_reported() {
+    let source = "cimport cstdd, link:m\n\
+                  hello world\n\
+                  __wrte__(stdout, \"x\", 1)\n\
+                  __wrt__(stdin, \"hi\", two); __wrt__(stdout, \"ok\", 2)\n\
+                  __wrt__(stdout, \"hi\", 2) junk\n\
+                  }\n";
+    assert_eq!(
+        codes(source),
+        ["N0002", "N0003", "N0005", "N0006", "N0006", "N0003", "N0003"]
+    );
+}
+
+#[test]
+fn statements_after_an_error_still_parse() {
+    let source = "cimport cstd\n__wrt__(stdin, \"a\", 1); __wrt__(stdout, \"b\", 1)\n__wrt__(stderr, \"c\", 1)\n";
+    let (tokens, _) = lexer(source);
+    let (ast, errors) = parse1::parse(&tokens, source);
+    assert_eq!(errors.len(), 1);
+    let calls = ast
+        .iter()
+        .filter(|node| matches!(node, parse1::AST::CWrt(_)))
+        .count();
+    assert_eq!(calls, 2);
+    assert!(matches!(
+        ast[1],
+        parse1::AST::Poisoned(parse1::Item::Call, _)
+    ));
+}
+
+#[test]
+fn follow_on_errors_are_suppressed() {
+    // The broken import may have been meant as cstd
+    assert_eq!(
+        codes("cimport cstdd\n__wrt__(stdout, \"hi\", 2)\n"),
+        ["N0002"]
+    );
+    // The lexer already reported the string, the call isn't reported again
+    assert_eq!(
+        codes("cimport cstd\n__wrt__(stdout, \"hi, 2)\n__wrt__(stdout, \"ok\", 2)\n"),
+        ["N0004"]
+    );
+    // One error per library
+    assert_eq!(codes("cimport cs((td, cstd\n"), ["N0001"]);
+}
@@ -0,0 +1,150 @@
+use neitc::{
+    diag::{Applicability, Diagnostic, SourceFile, Span},
+    json::Json,
+    report::{format, ErrorFormat},
+};
+
+fn invalid_library() -> (Diagnostic, SourceFile) {
+    let file = SourceFile::new("hello.nc", "cimport cstdd\n");
+    let diag = Diagnostic::error("Invalid library 'cstdd'")
+        .with_code("N0002")
+        .with_label(Span::new(8, 13), "not a known library")
+        .with_secondary(Span::new(0, 7), "imported here")
+        .with_help("the only library is 'cstd'")
+        .with_suggestion(
+            Span::new(8, 13),
+            "cstd",
+            "did you mean 'cstd'?",
+            Applicability::MachineApplicable,
+        );
+    (diag, file)
+}
+
+#[test]
+fn json_is_one_object_per_line() {
+    let (diag, file) = invalid_library();
+    let out = format(&[diag.clone(), diag], Some(&file), ErrorFormat::Json);
+    let line = "{\"code\":\"N0002\",\"severity\":\"error\",\"message\":\"Invalid library 'cstdd'\",\
+        \"file\":\"hello.nc\",\"spans\":[\
+        {\"start\":8,\"end\":13,\"line_start\":1,\"column_start\":9,\"line_end\":1,\"column_end\":14,\
+        \"primary\":true,\"label\":\"not a known library\"},\
+        {\"start\":0,\"end\":7,\"line_start\":1,\"column_start\":1,\"line_end\":1,\"column_end\":8,\
+        \"primary\":false,\"label\":\"imported here\"}],\
+        \"notes\":[],\"help\":[\"the only library is 'cstd'\"],\
+        \"suggestions\":[{\"message\":\"did you mean 'cstd'?\",\"replacement\":\"cstd\",\
+        \"applicability\":\"machine-applicable\",\
+        \"span\":{\"start\":8,\"end\":13,\"line_start\":1,\"column_start\":9,\"line_end\":1,\"column_end\":14}}]}\n";
+    assert_eq!(out, format!("{}{}", line, line));
+}
+
+#[test]
+fn json_without_a_file() {
+    let diag = Diagnostic::error("No C compiler found");
+    assert_eq!(
+        format(&[diag], None, ErrorFormat::Json),
+        "{\"code\":null,\"severity\":\"error\",\"message\":\"No C compiler found\",\"file\":null,\
+         \"spans\":[],\"notes\":[],\"help\":[],\"suggestions\":[]}\n"
+    );
+}
+
+#[test]
+fn sarif_holds_every_diagnostic() {
+    let (diag, file) = invalid_library();
+    let out = format(&[diag], Some(&file), ErrorFormat::Sarif);
+    let expected = r#"{
+  "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+  "version": "2.1.0",
+  "runs": [
+    {
+      "tool": {
+        "driver": {
+          "name": "neitc",
+          "version": "VERSION",
+          "rules": [
+            {
+              "id": "N0002"
+            }
+          ]
+        }
+      },
+      "results": [
+        {
+          "ruleId": "N0002",
+          "level": "error",
+          "message": {
+            "text": "Invalid library 'cstdd'\nhelp: the only library is 'cstd'"
+          },
+          "locations": [
+            {
+              "physicalLocation": {
+                "artifactLocation": {
+                  "uri": "hello.nc"
+                },
+                "region": {
+                  "startLine": 1,
+                  "startColumn": 9,
+                  "endLine": 1,
+                  "endColumn": 14
+                }
+              }
+            }
+          ],
+          "relatedLocations": [
+            {
+              "physicalLocation": {
+                "artifactLocation": {
+                  "uri": "hello.nc"
+                },
+                "region": {
+                  "startLine": 1,
+                  "startColumn": 1,
+                  "endLine": 1,
+                  "endColumn": 8
+                }
+              },
+              "message": {
+                "text": "imported here"
+              }
+            }
+          ],
+          "fixes": [
+            {
+              "description": {
+                "text": "did you mean 'cstd'?"
+              },
+              "artifactChanges": [
+                {
+                  "artifactLocation": {
+                    "uri": "hello.nc"
+                  },
+                  "replacements": [
+                    {
+                      "deletedRegion": {
+                        "startLine": 1,
+                        "startColumn": 9,
+                        "endLine": 1,
+                        "endColumn": 14
+                      },
+                      "insertedContent": {
+                        "text": "cstd"
+                      }
+                    }
+                  ]
+                }
+              ]
+            }
+          ]
+        }
+      ]
+    }
+  ]
+}
+"#;
+    assert_eq!(out, expected.replace("VERSION", env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn json_strings_are_escaped() {
+    let value = Json::object([("text", Json::string("say \"hi\"\n\\\u{1}"))]);
+    assert_eq!(value.compact(), r#"{"text":"say \"hi\"\n\\\u0001"}"#);
+}
@@ -0,0 +1,74 @@
+use neitc::{
+    analyze,
+    diag::Applicability,
+    suggest::{best_match, edit_distance, BUILTINS, LIBRARIES, STREAMS},
+    Options,
+};
+
+#[test]
+fn edit_distance_counts_typos() {
+    assert_eq!(edit_distance("cstd", "cstd"), 0);
+    assert_eq!(edit_distance("cstdd", "cstd"), 1);
+    assert_eq!(edit_distance("cimprot", "cimport"), 1);
+    assert_eq!(edit_distance("stdot", "stdout"), 1);
+    assert_eq!(edit_distance("", "abc"), 3);
+}
+
+#[test]
+fn only_close_names_match() {
+    assert_eq!(best_match("__wrte__", BUILTINS), Some("__wrt__"));
+    assert_eq!(best_match("_wrt_", BUILTINS), Some("__wrt__"));
+    assert_eq!(best_match("stderrr", STREAMS), Some("stderr"));
+    assert_eq!(best_match("stdin", STREAMS), None);
+    assert_eq!(best_match("math", LIBRARIES), None);
+}
+
+/// The first suggestion for `source` as `(replaced text, replacement)`,
+/// checking how sure it is
+fn suggestion_with(source: &str, applicability: Applicability) -> Option<(String, String)> {
+    let err = analyze(source, &Options::default()).unwrap_err();
+    let suggestion = err.diagnostics[0].suggestions.first()?;
+    assert_eq!(suggestion.applicability, applicability);
+    let span = suggestion.span;
+    Some((
+        source[span.start..span.end].to_string(),
+        suggestion.replacement.clone(),
+    ))
+}
+
+fn suggestion(source: &str) -> Option<(String, String)> {
+    suggestion_with(source, Applicability::MachineApplicable)
+}
+
+#[test]
+fn diagnostics_suggest_fixes() {
+    let fix = |a: &str, b: &str| Some((a.to_string(), b.to_string()));
+    assert_eq!(suggestion("cimport cstdd\n"), fix("cstdd", "cstd"));
+    assert_eq!(suggestion("cimprot cstd\n"), fix("cimprot", "cimport"));
+    assert_eq!(
+        suggestion("cimport cstd\n__wrte__(stdout, \"hi\", 2)\n"),
+        fix("__wrte__", "__wrt__")
+    );
+    assert_eq!(
+        suggestion("cimport cstd\n__wrt__(STDOT, \"hi\", 2)\n"),
+        fix("STDOT", "stdout")
+    );
+    assert_eq!(
+        suggestion("cimport cstd\n__print__(stdout, \"hi\", 2)\n"),
+        None
+    );
+}
+
+#[test]
+fn distant_guesses_are_not_applied_by_fix() {
+    let fix = |a: &str, b: &str| Some((a.to_string(), b.to_string()));
+    assert_eq!(
+        suggestion_with(
+            "cimport cstd\n_wrt_(stdout, \"hi\", 2)\n",
+            Applicability::MaybeIncorrect
+        ),
+        fix("_wrt_", "__wrt__")
+    );
+    let source = "cimport cstd\n_wrt_(stdout, \"hi\", 2)\n";
+    assert_eq!(neitc::fix::fix(source, &Options::default()).source, source);
+}
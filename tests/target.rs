@@ -0,0 +1,76 @@
+use neitc::{compile_str, target::Target, Options};
+
+fn compile_for(source: &str, triple: &str) -> String {
+    let options = Options {
+        target: Target::parse(triple).unwrap(),
+        ..Options::default()
+    };
+    compile_str(source, options).unwrap().code
+}
+
+#[test]
+fn parses_target_triples() {
+    let linux = Target::parse("x86_64-unknown-linux-gnu").unwrap();
+    assert_eq!(
+        (linux.arch.as_str(), linux.os.as_str(), linux.env.as_str()),
+        ("x86_64", "linux", "gnu")
+    );
+
+    let mingw = Target::parse("x86_64-w64-mingw32").unwrap();
+    assert_eq!((mingw.os.as_str(), mingw.env.as_str()), ("windows", "gnu"));
+    assert_eq!(mingw.exe_extension(), "exe");
+
+    let bsd = Target::parse("x86_64-unknown-freebsd").unwrap();
+    assert_eq!(
+        (bsd.os.as_str(), bsd.gcc_prefix().as_str()),
+        ("freebsd", "x86_64-unknown-freebsd")
+    );
+
+    let wasi = Target::parse("wasm32-wasip1").unwrap();
+    assert_eq!((wasi.arch.as_str(), wasi.os.as_str()), ("wasm32", "wasi"));
+    assert_eq!((wasi.exe_extension(), wasi.pointer_width()), ("wasm", 32));
+
+    assert!(Target::parse("x86_64").is_err());
+    assert!(Target::parse("x86_64-unknown-plan9").is_err());
+}
+
+#[test]
+fn host_includes_the_environment() {
+    let host = Target::host();
+    assert!(host.is_host());
+    let other_env = if host.env == "musl" { "gnu" } else { "musl" };
+    let other = Target::parse(&format!("{}-unknown-{}-{}", host.arch, host.os, other_env));
+    if let Ok(other) = other {
+        assert!(!other.is_host());
+    }
+    // Without an environment the host's is assumed
+    if let Ok(bare) = Target::parse(&format!("{}-unknown-{}", host.arch, host.os)) {
+        assert!(bare.is_host());
+    }
+}
+
+#[test]
+fn streams_map_to_file_descriptors() {
+    let c = compile_for(
+        "cimport cstd\n__wrt__(stdout,\"out\",3)\n__wrt__(stderr,\"err\",3)",
+        "x86_64-unknown-linux-gnu",
+    );
+    assert!(c.contains("write(1, \"out\", 3);"));
+    assert!(c.contains("write(2, \"err\", 3);"));
+}
+
+#[test]
+fn target_attributes_select_items() {
+    let source = "cimport cstd\n#[target_os = \"linux\"]\n__wrt__(stdout,\"linux\",5)\n#[target_arch = \"aarch64\"]\n__wrt__(stdout,\"arm\",3)";
+    let c = compile_for(source, "x86_64-unknown-linux-gnu");
+    assert!(c.contains("\"linux\"") && !c.contains("\"arm\""));
+
+    let c = compile_for(source, "aarch64-unknown-freebsd");
+    assert!(!c.contains("\"linux\"") && c.contains("\"arm\""));
+
+    assert!(compile_str(
+        "#[target_vendor = \"pc\"]\ncimport cstd",
+        Options::default()
+    )
+    .is_err());
+}
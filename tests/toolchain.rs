@@ -0,0 +1,81 @@
+use std::{env, path::Path};
+
+use neitc::{
+    target::Target,
+    toolchain::{Flavor, Toolchain},
+};
+
+fn args_of(toolchain: &Toolchain, cflags: &[&str]) -> Vec<String> {
+    let cflags: Vec<String> = cflags.iter().map(|flag| flag.to_string()).collect();
+    let cmd = toolchain.command(
+        Path::new("a.c"),
+        Path::new("a"),
+        &cflags,
+        &["m".to_string()],
+    );
+    cmd.get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn missing_compilers_are_not_detected() {
+    assert_eq!(Toolchain::probe("neitc-no-such-compiler"), None);
+    assert!(Toolchain::detect(Some("neitc-no-such-compiler"), &Target::host()).is_err());
+}
+
+#[test]
+fn flags_follow_the_compiler_dialect() {
+    let gcc = Toolchain {
+        program: "gcc".into(),
+        args: vec![],
+        flavor: Flavor::Gcc,
+    };
+    assert_eq!(
+        args_of(&gcc, &["-O2", "-g"]),
+        ["-O2", "-g", "a.c", "-o", "a", "-lm"]
+    );
+
+    let tcc = Toolchain {
+        program: "tcc".into(),
+        args: vec![],
+        flavor: Flavor::Tcc,
+    };
+    assert_eq!(
+        args_of(&tcc, &["-O2", "-g"]),
+        ["-g", "a.c", "-o", "a", "-lm"]
+    );
+
+    let wrapped = Toolchain {
+        program: "ccache".into(),
+        args: vec!["gcc".into()],
+        flavor: Flavor::Gcc,
+    };
+    assert_eq!(args_of(&wrapped, &[])[0], "gcc");
+}
+
+#[test]
+fn host_compilers_are_not_used_to_cross_compile() {
+    let Some(gcc) = Toolchain::probe("gcc").filter(|gcc| gcc.flavor == Flavor::Gcc) else {
+        return;
+    };
+    let Some(machine) = gcc
+        .machine()
+        .and_then(|machine| Target::parse(&machine).ok())
+    else {
+        return;
+    };
+    assert!(gcc.builds_for(&machine));
+    let mingw = Target::parse("x86_64-w64-mingw32").unwrap();
+    if machine.is_windows() {
+        return;
+    }
+    assert!(!gcc.builds_for(&mingw));
+
+    // `$CC` pointing at the host compiler is skipped for other targets
+    env::set_var("CC", "gcc");
+    if let Ok(toolchain) = Toolchain::detect(None, &mingw) {
+        assert!(toolchain.builds_for(&mingw), "{:?}", toolchain);
+    }
+    env::remove_var("CC");
+}
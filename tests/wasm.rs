@@ -0,0 +1,142 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use common::{available, dir, looping, neitc, SOURCE};
+use neitc::{backend::Backend, emit_wasm};
+
+fn compile(line_directives: bool) -> String {
+    common::compile(SOURCE, Backend::Wasm, "wasm32-wasi", line_directives)
+}
+
+#[test]
+fn programs_write_through_wasi() {
+    assert_eq!(
+        compile(false),
+        "(module\n  \
+         (import \"wasi_snapshot_preview1\" \"fd_write\" (func $fd_write (param i32 i32 i32 i32) (result i32)))\n  \
+         (import \"wasi_snapshot_preview1\" \"proc_exit\" (func $proc_exit (param i32)))\n  \
+         (memory (export \"memory\") 1)\n  \
+         (data (i32.const 16) \"hi\\0a\\00\")\n\
+         \n  \
+         (func $main (result i32)\n    \
+         i32.const 0\n    \
+         i32.const 16\n    \
+         i32.store\n    \
+         i32.const 0\n    \
+         i32.const 3\n    \
+         i32.store offset=4\n    \
+         i32.const 1\n    \
+         i32.const 0\n    \
+         i32.const 1\n    \
+         i32.const 8\n    \
+         call $fd_write\n    \
+         drop\n    \
+         i32.const 0\n    \
+         return\n  \
+         )\n\
+         \n  \
+         (func $_start (export \"_start\")\n    \
+         call $main\n    \
+         call $proc_exit\n  \
+         )\n\
+         )\n"
+    );
+}
+
+#[test]
+fn statements_carry_their_line() {
+    let wat = compile(true);
+    assert!(
+        wat.contains("(func $main (result i32)\n    ;; hello.nc:2:1\n    i32.const 0\n"),
+        "{}",
+        wat
+    );
+}
+
+#[test]
+fn loops_dispatch_on_the_next_block() {
+    let wat = emit_wasm(&looping(), false);
+    assert!(wat.contains("(func $add (param $t0 i32) (param $t1 i32) (result i32)\n"));
+    assert!(wat.contains(
+        "    (local $bb i32)\n    \
+         loop $dispatch\n      \
+         block $bb3\n        \
+         block $bb2\n          \
+         block $bb1\n            \
+         block $bb0\n              \
+         local.get $bb\n              \
+         br_table $bb0 $bb1 $bb2 $bb3\n"
+    ));
+    // The comparison is made in the type of its widest operand
+    assert!(wat.contains(
+        "local.get $t0\n          i64.extend_i32_s\n          i64.const 5\n          i64.lt_s\n"
+    ));
+    assert!(wat.contains(
+        "          local.get $t2\n          i32.eqz\n          br_if $bb3\n        end\n"
+    ));
+    assert!(wat.contains("i32.const 1\n        local.set $bb\n        br $dispatch\n"));
+    // The loop is left by returning, never by falling off its end
+    assert!(wat.contains("    end\n    unreachable\n  )\n\n  (func $add "));
+    // Without writes there is nothing to import for them
+    assert!(!wat.contains("fd_write"));
+
+    if !available("wat2wasm") || !available("wasmtime") {
+        return;
+    }
+    let dir = dir("wasm");
+    fs::write(dir.join("loop.wat"), &wat).unwrap();
+    let status = Command::new("wat2wasm")
+        .arg(dir.join("loop.wat"))
+        .arg("-o")
+        .arg(dir.join("loop.wasm"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // 0 + 1 + 2 + 3 + 4
+    let status = Command::new("wasmtime")
+        .arg("run")
+        .arg(dir.join("loop.wasm"))
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(10));
+}
+
+#[test]
+fn programs_target_wasi_by_default() {
+    let file = dir("wasm").join("hello.nc");
+    fs::write(&file, SOURCE).unwrap();
+    let out = neitc(&["emit", "--stage=wasm"], &file);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(String::from_utf8_lossy(&out.stdout).starts_with("(module\n"));
+
+    let out = neitc(
+        &[
+            "build",
+            "--backend=wasm",
+            "--target",
+            "x86_64-unknown-linux-gnu",
+        ],
+        &file,
+    );
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr)
+        .contains("The wasm backend can't generate code for x86_64-unknown-linux-gnu"));
+
+    if !available("wasmtime") && !available("wasm-interp") {
+        let out = neitc(&["run", "--backend=wasm"], &file);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("Running WebAssembly needs"));
+    } else if available("wat2wasm") || available("wasm-tools") {
+        let out = neitc(&["run", "--backend=wasm"], &file);
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        assert_eq!(out.stdout, b"hi\n");
+    }
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use neitc::{backend::Backend, target::Target, toolchain::Toolchain};
+
+const HELLO: &str = "cimport cstd\n__wrt__(stdout,\"hi\\n\",3)\n__wrt__(stderr,\"oops\\n\",5)";
+
+fn compile_for(triple: &str) -> String {
+    common::compile(HELLO, Backend::C, triple, false)
+}
+
+#[test]
+fn windows_prelude_uses_the_crt() {
+    for triple in [
+        "x86_64-pc-windows-msvc",
+        "x86_64-pc-windows-gnu",
+        "x86_64-w64-mingw32",
+    ] {
+        let c = compile_for(triple);
+        assert!(c.contains("#include <io.h>"), "{}", c);
+        assert!(!c.contains("unistd.h"), "{}", c);
+        assert!(c.contains("_write(1, \"hi\\n\", 3);"), "{}", c);
+        assert!(c.contains("_write(2, \"oops\\n\", 5);"), "{}", c);
+    }
+}
+
+#[test]
+fn cross_compiles_with_mingw() {
+    let target = Target::parse("x86_64-w64-mingw32").unwrap();
+    // `$CC` or clang may be found without a mingw sysroot to build against
+    let mingw = Toolchain::detect(None, &target).ok().filter(|toolchain| {
+        toolchain
+            .machine()
+            .is_some_and(|machine| machine.ends_with("-w64-mingw32"))
+    });
+    let Some(toolchain) = mingw else {
+        eprintln!("skipping: no mingw cross compiler installed");
+        return;
+    };
+    let dir = common::dir("windows");
+    let c_file = dir.join("hello.c");
+    let binary = dir.join("hello.exe");
+    fs::write(&c_file, compile_for(&target.triple)).unwrap();
+
+    let out = toolchain
+        .command(&c_file, &binary, &[], &[])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(binary.exists());
+    // Nothing beyond the build can be checked on Linux, unless wine is around
+    if let Ok(run) = Command::new("wine").arg(&binary).output() {
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "hi\n");
+    }
+    fs::remove_dir_all(&dir).ok();
+}